@@ -133,6 +133,15 @@ fn benchmark_where_operations(c: &mut Criterion) {
         })
     });
 
+    // Same query compiled once, reusing the per-thread JavaScript context
+    let where_filter = create_filter(&where_query).unwrap();
+    group.bench_function("$where logic (compiled)", |b| {
+        b.iter(|| {
+            let count = data.iter().filter(|item| where_filter(black_box(item))).count();
+            black_box(count)
+        })
+    });
+
     group.finish();
 }
 
@@ -246,6 +255,17 @@ fn benchmark_array_operations(c: &mut Criterion) {
         })
     });
 
+    // Test $in with a large list of ids
+    let ids: Vec<_> = (0..5_000).map(|id| json!(format!("EMP-{:03}", id))).collect();
+    let large_in_query = json!({"company.employees.id": {"$in": ids}});
+    let large_in_filter = create_filter(&large_in_query).unwrap();
+    group.bench_function("$in operator (5000 ids)", |b| {
+        b.iter(|| {
+            let count = data.iter().filter(|item| large_in_filter(black_box(item))).count();
+            black_box(count)
+        })
+    });
+
     // Test $all operator
     let all_query = json!({"company.employees.0.skills": {"$all": ["leadership", "strategy"]}});
     group.bench_function("$all operator", |b| {
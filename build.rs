@@ -0,0 +1,14 @@
+//! Generates the gRPC service from `proto/sift.proto` when the `grpc` feature is enabled
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/sift.proto");
+        let descriptors = protox::compile(["sift.proto"], ["proto"]).expect("Failed to parse proto/sift.proto");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("Failed to generate the gRPC service");
+    }
+}
@@ -94,16 +94,3 @@ impl WebSocketMessage {
         }
     }
 }
-
-/// Sift validation request structure
-#[derive(Debug, Serialize)]
-pub struct SiftValidationRequest {
-    pub input: serde_json::Value,
-    pub query: serde_json::Value,
-}
-
-/// Sift validation response structure
-#[derive(Debug, Deserialize)]
-pub struct SiftValidationResponse {
-    pub valid: bool,
-}
@@ -0,0 +1,236 @@
+//! Typed async client for the sift-rs API server
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), sift_rs_client::Error> {
+//! use serde_json::json;
+//! use sift_rs_client::SiftClient;
+//!
+//! let client = SiftClient::new("http://localhost:3000").with_api_key("change-me");
+//! let adult = client.matches(&json!({"age": {"$gte": 18}}), &json!({"age": 30})).await?;
+//! assert!(adult);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod models;
+
+pub use models::*;
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Errors returned by [`SiftClient`]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request could not be sent or the response could not be read
+    #[error("request to sift-rs failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server answered with an error status
+    #[error("sift-rs returned {status} {}: {}", .body.error, .body.message)]
+    Api { status: StatusCode, body: ErrorResponse },
+    /// The server could not evaluate one validation item
+    #[error("sift-rs rejected the query ({}): {}", .0.code, .0.message)]
+    Item(ItemError),
+}
+
+impl Error {
+    /// The server's error code, e.g. `InvalidQuery`, when it answered with one
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Error::Http(_) => None,
+            Error::Api { body, .. } => Some(&body.error),
+            Error::Item(error) => Some(&error.code),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Client for one sift-rs server
+#[derive(Clone, Debug)]
+pub struct SiftClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl SiftClient {
+    /// Create a client for the server at `base_url`, e.g. `http://localhost:3000`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        SiftClient::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Create a client sending requests through `http`, e.g. to set timeouts
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        SiftClient {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+        }
+    }
+
+    /// Send `key` as a bearer token with every request
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// GET /health
+    pub async fn health(&self) -> Result<HealthResponse> {
+        self.send(self.request(Method::GET, "/health")).await
+    }
+
+    /// POST /validate, validating each input/query pair independently
+    pub async fn validate(&self, items: &[ValidationItem]) -> Result<Vec<ValidationResult>> {
+        self.send(self.request(Method::POST, "/validate").json(items)).await
+    }
+
+    /// POST /validate?dedupe=true, compiling each distinct query once
+    pub async fn validate_deduped(&self, items: &[ValidationItem]) -> Result<Vec<ValidationResult>> {
+        let request = self.request(Method::POST, "/validate").query(&[("dedupe", "true")]);
+        self.send(request.json(items)).await
+    }
+
+    /// Whether `input` matches `query`, validated through `POST /validate`
+    pub async fn matches(&self, query: &Value, input: &Value) -> Result<bool> {
+        let items = [ValidationItem {
+            input: input.clone(),
+            query: query.clone(),
+        }];
+        match self.validate(&items).await?.into_iter().next() {
+            Some(ValidationResult::Valid { valid }) => Ok(valid),
+            Some(ValidationResult::Error { error }) => Err(Error::Item(error)),
+            None => Ok(false),
+        }
+    }
+
+    /// POST /filter
+    pub async fn filter(&self, request: &FilterRequest) -> Result<FilterResponse> {
+        self.send(self.request(Method::POST, "/filter").json(request)).await
+    }
+
+    /// POST /lint, returning the problems found in `query`
+    pub async fn lint(&self, query: &Value) -> Result<Vec<Diagnostic>> {
+        #[derive(serde::Deserialize)]
+        struct LintResponse {
+            diagnostics: Vec<Diagnostic>,
+        }
+
+        let request = self.request(Method::POST, "/lint").json(&json!({ "query": query }));
+        let response: LintResponse = self.send(request).await?;
+        Ok(response.diagnostics)
+    }
+
+    /// GET /queries
+    pub async fn list_queries(&self) -> Result<Vec<StoredQuery>> {
+        self.send(self.request(Method::GET, "/queries")).await
+    }
+
+    /// GET /queries/{name}
+    pub async fn get_query(&self, name: &str) -> Result<StoredQuery> {
+        self.send(self.request(Method::GET, &format!("/queries/{}", name))).await
+    }
+
+    /// PUT /queries/{name}, replacing any query already stored under `name`
+    pub async fn put_query(&self, name: &str, query: &Value) -> Result<StoredQuery> {
+        let request = self.request(Method::PUT, &format!("/queries/{}", name));
+        self.send(request.json(&json!({ "query": query }))).await
+    }
+
+    /// DELETE /queries/{name}
+    pub async fn delete_query(&self, name: &str) -> Result<()> {
+        self.send_empty(self.request(Method::DELETE, &format!("/queries/{}", name)))
+            .await
+    }
+
+    /// POST /queries/{name}/match
+    pub async fn match_query(&self, name: &str, documents: &[Value]) -> Result<MatchResponse> {
+        let request = self.request(Method::POST, &format!("/queries/{}/match", name));
+        self.send(request.json(&json!({ "documents": documents }))).await
+    }
+
+    /// GET /collections
+    pub async fn list_collections(&self) -> Result<Vec<CollectionInfo>> {
+        self.send(self.request(Method::GET, "/collections")).await
+    }
+
+    /// DELETE /collections/{collection}
+    pub async fn drop_collection(&self, collection: &str) -> Result<()> {
+        self.send_empty(self.request(Method::DELETE, &format!("/collections/{}", collection)))
+            .await
+    }
+
+    /// POST /collections/{collection}/documents
+    pub async fn insert(&self, collection: &str, documents: &[Value]) -> Result<InsertResponse> {
+        let request = self.request(Method::POST, &format!("/collections/{}/documents", collection));
+        self.send(request.json(&json!({ "documents": documents }))).await
+    }
+
+    /// POST /collections/{collection}/find
+    pub async fn find(&self, collection: &str, request: &FindRequest) -> Result<FilterResponse> {
+        let builder = self.request(Method::POST, &format!("/collections/{}/find", collection));
+        self.send(builder.json(request)).await
+    }
+
+    /// PATCH /collections/{collection}/update
+    pub async fn update(&self, collection: &str, request: &UpdateRequest) -> Result<UpdateResponse> {
+        let builder = self.request(Method::PATCH, &format!("/collections/{}/update", collection));
+        self.send(builder.json(request)).await
+    }
+
+    /// POST /collections/{collection}/delete
+    pub async fn delete(&self, collection: &str, request: &DeleteRequest) -> Result<DeleteResponse> {
+        let builder = self.request(Method::POST, &format!("/collections/{}/delete", collection));
+        self.send(builder.json(request)).await
+    }
+
+    /// POST /publish, routing documents to live-query subscribers
+    pub async fn publish(&self, documents: &[Value]) -> Result<PublishResponse> {
+        let request = self.request(Method::POST, "/publish");
+        self.send(request.json(&json!({ "documents": documents }))).await
+    }
+
+    /// GET /openapi.json
+    pub async fn openapi(&self) -> Result<Value> {
+        self.send(self.request(Method::GET, "/openapi.json")).await
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = check(request.send().await?).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn send_empty(&self, request: RequestBuilder) -> Result<()> {
+        check(request.send().await?).await?;
+        Ok(())
+    }
+}
+
+/// Turn an error status into [`Error::Api`]
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    // Errors from outside the handlers, e.g. an oversized body, are plain text
+    let text = response.text().await?;
+    let body = serde_json::from_str(&text).unwrap_or_else(|_| ErrorResponse {
+        error: status.canonical_reason().unwrap_or("Error").replace(' ', ""),
+        message: text,
+    });
+    Err(Error::Api { status, body })
+}
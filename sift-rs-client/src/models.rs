@@ -0,0 +1,161 @@
+//! Request and response bodies of the sift-rs API
+//!
+//! These mirror the schemas in the server's `GET /openapi.json`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Body of every error response
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ErrorResponse {
+    /// Machine-readable error code, e.g. `InvalidQuery` or `NotFound`
+    pub error: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+    pub message: String,
+}
+
+/// One input/query pair for `POST /validate`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ValidationItem {
+    pub input: Value,
+    pub query: Value,
+}
+
+/// Outcome for one validation item
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ValidationResult {
+    Valid { valid: bool },
+    Error { error: ItemError },
+}
+
+/// Why one validation item could not be evaluated
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ItemError {
+    /// The kind of error, e.g. `InvalidQuery` or `EvaluationError`
+    pub code: String,
+    pub message: String,
+    /// Location of the offending value in the request body, e.g. `$[2].query`
+    pub path: String,
+}
+
+/// Body of `POST /filter`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct FilterRequest {
+    pub query: Value,
+    pub documents: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projection: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// Documents selected by `POST /filter` or a collection find
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FilterResponse {
+    /// The matching documents after sort, skip, limit and projection
+    pub documents: Vec<Value>,
+    /// Total number of matching documents, before skip and limit
+    pub count: usize,
+}
+
+/// One problem `POST /lint` found in a query
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Diagnostic {
+    /// `info`, `warning`, or `error` when the query would be rejected
+    pub severity: String,
+    /// Name of the check, e.g. `ExistsNotBoolean`
+    pub code: String,
+    pub message: String,
+    /// Location of the offending value in the query, e.g. `$.age.$in`
+    pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct StoredQuery {
+    pub name: String,
+    pub query: Value,
+}
+
+/// Result of testing documents against a stored query
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct MatchResponse {
+    /// Whether each document matches, in request order
+    pub matches: Vec<bool>,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct InsertResponse {
+    /// The `_id` of each inserted document, in request order
+    pub inserted_ids: Vec<Value>,
+}
+
+/// Body of a collection find; an absent filter matches every document
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct FindRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projection: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// Body of a collection update
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct UpdateRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Value>,
+    /// Update operators such as `{"$set": {...}}`, or a replacement document
+    pub update: Value,
+    /// Update every matching document rather than only the first
+    pub multi: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UpdateResponse {
+    pub matched: usize,
+    pub modified: usize,
+}
+
+/// Body of a collection delete
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeleteRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Value>,
+    /// Delete only the first matching document
+    pub just_one: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DeleteResponse {
+    pub deleted: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PublishResponse {
+    pub published: usize,
+    /// Number of live-query connections the documents were delivered to
+    pub connections: usize,
+}
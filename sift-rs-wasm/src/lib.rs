@@ -15,14 +15,8 @@ pub struct JsSiftError {
 // Convert internal SiftError to a JavaScript-compatible error
 impl From<SiftError> for JsSiftError {
     fn from(err: SiftError) -> Self {
-        let error_type = match err {
-            SiftError::InvalidQuery(_) => "InvalidQuery".to_string(),
-            SiftError::InvalidValue(_) => "InvalidValue".to_string(),
-            SiftError::UnsupportedOperation(_) => "UnsupportedOperation".to_string(),
-            SiftError::SerializationError(_) => "SerializationError".to_string(),
-            SiftError::EvaluationError(_) => "EvaluationError".to_string(),
-        };
-        
+        let error_type = err.kind().to_string();
+
         JsSiftError {
             message: err.to_string(),
             error_type,
@@ -184,4 +178,21 @@ pub fn validate_query(query: &str) -> Result<bool, JsValue> {
         Ok(_) => Ok(true),
         Err(e) => Err(error_to_js_value(e)),
     }
-}
\ No newline at end of file
+}
+/// Checks a query for invalid or suspicious operator usage without running it
+/// 
+/// # Arguments
+/// 
+/// * `query` - The MongoDB-style query as a stringified JSON
+/// 
+/// # Returns
+/// 
+/// Returns a stringified JSON array of `{ severity, code, message, path }` diagnostics
+#[wasm_bindgen]
+pub fn lint_query(query: &str) -> Result<String, JsValue> {
+    let query_json: Value = serde_json::from_str(query)
+        .map_err(|e| JsValue::from_str(&format!("Invalid query JSON: {}", e)))?;
+    
+    serde_json::to_string(&sift_rs::lint(&query_json))
+        .map_err(|e| JsValue::from_str(&format!("Error serializing diagnostics: {}", e)))
+}
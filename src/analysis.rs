@@ -0,0 +1,260 @@
+//! Static facts about a query, for deciding whether to run it at all
+
+use crate::lint::child_path;
+use crate::query::{estimate_cost, COST_EQUALITY};
+use serde::Serialize;
+use serde_json::Value;
+
+/// What a query references and roughly how expensive it is, from [`Query::analyze`](crate::Query::analyze)
+///
+/// ```rust
+/// use serde_json::json;
+/// use sift_rs::Query;
+///
+/// let query = Query::from_value(&json!({
+///     "status": {"$in": ["active", "trial"]},
+///     "$or": [{"name": {"$regex": "^A"}}, {"orders": {"$elemMatch": {"total": {"$gt": 100}}}}]
+/// })).unwrap();
+/// let analysis = query.analyze();
+///
+/// assert_eq!(analysis.fields, ["status", "name", "orders", "orders.total"]);
+/// assert_eq!(analysis.depth, 3);
+/// assert!(analysis.uses_regex && !analysis.uses_where);
+/// assert_eq!(analysis.in_lists[0].values, 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueryAnalysis {
+    /// Every dotted field path the query reads, in the order they first appear
+    ///
+    /// Fields inside `$elemMatch` are prefixed with the array field, e.g. `orders.total`.
+    pub fields: Vec<String>,
+    /// Every operator the query uses, in query order; a literal value counts as `$eq`
+    pub operators: Vec<OperatorUse>,
+    /// Levels of nesting: 1 for a flat query, plus one for each `$and`, `$or`,
+    /// `$nor`, `$not` or `$elemMatch` nested inside another
+    pub depth: usize,
+    /// The number of values in each `$in`, `$nin` and `$all`
+    pub in_lists: Vec<InList>,
+    pub uses_where: bool,
+    pub uses_regex: bool,
+    /// Estimated per-document cost: the sum of [`estimate_cost`] over every
+    /// operator except the logical ones, whose cost is that of their children,
+    /// plus one for every 10 values of an `$in`, `$nin` or `$all`
+    pub cost: u32,
+}
+
+/// One use of an operator
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OperatorUse {
+    /// The operator, e.g. `$gt`
+    pub operator: String,
+    /// The field it tests, or `None` for a root-level `$and`, `$or`, `$nor`, `$where` or `$function`
+    pub field: Option<String>,
+    /// JSON path of the operator within the query, e.g. `$.age.$gt`
+    pub path: String,
+}
+
+/// The values of one `$in`, `$nin` or `$all`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InList {
+    pub operator: String,
+    pub field: String,
+    /// JSON path of the list within the query, e.g. `$.status.$in`
+    pub path: String,
+    /// Number of values in the list
+    pub values: usize,
+}
+
+/// Analyze a query in its MongoDB-style JSON form
+pub(crate) fn analyze(query: &Value) -> QueryAnalysis {
+    let mut analysis = QueryAnalysis::default();
+    Analyzer {
+        analysis: &mut analysis,
+    }
+    .query(query, "$", "", 1);
+    analysis
+}
+
+struct Analyzer<'a> {
+    analysis: &'a mut QueryAnalysis,
+}
+
+impl Analyzer<'_> {
+    fn operator_use(&mut self, operator: &str, field: Option<&str>, path: &str, operand: &Value) {
+        self.analysis.operators.push(OperatorUse {
+            operator: operator.to_string(),
+            field: field.map(str::to_string),
+            path: path.to_string(),
+        });
+        match operator {
+            "$and" | "$or" | "$nor" | "$not" => {}
+            "$regex" => {
+                self.analysis.uses_regex = true;
+                self.analysis.cost += estimate_cost(operator, operand);
+            }
+            "$where" => {
+                self.analysis.uses_where = true;
+                self.analysis.cost += estimate_cost(operator, operand);
+            }
+            // The fields inside add their own cost
+            "$elemMatch" => self.analysis.cost += COST_EQUALITY,
+            _ => self.analysis.cost += estimate_cost(operator, operand),
+        }
+    }
+
+    fn field(&mut self, field: &str) {
+        if !self.analysis.fields.iter().any(|seen| seen == field) {
+            self.analysis.fields.push(field.to_string());
+        }
+    }
+
+    fn nest(&mut self, depth: usize) {
+        self.analysis.depth = self.analysis.depth.max(depth);
+    }
+
+    /// Walk a query object at nesting `depth`; `prefix` is the array field inside `$elemMatch`
+    fn query(&mut self, query: &Value, path: &str, prefix: &str, depth: usize) {
+        self.nest(depth);
+        let Value::Object(clauses) = query else {
+            // A non-object query compares the whole document
+            return self.operator_use("$eq", None, path, query);
+        };
+        for (key, value) in clauses {
+            let child = child_path(path, key);
+            if key.starts_with('$') {
+                let field = (!prefix.is_empty()).then_some(prefix);
+                self.operator_use(key, field, &child, value);
+                if let ("$and" | "$or" | "$nor", Value::Array(queries)) = (key.as_str(), value) {
+                    for (index, query) in queries.iter().enumerate() {
+                        self.query(query, &format!("{}[{}]", child, index), prefix, depth + 1);
+                    }
+                }
+                continue;
+            }
+
+            let field = match prefix {
+                "" => key.clone(),
+                _ => format!("{}.{}", prefix, key),
+            };
+            self.field(&field);
+            self.condition(&field, value, &child, depth);
+        }
+    }
+
+    /// Walk the condition on one field
+    fn condition(&mut self, field: &str, condition: &Value, path: &str, depth: usize) {
+        let Value::Object(operators) = condition else {
+            return self.operator_use("$eq", Some(field), path, condition);
+        };
+        if !operators.keys().any(|key| key.starts_with('$')) {
+            return self.operator_use("$eq", Some(field), path, condition);
+        }
+        // Literal keys next to operators are compared as one embedded document
+        if operators.keys().any(|key| !key.starts_with('$')) {
+            self.operator_use("$eq", Some(field), path, &Value::Null);
+        }
+
+        for (operator, operand) in operators.iter().filter(|(key, _)| key.starts_with('$')) {
+            let child = child_path(path, operator);
+            self.operator_use(operator, Some(field), &child, operand);
+            match (operator.as_str(), operand) {
+                ("$in" | "$nin" | "$all", Value::Array(values)) => {
+                    self.analysis.cost += (values.len() / 10) as u32;
+                    self.analysis.in_lists.push(InList {
+                        operator: operator.clone(),
+                        field: field.to_string(),
+                        path: child,
+                        values: values.len(),
+                    });
+                }
+                ("$not", Value::Object(_)) => {
+                    self.nest(depth + 1);
+                    self.condition(field, operand, &child, depth + 1);
+                }
+                ("$elemMatch", Value::Object(query)) => {
+                    if query.keys().all(|key| key.starts_with('$')) {
+                        self.nest(depth + 1);
+                        self.condition(field, operand, &child, depth + 1);
+                    } else {
+                        self.query(operand, &child, field, depth + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{COST_RANGE, COST_REGEX, COST_WHERE};
+    use serde_json::json;
+
+    #[test]
+    fn test_flat_query() {
+        let analysis = analyze(&json!({"name": "ana", "age": {"$gte": 18, "$lt": 65}}));
+        assert_eq!(analysis.fields, ["name", "age"]);
+        assert_eq!(analysis.depth, 1);
+        let operators: Vec<_> = analysis
+            .operators
+            .iter()
+            .map(|used| (used.operator.as_str(), used.field.as_deref(), used.path.as_str()))
+            .collect();
+        assert_eq!(
+            operators,
+            [
+                ("$eq", Some("name"), "$.name"),
+                ("$gte", Some("age"), "$.age.$gte"),
+                ("$lt", Some("age"), "$.age.$lt"),
+            ]
+        );
+        assert_eq!(analysis.cost, COST_EQUALITY + 2 * COST_RANGE);
+        assert!(!analysis.uses_regex && !analysis.uses_where);
+    }
+
+    #[test]
+    fn test_nested_fields_and_depth() {
+        let analysis = analyze(&json!({
+            "$and": [
+                {"$or": [{"a.b": 1}, {"items": {"$elemMatch": {"qty": {"$not": {"$gt": 5}}}}}]},
+                {"tags": {"$elemMatch": {"$regex": "^x"}}},
+            ]
+        }));
+        assert_eq!(analysis.fields, ["a.b", "items", "items.qty", "tags"]);
+        // $and > $or > $elemMatch > $not
+        assert_eq!(analysis.depth, 5);
+        assert!(analysis.uses_regex);
+        let not = analysis.operators.iter().find(|used| used.operator == "$not").unwrap();
+        assert_eq!(not.field.as_deref(), Some("items.qty"));
+        assert_eq!(not.path, "$.$and[0].$or[1].items.$elemMatch.qty.$not");
+        assert_eq!(analysis.operators[0].field, None);
+    }
+
+    #[test]
+    fn test_in_lists_and_cost() {
+        let values: Vec<_> = (0..25).collect();
+        let analysis = analyze(&json!({"id": {"$in": values}, "tag": {"$nin": []}, "$where": "this.a > 1"}));
+        let lists: Vec<_> = analysis
+            .in_lists
+            .iter()
+            .map(|list| (list.operator.as_str(), list.field.as_str(), list.path.as_str(), list.values))
+            .collect();
+        assert_eq!(lists, [("$in", "id", "$.id.$in", 25), ("$nin", "tag", "$.tag.$nin", 0)]);
+        assert!(analysis.uses_where);
+        assert_eq!(analysis.cost, COST_RANGE + 2 + COST_RANGE + COST_WHERE);
+
+        let regex = analyze(&json!({"name": {"$regex": "a"}}));
+        assert_eq!(regex.cost, COST_REGEX);
+    }
+
+    #[test]
+    fn test_mixed_and_scalar_queries() {
+        let analysis = analyze(&json!({"user": {"$exists": true, "name": "ana"}}));
+        let operators: Vec<_> = analysis.operators.iter().map(|used| used.operator.as_str()).collect();
+        assert_eq!(operators, ["$eq", "$exists"]);
+
+        let analysis = analyze(&json!(5));
+        assert_eq!((analysis.fields.len(), analysis.operators[0].operator.as_str()), (0, "$eq"));
+    }
+}
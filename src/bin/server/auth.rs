@@ -0,0 +1,363 @@
+use crate::{api_error, ApiError, AppState, ErrorResponse};
+use axum::extract::{FromRequestParts, MatchedPath, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Header carrying an API key, as an alternative to `Authorization: Bearer`
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Routes that never require a key; `/metrics` stays open so Prometheus can scrape it
+pub const PUBLIC_ROUTES: &[&str] = &["/health", "/metrics", "/openapi.json"];
+
+/// Operators whose arguments are not queries, so their contents are not inspected
+const OPAQUE_OPERATORS: &[&str] = &["$where", "$function"];
+
+/// API keys accepted by the server, loaded from a JSON file
+///
+/// ```json
+/// {"keys": [{"name": "dashboard", "key": "...", "denied_operators": ["$where"], "max_batch_size": 1000}]}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct AuthConfig {
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKey {
+    /// Name identifying the key in logs
+    name: String,
+    key: String,
+    #[serde(flatten)]
+    policy: Policy,
+}
+
+/// Limits applied to the queries and batches sent with a key
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Policy {
+    /// Operators the key may use; any operator when absent
+    #[serde(default)]
+    allowed_operators: Option<HashSet<String>>,
+    /// Operators the key may never use, e.g. `$where` and `$regex`
+    #[serde(default)]
+    denied_operators: HashSet<String>,
+    /// Most documents or items accepted in one request
+    #[serde(default)]
+    max_batch_size: Option<usize>,
+    /// Deepest nesting of objects accepted in a query
+    #[serde(default)]
+    max_query_depth: Option<usize>,
+}
+
+/// A request that breaks the caller's policy
+#[derive(Debug, PartialEq)]
+pub struct PolicyViolation {
+    pub error: &'static str,
+    pub message: String,
+}
+
+impl From<PolicyViolation> for ApiError {
+    fn from(violation: PolicyViolation) -> Self {
+        api_error(StatusCode::FORBIDDEN, violation.error, &violation.message)
+    }
+}
+
+impl AuthConfig {
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let contents = std::fs::read(path)?;
+        let config: AuthConfig = serde_json::from_slice(&contents)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if let Some(key) = config.keys.iter().find(|key| key.key.is_empty()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("API key '{}' is empty", key.name),
+            ));
+        }
+        Ok(config)
+    }
+
+    fn find(&self, presented: &str) -> Option<&ApiKey> {
+        self.keys
+            .iter()
+            .find(|key| constant_time_eq(key.key.as_bytes(), presented.as_bytes()))
+    }
+
+    /// The caller identified by the key in `headers`, if it is a known key
+    ///
+    /// Keys are sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`;
+    /// gRPC clients send the same names as metadata.
+    pub fn caller(&self, headers: &HeaderMap) -> Option<Caller> {
+        let key = self.find(presented_key(headers)?)?;
+        Some(Caller {
+            name: Some(key.name.clone()),
+            policy: Arc::new(key.policy.clone()),
+        })
+    }
+}
+
+/// Compare secrets without returning early on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+impl Policy {
+    /// Check a query's operators and depth
+    pub fn check_query(&self, query: &Value) -> Result<(), PolicyViolation> {
+        let mut operators = Vec::new();
+        let depth = inspect(query, &mut operators);
+
+        for operator in operators {
+            let allowed = self
+                .allowed_operators
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(operator));
+            if !allowed || self.denied_operators.contains(operator) {
+                return Err(PolicyViolation {
+                    error: "OperatorNotAllowed",
+                    message: format!("Operator {} is not allowed for this API key", operator),
+                });
+            }
+        }
+
+        match self.max_query_depth {
+            Some(max) if depth > max => Err(PolicyViolation {
+                error: "QueryTooDeep",
+                message: format!("Query nesting depth {} exceeds the limit of {}", depth, max),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the number of documents or items in one request
+    pub fn check_batch(&self, size: usize) -> Result<(), PolicyViolation> {
+        match self.max_batch_size {
+            Some(max) if size > max => Err(PolicyViolation {
+                error: "BatchTooLarge",
+                message: format!("Batch of {} exceeds the limit of {}", size, max),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn max_batch_size(&self) -> Option<usize> {
+        self.max_batch_size
+    }
+}
+
+/// Collect the operators used in `value`, returning its object nesting depth
+fn inspect<'a>(value: &'a Value, operators: &mut Vec<&'a str>) -> usize {
+    match value {
+        Value::Object(fields) => {
+            let mut depth = 0;
+            for (key, field) in fields {
+                if key.starts_with('$') {
+                    operators.push(key);
+                    if OPAQUE_OPERATORS.contains(&key.as_str()) {
+                        continue;
+                    }
+                }
+                depth = depth.max(inspect(field, operators));
+            }
+            depth + 1
+        }
+        Value::Array(items) => items.iter().map(|item| inspect(item, operators)).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// The caller of a request and the policy that applies to it
+///
+/// Without authentication every request is anonymous and unrestricted.
+#[derive(Clone)]
+pub struct Caller {
+    name: Option<String>,
+    policy: Arc<Policy>,
+}
+
+impl Caller {
+    pub fn anonymous() -> Self {
+        Caller {
+            name: None,
+            policy: Arc::new(Policy::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("anonymous")
+    }
+}
+
+impl std::ops::Deref for Caller {
+    type Target = Policy;
+
+    fn deref(&self) -> &Policy {
+        &self.policy
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Caller>().cloned().unwrap_or_else(Caller::anonymous))
+    }
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        return value.to_str().ok()?.strip_prefix("Bearer ").map(str::trim);
+    }
+    headers.get(API_KEY_HEADER)?.to_str().ok().map(str::trim)
+}
+
+/// Middleware requiring a valid API key when authentication is configured
+///
+/// Keys are sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    route: Option<MatchedPath>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(auth) = &state.auth else {
+        return next.run(request).await;
+    };
+    if route.is_some_and(|route| PUBLIC_ROUTES.contains(&route.as_str())) {
+        return next.run(request).await;
+    }
+
+    let Some(caller) = auth.caller(request.headers()) else {
+        warn!("Rejected request to {} without a valid API key", request.uri().path());
+        let body = ErrorResponse {
+            error: "Unauthorized".to_string(),
+            message: "A valid API key is required".to_string(),
+        };
+        return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], Json(body)).into_response();
+    };
+
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn policy(spec: Value) -> Policy {
+        serde_json::from_value(spec).unwrap()
+    }
+
+    #[test]
+    fn test_operator_lists() {
+        let denied = policy(json!({"denied_operators": ["$where", "$regex"]}));
+        assert!(denied.check_query(&json!({"age": {"$gt": 1}})).is_ok());
+        let violation = denied
+            .check_query(&json!({"$or": [{"a": 1}, {"name": {"$regex": "^A"}}]}))
+            .unwrap_err();
+        assert_eq!(violation.error, "OperatorNotAllowed");
+        assert!(denied.check_query(&json!({"$where": {"$fn": "isVip"}})).is_err());
+
+        let allowed = policy(json!({"allowed_operators": ["$eq", "$in", "$where"]}));
+        assert!(allowed.check_query(&json!({"a": {"$in": [1, 2]}, "b": 3})).is_ok());
+        assert!(allowed.check_query(&json!({"a": {"$nin": [1]}})).is_err());
+        // The arguments of $where are not queries
+        assert!(allowed.check_query(&json!({"$where": {"$fn": "isVip"}})).is_ok());
+    }
+
+    #[test]
+    fn test_depth_and_batch_limits() {
+        let limited = policy(json!({"max_query_depth": 3, "max_batch_size": 10}));
+        assert!(limited.check_query(&json!({"a": {"$gt": 1}})).is_ok());
+        // Arrays do not add a level: the root, the clause and the operator object make three
+        assert!(limited.check_query(&json!({"$and": [{"a": {"$gt": 1}}]})).is_ok());
+        assert_eq!(
+            limited.check_query(&json!({"a": {"$elemMatch": {"b": {"$gt": 1}}}})).unwrap_err().error,
+            "QueryTooDeep"
+        );
+        assert!(limited.check_batch(10).is_ok());
+        assert_eq!(limited.check_batch(11).unwrap_err().error, "BatchTooLarge");
+    }
+
+    fn secured_state() -> AppState {
+        let config = json!({"keys": [
+            {"name": "admin", "key": "admin-key"},
+            {"name": "reader", "key": "reader-key", "denied_operators": ["$where"], "max_batch_size": 2}
+        ]});
+        AppState {
+            auth: Some(Arc::new(serde_json::from_value(config).unwrap())),
+            ..AppState::default()
+        }
+    }
+
+    async fn send(state: &AppState, uri: &str, headers: &[(&str, &str)], body: Value) -> (StatusCode, Value) {
+        let mut request = axum::http::Request::post(uri).header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = crate::routes(state.clone())
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_requests_require_a_valid_key() {
+        let state = secured_state();
+        let request = json!({"query": {"a": 1}, "documents": [{"a": 1}]});
+
+        let (status, body) = send(&state, "/filter", &[], request.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "Unauthorized");
+        let (status, _) = send(&state, "/filter", &[("authorization", "Bearer wrong")], request.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = send(&state, "/filter", &[("authorization", "Bearer admin-key")], request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&state, "/filter", &[("x-api-key", "reader-key")], request).await;
+        assert_eq!(status, StatusCode::OK);
+
+        for route in ["/health", "/metrics"] {
+            let response = crate::routes(state.clone())
+                .oneshot(axum::http::Request::get(route).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", route);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_policy_violations_are_rejected_before_evaluation() {
+        let state = secured_state();
+        let reader = [("x-api-key", "reader-key")];
+
+        let request = json!({"query": {"$where": "this.a > 1"}, "documents": [{"a": 2}]});
+        let (status, body) = send(&state, "/filter", &reader, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "OperatorNotAllowed");
+
+        let request = json!([{"input": {}, "query": {}}, {"input": {}, "query": {}}, {"input": {}, "query": {}}]);
+        let (status, body) = send(&state, "/validate", &reader, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "BatchTooLarge");
+
+        let request = json!({"documents": [{"a": 1}], "query": {"a": {"$elemMatch": {"$where": "true"}}}});
+        let (status, _) = send(&state, "/filter", &reader, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        assert!(!state.metrics.render().contains("sift_documents_evaluated_total{"));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::metrics::Metrics;
+use indexmap::IndexMap;
+use serde_json::Value;
+use sift_rs::{
+    default_script_engine, CompiledQuery, Query, QueryContext, QueryOptions, ScriptEngine, SiftResult, WhereOperator,
+};
+use std::sync::{Arc, Mutex};
+
+/// Number of compiled queries kept by default
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Compiled queries shared between requests, keyed by their JSON text
+///
+/// When full, the least recently used query is evicted. Queries that fail
+/// to compile are not cached. Lookups, compile errors and `$where` evaluation
+/// time are recorded in the server metrics.
+pub struct CompileCache {
+    entries: Mutex<IndexMap<String, Arc<CompiledQuery>>>,
+    capacity: usize,
+    metrics: Arc<Metrics>,
+    where_engine: Option<Arc<dyn ScriptEngine>>,
+    options: QueryOptions,
+}
+
+impl CompileCache {
+    pub fn new(capacity: usize, metrics: Arc<Metrics>) -> Self {
+        CompileCache {
+            entries: Mutex::new(IndexMap::new()),
+            capacity: capacity.max(1),
+            where_engine: default_script_engine().map(|engine| metrics.timed_engine(engine)),
+            metrics,
+            options: QueryOptions::default(),
+        }
+    }
+
+    /// Compile every query with `options`, e.g. to reject `$where`
+    pub fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// A fresh context with the options and `$where` engine queries are compiled with
+    pub fn context(&self) -> QueryContext {
+        let mut context = QueryContext::with_options(self.options.clone());
+        if let Some(engine) = &self.where_engine {
+            context
+                .registry
+                .register("$where".to_string(), Box::new(WhereOperator::with_shared_engine(engine.clone())));
+        }
+        context
+    }
+
+    /// Return the compiled form of `query`, compiling it on a miss
+    pub fn get_or_compile(&self, query: &Value) -> SiftResult<Arc<CompiledQuery>> {
+        let key = query.to_string();
+
+        let cached = self.lookup(&key);
+        self.metrics.record_cache_lookup(cached.is_some());
+        if let Some(compiled) = cached {
+            return Ok(compiled);
+        }
+
+        // Compile outside the lock; a concurrent miss on the same query just compiles twice
+        let compiled = Query::from_value(query)
+            .and_then(|query| query.compile_with_context(self.context()))
+            .inspect_err(|e| self.metrics.record_compile_error(e))?;
+        let compiled = Arc::new(compiled);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.shift_remove_index(0);
+        }
+        entries.insert(key, compiled.clone());
+        Ok(compiled)
+    }
+
+    fn lookup(&self, key: &str) -> Option<Arc<CompiledQuery>> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries.get_index_of(key)?;
+        let last = entries.len() - 1;
+        entries.move_index(index, last);
+        entries.get_index(last).map(|(_, compiled)| compiled.clone())
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.metrics.cache_hits()
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.metrics.cache_misses()
+    }
+}
+
+impl Default for CompileCache {
+    fn default() -> Self {
+        CompileCache::new(DEFAULT_CAPACITY, Arc::new(Metrics::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_reuses_compiled_queries() {
+        let cache = CompileCache::default();
+        let first = cache.get_or_compile(&json!({"age": {"$gt": 1}})).unwrap();
+        let second = cache.get_or_compile(&json!({"age": {"$gt": 1}})).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        assert!(cache.get_or_compile(&json!({"age": {"$bogus": 1}})).is_err());
+        assert!(cache.get_or_compile(&json!({"age": {"$bogus": 1}})).is_err());
+        assert_eq!(cache.misses(), 3);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = CompileCache::new(2, Arc::new(Metrics::default()));
+        let a = json!({"a": 1});
+        let b = json!({"b": 1});
+        cache.get_or_compile(&a).unwrap();
+        cache.get_or_compile(&b).unwrap();
+        cache.get_or_compile(&a).unwrap();
+        cache.get_or_compile(&json!({"c": 1})).unwrap();
+
+        cache.get_or_compile(&a).unwrap();
+        cache.get_or_compile(&b).unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (2, 4));
+    }
+
+    #[test]
+    fn test_cache_applies_query_options() {
+        let options = QueryOptions {
+            allow_where: false,
+            ..QueryOptions::default()
+        };
+        let cache = CompileCache::default().with_options(options);
+        let Err(error) = cache.get_or_compile(&json!({"$where": "this.a > 1"})) else {
+            panic!("$where should be rejected");
+        };
+        assert_eq!(error.kind(), "UnsupportedOperation");
+        assert!(cache.get_or_compile(&json!({"a": 1})).is_ok());
+    }
+}
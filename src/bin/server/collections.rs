@@ -0,0 +1,598 @@
+use crate::auth::{Caller, PolicyViolation};
+use crate::filter::{FilterResponse, FindOptions};
+use crate::{api_error, bad_request, blocking, not_found, sift_error, validate_name, ApiError, AppState, ErrorResponse};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sift_rs::{CompiledQuery, Update};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+use utoipa::ToSchema;
+
+/// In-memory document collections, created on first insert
+///
+/// Filters are evaluated against a snapshot of a collection, so the lock is
+/// only held to take the snapshot and to store the result.
+#[derive(Default)]
+pub struct Collections {
+    collections: RwLock<BTreeMap<String, Arc<Vec<Value>>>>,
+    /// Held for the whole of each write, so a write's snapshot is still current when it is stored
+    writer: tokio::sync::Mutex<()>,
+    next_id: AtomicU64,
+}
+
+impl Collections {
+    // Every change is stored in one assignment, so a panic elsewhere cannot
+    // leave the map half-updated and the poison flag can be ignored
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<String, Arc<Vec<Value>>>> {
+        self.collections.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, BTreeMap<String, Arc<Vec<Value>>>> {
+        self.collections.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The documents of a collection as they are now, or `None` if it does not exist
+    fn snapshot(&self, name: &str) -> Option<Arc<Vec<Value>>> {
+        self.read().get(name).cloned()
+    }
+
+    /// Generate an `_id` shaped like a MongoDB ObjectId: 24 hex digits, roughly time-ordered
+    fn generate_id(&self) -> Value {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let counter = self.next_id.fetch_add(1, Ordering::Relaxed);
+        Value::String(format!("{:08x}{:016x}", seconds as u32, counter))
+    }
+}
+
+fn default_filter() -> Value {
+    json!({})
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct InsertRequest {
+    /// The documents to insert; an `_id` is generated for those without one
+    documents: Vec<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InsertResponse {
+    /// The `_id` of each inserted document, in request order
+    inserted_ids: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FindRequest {
+    /// The MongoDB-style query documents must match; defaults to all documents
+    #[serde(default = "default_filter")]
+    filter: Value,
+    #[serde(default)]
+    projection: Option<Value>,
+    #[serde(default)]
+    sort: Option<Value>,
+    #[serde(default)]
+    skip: Option<usize>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateRequest {
+    #[serde(default = "default_filter")]
+    filter: Value,
+    /// Update operators such as `{"$set": {...}}`, or a replacement document
+    update: Value,
+    /// Update every matching document rather than only the first
+    #[serde(default)]
+    multi: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateResponse {
+    /// Number of documents matching the filter
+    matched: usize,
+    /// Number of documents actually changed
+    modified: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeleteRequest {
+    #[serde(default = "default_filter")]
+    filter: Value,
+    /// Delete only the first matching document
+    #[serde(default)]
+    just_one: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeleteResponse {
+    deleted: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CollectionInfo {
+    name: String,
+    count: usize,
+}
+
+fn compile(state: &AppState, filter: &Value) -> Result<Arc<CompiledQuery>, ApiError> {
+    state
+        .cache
+        .get_or_compile(filter)
+        .map_err(|e| sift_error(&e, format!("Failed to compile filter: {}", e)))
+}
+
+/// Check the conditions of `$pull`, the only update operator that takes queries
+fn check_update(caller: &Caller, update: &Value) -> Result<(), PolicyViolation> {
+    if let Some(Value::Object(pulls)) = update.get("$pull") {
+        for condition in pulls.values() {
+            caller.check_query(condition)?;
+        }
+    }
+    Ok(())
+}
+
+/// Indexes of the documents matching `compiled`, stopping after the first when `first_only`
+fn matching_indexes(
+    state: &AppState,
+    compiled: &CompiledQuery,
+    documents: &[Value],
+    first_only: bool,
+) -> Result<Vec<usize>, ApiError> {
+    let mut indexes = Vec::new();
+    for (index, document) in documents.iter().enumerate() {
+        let matched = state
+            .metrics
+            .evaluate(compiled, document)
+            .map_err(|e| sift_error(&e, format!("Failed to filter document {}: {}", index, e)))?;
+        if matched {
+            indexes.push(index);
+            if first_only {
+                break;
+            }
+        }
+    }
+    Ok(indexes)
+}
+
+/// List collections and their sizes
+///
+/// GET /collections
+/// Response: [{ "name": "...", "count": n }, ...]
+#[utoipa::path(get, path = "/collections", tag = "collections", responses((status = 200, body = Vec<CollectionInfo>)))]
+pub async fn list(State(state): State<AppState>) -> Json<Vec<CollectionInfo>> {
+    let collections = state.collections.read();
+    Json(
+        collections
+            .iter()
+            .map(|(name, documents)| CollectionInfo {
+                name: name.clone(),
+                count: documents.len(),
+            })
+            .collect(),
+    )
+}
+
+/// Drop a collection and all of its documents
+///
+/// DELETE /collections/{collection}
+/// Response: 204 when dropped, 404 when there was no such collection
+#[utoipa::path(
+    delete,
+    path = "/collections/{collection}",
+    tag = "collections",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    responses(
+        (status = 204, description = "The collection was dropped"),
+        (status = 404, body = ErrorResponse),
+    )
+)]
+pub async fn drop_collection(State(state): State<AppState>, Path(name): Path<String>) -> Result<StatusCode, ApiError> {
+    let _writer = state.collections.writer.lock().await;
+    if state.collections.write().remove(&name).is_some() {
+        info!("Dropped collection '{}'", name);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(not_found(&format!("No collection named '{}'", name)))
+    }
+}
+
+/// Insert documents, creating the collection if needed
+///
+/// POST /collections/{collection}/documents
+/// Body: { "documents": [...] }
+/// Response: 201 with { "inserted_ids": [...] }
+///
+/// The batch is rejected as a whole if any document is not an object or
+/// reuses an existing `_id`.
+#[utoipa::path(
+    post,
+    path = "/collections/{collection}/documents",
+    tag = "collections",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    request_body = InsertRequest,
+    responses(
+        (status = 201, body = InsertResponse),
+        (status = 400, description = "The name or a document is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+        (status = 409, description = "A document reuses an existing `_id`", body = ErrorResponse),
+    )
+)]
+pub async fn insert(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<InsertRequest>,
+) -> Result<(StatusCode, Json<InsertResponse>), ApiError> {
+    validate_name(&name)?;
+    caller.check_batch(request.documents.len())?;
+
+    let mut documents = request.documents;
+    for (index, document) in documents.iter_mut().enumerate() {
+        let Value::Object(fields) = document else {
+            return Err(bad_request(
+                "InvalidDocument",
+                &format!("Document {} must be an object", index),
+            ));
+        };
+        if !fields.contains_key("_id") {
+            fields.insert("_id".to_string(), state.collections.generate_id());
+        }
+    }
+
+    let _writer = state.collections.writer.lock().await;
+    let existing = state.collections.snapshot(&name).unwrap_or_default();
+
+    let mut ids: HashSet<String> = existing.iter().map(|document| document["_id"].to_string()).collect();
+    let mut inserted_ids = Vec::with_capacity(documents.len());
+    for document in &documents {
+        let id = &document["_id"];
+        if !ids.insert(id.to_string()) {
+            return Err(api_error(
+                StatusCode::CONFLICT,
+                "DuplicateKey",
+                &format!("A document with _id {} already exists in '{}'", id, name),
+            ));
+        }
+        inserted_ids.push(id.clone());
+    }
+
+    let mut collections = state.collections.write();
+    Arc::make_mut(collections.entry(name.clone()).or_default()).extend(documents);
+    info!("Inserted {} documents into '{}'", inserted_ids.len(), name);
+    Ok((StatusCode::CREATED, Json(InsertResponse { inserted_ids })))
+}
+
+/// Find documents matching a filter
+///
+/// POST /collections/{collection}/find
+/// Body: { "filter"?: {...}, "projection"?: {...}, "sort"?: {...}, "skip"?: n, "limit"?: n }
+/// Response: { "documents": [...], "count": n }
+///
+/// Documents are returned in insertion order unless sorted. A missing
+/// collection behaves like an empty one.
+#[utoipa::path(
+    post,
+    path = "/collections/{collection}/find",
+    tag = "collections",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    request_body = FindRequest,
+    responses(
+        (status = 200, body = FilterResponse),
+        (status = 400, description = "The filter, projection or sort is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn find(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<FindRequest>,
+) -> Result<Json<FilterResponse>, ApiError> {
+    caller.check_query(&request.filter)?;
+    let compiled = compile(&state, &request.filter)?;
+    let options = FindOptions::parse(&request.projection, &request.sort, request.skip, request.limit)?;
+
+    let documents = state.collections.snapshot(&name).unwrap_or_default();
+    let total = documents.len();
+    let metrics = state.metrics.clone();
+    let response = blocking(move || options.select(&metrics, &compiled, &documents)).await?;
+
+    info!("Find in '{}': {} of {} documents matched", name, response.count, total);
+    Ok(Json(response))
+}
+
+/// Update documents matching a filter
+///
+/// PATCH /collections/{collection}/update
+/// Body: { "filter"?: {...}, "update": {"$set": {...}, ...}, "multi"?: false }
+/// Response: { "matched": n, "modified": n }
+///
+/// Like MongoDB, only the first matching document is updated unless `multi`
+/// is set. Either every matching document is updated or, on error, none is.
+#[utoipa::path(
+    patch,
+    path = "/collections/{collection}/update",
+    tag = "collections",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, body = UpdateResponse),
+        (status = 400, description = "The filter or update is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<UpdateRequest>,
+) -> Result<Json<UpdateResponse>, ApiError> {
+    caller.check_query(&request.filter)?;
+    check_update(&caller, &request.update)?;
+    let compiled = compile(&state, &request.filter)?;
+    let update = Update::from_value_with_context(&request.update, &state.cache.context())
+        .map_err(|e| sift_error(&e, format!("Invalid update: {}", e)))?;
+
+    let _writer = state.collections.writer.lock().await;
+    let Some(documents) = state.collections.snapshot(&name) else {
+        return Ok(Json(UpdateResponse { matched: 0, modified: 0 }));
+    };
+
+    let multi = request.multi;
+    let evaluating = state.clone();
+    let (matched, updated) = blocking(move || {
+        let indexes = matching_indexes(&evaluating, &compiled, &documents, !multi)?;
+        let mut updated = Vec::new();
+        for &index in &indexes {
+            let mut document = documents[index].clone();
+            let changed = update
+                .apply(&mut document)
+                .map_err(|e| sift_error(&e, format!("Failed to update document {}: {}", index, e)))?;
+            if changed {
+                updated.push((index, document));
+            }
+        }
+        Ok((indexes.len(), updated))
+    })
+    .await?;
+
+    let modified = updated.len();
+    if modified > 0 {
+        let mut collections = state.collections.write();
+        if let Some(documents) = collections.get_mut(&name) {
+            let documents = Arc::make_mut(documents);
+            for (index, document) in updated {
+                documents[index] = document;
+            }
+        }
+    }
+
+    info!("Update in '{}': {} matched, {} modified", name, matched, modified);
+    Ok(Json(UpdateResponse { matched, modified }))
+}
+
+/// Delete documents matching a filter
+///
+/// POST /collections/{collection}/delete
+/// Body: { "filter"?: {...}, "just_one"?: false }
+/// Response: { "deleted": n }
+#[utoipa::path(
+    post,
+    path = "/collections/{collection}/delete",
+    tag = "collections",
+    params(("collection" = String, Path, description = "Name of the collection")),
+    request_body = DeleteRequest,
+    responses(
+        (status = 200, body = DeleteResponse),
+        (status = 400, description = "The filter is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<DeleteRequest>,
+) -> Result<Json<DeleteResponse>, ApiError> {
+    caller.check_query(&request.filter)?;
+    let compiled = compile(&state, &request.filter)?;
+
+    let _writer = state.collections.writer.lock().await;
+    let Some(documents) = state.collections.snapshot(&name) else {
+        return Ok(Json(DeleteResponse { deleted: 0 }));
+    };
+
+    let just_one = request.just_one;
+    let evaluating = state.clone();
+    let indexes: HashSet<usize> = blocking(move || matching_indexes(&evaluating, &compiled, &documents, just_one))
+        .await?
+        .into_iter()
+        .collect();
+    if !indexes.is_empty() {
+        let mut collections = state.collections.write();
+        if let Some(documents) = collections.get_mut(&name) {
+            let mut index = 0;
+            Arc::make_mut(documents).retain(|_| {
+                let keep = !indexes.contains(&index);
+                index += 1;
+                keep
+            });
+        }
+    }
+
+    info!("Deleted {} documents from '{}'", indexes.len(), name);
+    Ok(Json(DeleteResponse { deleted: indexes.len() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = crate::routes(state.clone())
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    async fn seed(state: &AppState) {
+        let documents = json!({"documents": [
+            {"_id": 1, "name": "Alice", "age": 30, "tags": ["admin"]},
+            {"_id": 2, "name": "Bob", "age": 20, "tags": []},
+            {"_id": 3, "name": "Carol", "age": 41, "tags": ["admin", "ops"]}
+        ]});
+        let (status, body) = send(state, "POST", "/collections/users/documents", Some(documents)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, json!({"inserted_ids": [1, 2, 3]}));
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_find() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let find = json!({"filter": {"tags": {"$in": ["admin"]}}, "sort": {"age": -1}, "projection": {"name": 1, "_id": 0}, "limit": 1});
+        let (status, body) = send(&state, "POST", "/collections/users/find", Some(find)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"documents": [{"name": "Carol"}], "count": 2}));
+
+        let (_, body) = send(&state, "POST", "/collections/missing/find", Some(json!({}))).await;
+        assert_eq!(body, json!({"documents": [], "count": 0}));
+    }
+
+    #[tokio::test]
+    async fn test_insert_generates_ids_and_rejects_duplicates() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let (status, body) = send(&state, "POST", "/collections/logs/documents", Some(json!({"documents": [{"msg": "hi"}]}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["inserted_ids"][0].as_str().unwrap().len(), 24);
+
+        let duplicate = json!({"documents": [{"_id": 4}, {"_id": 1}]});
+        let (status, body) = send(&state, "POST", "/collections/users/documents", Some(duplicate)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "DuplicateKey");
+
+        let (status, body) = send(&state, "POST", "/collections/users/documents", Some(json!({"documents": [1]}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "InvalidDocument");
+
+        // Rejected batches insert nothing
+        let (_, body) = send(&state, "GET", "/collections", None).await;
+        assert_eq!(body, json!([{"name": "logs", "count": 1}, {"name": "users", "count": 3}]));
+    }
+
+    #[tokio::test]
+    async fn test_update_one_and_many() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let update = json!({"filter": {"age": {"$gte": 25}}, "update": {"$inc": {"age": 1}}});
+        let (_, body) = send(&state, "PATCH", "/collections/users/update", Some(update)).await;
+        assert_eq!(body, json!({"matched": 1, "modified": 1}));
+
+        let update = json!({"filter": {"tags": {"$in": ["admin"]}}, "update": {"$addToSet": {"tags": "ops"}}, "multi": true});
+        let (_, body) = send(&state, "PATCH", "/collections/users/update", Some(update)).await;
+        assert_eq!(body, json!({"matched": 2, "modified": 1}));
+
+        let (_, body) = send(&state, "POST", "/collections/users/find", Some(json!({"filter": {"tags": {"$in": ["ops"]}}, "projection": {"age": 1}}))).await;
+        assert_eq!(body["documents"], json!([{"_id": 1, "age": 31}, {"_id": 3, "age": 41}]));
+
+        let (status, body) = send(&state, "PATCH", "/collections/users/update", Some(json!({"update": {"$set": {"_id": 9}}}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "InvalidQuery");
+    }
+
+    #[tokio::test]
+    async fn test_update_rejects_huge_array_index() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let update = json!({"filter": {"_id": 1}, "update": {"$set": {"tags.18446744073709551615": 1}}});
+        let (status, body) = send(&state, "PATCH", "/collections/users/update", Some(update)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "InvalidQuery");
+
+        let (status, body) = send(&state, "POST", "/collections/users/find", Some(json!({"filter": {"_id": 1}}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["documents"][0]["tags"], json!(["admin"]));
+    }
+
+    #[tokio::test]
+    async fn test_update_respects_disabled_where() {
+        let options = sift_rs::QueryOptions {
+            allow_where: false,
+            ..sift_rs::QueryOptions::default()
+        };
+        let metrics = Arc::new(crate::metrics::Metrics::new());
+        let state = AppState {
+            cache: Arc::new(crate::cache::CompileCache::new(crate::cache::DEFAULT_CAPACITY, metrics.clone()).with_options(options)),
+            metrics,
+            ..AppState::default()
+        };
+        seed(&state).await;
+
+        let update = json!({"filter": {"_id": 3}, "update": {"$pull": {"tags": {"$where": "this === 'ops'"}}}});
+        let (status, body) = send(&state, "PATCH", "/collections/users/update", Some(update)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "UnsupportedOperation");
+
+        let (_, body) = send(&state, "POST", "/collections/users/find", Some(json!({"filter": {"_id": 3}}))).await;
+        assert_eq!(body["documents"][0]["tags"], json!(["admin", "ops"]));
+    }
+
+    #[tokio::test]
+    async fn test_collections_survive_a_poisoned_lock() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let collections = state.collections.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = collections.collections.write().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(state.collections.collections.is_poisoned());
+
+        let update = json!({"filter": {"_id": 2}, "update": {"$set": {"age": 21}}});
+        let (status, body) = send(&state, "PATCH", "/collections/users/update", Some(update)).await;
+        assert_eq!((status, body), (StatusCode::OK, json!({"matched": 1, "modified": 1})));
+        let (_, body) = send(&state, "POST", "/collections/users/find", Some(json!({"filter": {"age": 21}}))).await;
+        assert_eq!(body["count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_drop() {
+        let state = AppState::default();
+        seed(&state).await;
+
+        let (_, body) = send(&state, "POST", "/collections/users/delete", Some(json!({"filter": {"tags": {"$in": ["admin"]}}, "just_one": true}))).await;
+        assert_eq!(body, json!({"deleted": 1}));
+        let (_, body) = send(&state, "POST", "/collections/users/delete", Some(json!({"filter": {"age": {"$lt": 50}}}))).await;
+        assert_eq!(body, json!({"deleted": 2}));
+
+        let (status, _) = send(&state, "DELETE", "/collections/users", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&state, "DELETE", "/collections/users", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,350 @@
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Deserializer};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Command-line flags; each can also be set with the environment variable shown
+///
+/// Flags and environment variables override the config file, which
+/// overrides the built-in defaults.
+#[derive(Debug, Default, Parser)]
+#[command(name = "sift-rs-server", version, about = "HTTP API for MongoDB-style queries over JSON")]
+pub struct Cli {
+    /// TOML or YAML config file, chosen by its `.toml`, `.yaml` or `.yml` extension
+    #[arg(long, env = "SIFT_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. 127.0.0.1:8080
+    #[arg(long, env = "SIFT_BIND")]
+    pub bind: Option<SocketAddr>,
+    /// Origin allowed by CORS; repeat for several. Any origin is allowed when none is set
+    #[arg(long = "cors-origin", env = "SIFT_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+    /// Largest accepted JSON request body, e.g. 2MB or 512KiB
+    #[arg(long, env = "SIFT_MAX_BODY", value_parser = parse_size)]
+    pub max_body: Option<usize>,
+    #[arg(long, env = "SIFT_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+    /// Reject queries using $where; `--disable-where=false` allows them even if the config file disables them
+    #[arg(
+        long,
+        env = "SIFT_DISABLE_WHERE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub disable_where: Option<bool>,
+    /// Address for the gRPC service, e.g. 0.0.0.0:50051 (needs the `grpc` feature)
+    #[arg(long, env = "SIFT_GRPC_BIND")]
+    pub grpc_bind: Option<SocketAddr>,
+    /// PEM certificate chain; serves HTTPS together with --tls-key
+    #[arg(long, env = "SIFT_TLS_CERT", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "SIFT_TLS_KEY", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// JSON file stored queries are persisted to
+    #[arg(long, env = "SIFT_QUERIES_FILE")]
+    pub queries_file: Option<PathBuf>,
+    /// JSON file of API keys; enables authentication
+    #[arg(long, env = "SIFT_AUTH_FILE")]
+    pub auth_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Server settings, read from the config file and then overridden by [`Cli`]
+///
+/// ```toml
+/// bind = "0.0.0.0:3000"
+/// cors_origins = ["https://app.example.com"]
+/// max_body = "4MB"
+/// log_format = "json"
+/// disable_where = true
+/// ```
+///
+/// The same keys can be written in YAML instead, in a `.yaml` or `.yml` file.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Address of the gRPC service; gRPC is off when unset
+    pub grpc_bind: Option<SocketAddr>,
+    pub cors_origins: Vec<String>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_body: usize,
+    pub log_format: LogFormat,
+    pub disable_where: bool,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub queries_file: Option<PathBuf>,
+    pub auth_file: Option<PathBuf>,
+    /// Number of compiled queries kept in the shared cache
+    pub cache_capacity: usize,
+    /// Seconds to wait for in-flight requests on shutdown before exiting anyway
+    pub shutdown_timeout: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            grpc_bind: None,
+            cors_origins: Vec::new(),
+            max_body: 2 * 1024 * 1024,
+            log_format: LogFormat::Text,
+            disable_where: false,
+            tls_cert: None,
+            tls_key: None,
+            queries_file: None,
+            auth_file: None,
+            cache_capacity: crate::cache::DEFAULT_CAPACITY,
+            shutdown_timeout: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Build the configuration from the config file, if any, and the flags
+    ///
+    /// The legacy `PORT` variable still sets the port when no bind address is given.
+    pub fn load(cli: Cli) -> Result<Self, String> {
+        let mut config = match &cli.config {
+            Some(path) => ServerConfig::from_file(path)?,
+            None => ServerConfig::default(),
+        };
+
+        match cli.bind {
+            Some(bind) => config.bind = bind,
+            None => {
+                if let Ok(port) = std::env::var("PORT") {
+                    let port = port.parse().map_err(|_| format!("Invalid PORT '{}'", port))?;
+                    config.bind.set_port(port);
+                }
+            }
+        }
+        if cli.grpc_bind.is_some() {
+            config.grpc_bind = cli.grpc_bind;
+        }
+        if !cli.cors_origins.is_empty() {
+            config.cors_origins = cli.cors_origins;
+        }
+        if let Some(max_body) = cli.max_body {
+            config.max_body = max_body;
+        }
+        if let Some(log_format) = cli.log_format {
+            config.log_format = log_format;
+        }
+        if let Some(disable_where) = cli.disable_where {
+            config.disable_where = disable_where;
+        }
+        if cli.tls_cert.is_some() {
+            config.tls_cert = cli.tls_cert;
+            config.tls_key = cli.tls_key;
+        }
+        if cli.queries_file.is_some() {
+            config.queries_file = cli.queries_file;
+        }
+        if cli.auth_file.is_some() {
+            config.auth_file = cli.auth_file;
+        }
+
+        if config.tls_cert.is_some() != config.tls_key.is_some() {
+            return Err("tls_cert and tls_key must be set together".to_string());
+        }
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        let yaml = matches!(path.extension().and_then(|extension| extension.to_str()), Some("yaml" | "yml"));
+        let config = if yaml {
+            serde_yaml::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        config.map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    pub fn tls(&self) -> Option<(&Path, &Path)> {
+        self.tls_cert.as_deref().zip(self.tls_key.as_deref())
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+
+    /// The CORS policy for `cors_origins`: any origin when empty or `"*"`, otherwise just those listed
+    pub fn cors_layer(&self) -> Result<CorsLayer, String> {
+        if self.cors_origins.is_empty() || self.cors_origins.iter().any(|origin| origin == "*") {
+            return Ok(CorsLayer::permissive());
+        }
+        let origins = self
+            .cors_origins
+            .iter()
+            .map(|origin| origin.parse().map_err(|_| format!("Invalid CORS origin '{}'", origin)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(Any)
+            .allow_headers(Any))
+    }
+}
+
+/// Parse a byte size such as `1048576`, `512KiB`, `4MB` or `1 GB`
+///
+/// Both decimal-looking and binary suffixes are read as powers of 1024.
+pub fn parse_size(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: usize = number.parse().map_err(|_| format!("Invalid size '{}'", text))?;
+
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown size unit in '{}'", text)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size '{}' is too large", text))
+}
+
+/// Accept a size as either a number of bytes or a string such as `"4MB"`
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(usize),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(bytes),
+        Size::Text(text) => parse_size(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512KiB"), Ok(512 * 1024));
+        assert_eq!(parse_size("4MB"), Ok(4 * 1024 * 1024));
+        assert_eq!(parse_size("1 gb"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("3 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            bind = "127.0.0.1:8080"
+            cors_origins = ["https://app.example.com"]
+            max_body = "4MB"
+            log_format = "json"
+            disable_where = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.max_body, 4 * 1024 * 1024);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(config.disable_where);
+        assert_eq!(config.cache_capacity, crate::cache::DEFAULT_CAPACITY);
+
+        assert!(toml::from_str::<ServerConfig>("max_body = 1024").is_ok());
+        assert!(toml::from_str::<ServerConfig>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_flags_override_the_config_file() {
+        let path = std::env::temp_dir().join(format!("sift-server-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "bind = \"127.0.0.1:8080\"\nmax_body = 1000\ncors_origins = [\"https://a.example\"]\n").unwrap();
+
+        let cli = Cli::parse_from([
+            "sift-rs-server",
+            "--config",
+            path.to_str().unwrap(),
+            "--bind",
+            "0.0.0.0:9000",
+            "--disable-where",
+            "--log-format",
+            "json",
+        ]);
+        let config = ServerConfig::load(cli).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.bind, "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(config.max_body, 1000);
+        assert_eq!(config.cors_origins, vec!["https://a.example".to_string()]);
+        assert!(config.disable_where);
+        assert_eq!(config.log_format, LogFormat::Json);
+
+        assert!(Cli::try_parse_from(["sift-rs-server", "--tls-cert", "cert.pem"]).is_err());
+    }
+
+    #[test]
+    fn test_flags_can_allow_where_disabled_by_the_config_file() {
+        let path = std::env::temp_dir().join(format!("sift-server-where-{}.toml", std::process::id()));
+        std::fs::write(&path, "disable_where = true\n").unwrap();
+        let load = |args: &[&str]| {
+            let cli = Cli::parse_from(["sift-rs-server", "--config", path.to_str().unwrap()].iter().chain(args));
+            ServerConfig::load(cli).unwrap().disable_where
+        };
+
+        let from_file = load(&[]);
+        let allowed = load(&["--disable-where=false"]);
+        let disabled = load(&["--disable-where"]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(from_file);
+        assert!(!allowed);
+        assert!(disabled);
+    }
+
+    #[test]
+    fn test_yaml_config_file() {
+        let path = std::env::temp_dir().join(format!("sift-server-config-{}.yaml", std::process::id()));
+        std::fs::write(
+            &path,
+            "bind: 127.0.0.1:8080\ncors_origins:\n  - https://app.example.com\nmax_body: 4MB\ndisable_where: true\n",
+        )
+        .unwrap();
+
+        let config = ServerConfig::load(Cli::parse_from(["sift-rs-server", "--config", path.to_str().unwrap()]));
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(config.cors_origins, vec!["https://app.example.com".to_string()]);
+        assert_eq!(config.max_body, 4 * 1024 * 1024);
+        assert!(config.disable_where);
+    }
+
+    #[test]
+    fn test_cors_origins_must_be_valid() {
+        let mut config = ServerConfig::default();
+        assert!(config.cors_layer().is_ok());
+        config.cors_origins = vec!["https://app.example.com".to_string()];
+        assert!(config.cors_layer().is_ok());
+        config.cors_origins = vec!["bad\norigin".to_string()];
+        assert!(config.cors_layer().is_err());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::auth::Caller;
+use crate::metrics::Metrics;
+use crate::{bad_request, blocking, sift_error, ApiError, AppState, ErrorResponse};
+use axum::body::{Body, Bytes};
+use axum::extract::{Query as QueryParams, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Json, Response};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sift_rs::{CompiledQuery, Projection, Sort};
+use std::sync::Arc;
+use std::convert::Infallible;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+/// Header carrying the query for `POST /filter/ndjson`
+const QUERY_HEADER: &str = "x-sift-query";
+
+/// Longest NDJSON line accepted before the stream is aborted
+const MAX_LINE_LENGTH: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FilterRequest {
+    /// The MongoDB-style query documents must match
+    query: Value,
+    /// The documents to filter
+    documents: Vec<Value>,
+    /// Optional projection applied to each returned document, e.g. `{"name": 1}`
+    #[serde(default)]
+    projection: Option<Value>,
+    /// Optional sort applied to the matches, e.g. `{"age": -1}`
+    #[serde(default)]
+    sort: Option<Value>,
+    /// Number of sorted matches to skip
+    #[serde(default)]
+    skip: Option<usize>,
+    /// Maximum number of documents to return
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FilterResponse {
+    /// The matching documents after sort, skip, limit and projection
+    pub(crate) documents: Vec<Value>,
+    /// Total number of matching documents, before skip and limit
+    pub(crate) count: usize,
+}
+
+/// Filter endpoint - returns the documents matching a single query
+///
+/// POST /filter
+/// Body: { "query": {...}, "documents": [...], "projection"?: {...}, "sort"?: {...}, "skip"?: n, "limit"?: n }
+/// Response: { "documents": [...], "count": n }
+#[utoipa::path(
+    post,
+    path = "/filter",
+    tag = "queries",
+    request_body = FilterRequest,
+    responses(
+        (status = 200, body = FilterResponse),
+        (status = 400, description = "The query, projection or sort is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn filter(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<FilterRequest>,
+) -> Result<Json<FilterResponse>, ApiError> {
+    info!("Processing filter request with {} documents", request.documents.len());
+    caller.check_query(&request.query)?;
+    caller.check_batch(request.documents.len())?;
+
+    let compiled = state
+        .cache
+        .get_or_compile(&request.query)
+        .map_err(|e| sift_error(&e, format!("Failed to compile query: {}", e)))?;
+    let options = FindOptions::parse(&request.projection, &request.sort, request.skip, request.limit)?;
+    let total = request.documents.len();
+    let metrics = state.metrics.clone();
+    let response = blocking(move || options.select(&metrics, &compiled, &request.documents)).await?;
+
+    info!("Filter completed: {} of {} documents matched", response.count, total);
+    Ok(Json(response))
+}
+
+/// Projection, sort, skip and limit applied to the documents matching a query
+pub struct FindOptions {
+    projection: Option<Projection>,
+    sort: Option<Sort>,
+    skip: usize,
+    limit: usize,
+}
+
+impl FindOptions {
+    pub fn parse(
+        projection: &Option<Value>,
+        sort: &Option<Value>,
+        skip: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Self, ApiError> {
+        let projection = projection
+            .as_ref()
+            .map(Projection::from_value)
+            .transpose()
+            .map_err(|e| sift_error(&e, format!("Invalid projection: {}", e)))?;
+        let sort = sort
+            .as_ref()
+            .map(Sort::from_value)
+            .transpose()
+            .map_err(|e| sift_error(&e, format!("Invalid sort: {}", e)))?;
+
+        Ok(FindOptions {
+            projection,
+            sort,
+            skip: skip.unwrap_or(0),
+            limit: limit.unwrap_or(usize::MAX),
+        })
+    }
+
+    /// Filter, sort, page and project `documents`
+    pub fn select(
+        &self,
+        metrics: &Metrics,
+        compiled: &CompiledQuery,
+        documents: &[Value],
+    ) -> Result<FilterResponse, ApiError> {
+        let mut matches = Vec::new();
+        for (index, document) in documents.iter().enumerate() {
+            let matched = metrics
+                .evaluate(compiled, document)
+                .map_err(|e| sift_error(&e, format!("Failed to filter document {}: {}", index, e)))?;
+            if matched {
+                matches.push(document);
+            }
+        }
+
+        if let Some(sort) = &self.sort {
+            sort.sort(&mut matches);
+        }
+
+        let count = matches.len();
+        let documents = matches
+            .into_iter()
+            .skip(self.skip)
+            .take(self.limit)
+            .map(|document| match &self.projection {
+                Some(projection) => projection.apply(document),
+                None => document.clone(),
+            })
+            .collect();
+
+        Ok(FilterResponse { documents, count })
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NdjsonParams {
+    /// The query as JSON, used when the `X-Sift-Query` header is absent
+    query: Option<String>,
+    /// Skip lines that are not valid JSON or fail to evaluate instead of stopping
+    #[serde(default)]
+    skip_invalid: bool,
+}
+
+/// Streaming filter endpoint - filters an NDJSON body line by line
+///
+/// POST /filter/ndjson?query=...   (or the query in an `X-Sift-Query` header)
+/// Body: one JSON document per line, optionally sent chunked
+/// Response: the matching lines, streamed back as NDJSON while the body is read
+///
+/// A line that is not valid JSON or fails to evaluate ends the stream with an
+/// `{"error": ..., "message": ..., "line": n}` line, unless `skip_invalid=true`.
+/// So does a document beyond the caller's maximum batch size.
+#[utoipa::path(
+    post,
+    path = "/filter/ndjson",
+    tag = "queries",
+    params(
+        NdjsonParams,
+        ("X-Sift-Query" = Option<String>, Header, description = "The query as JSON"),
+    ),
+    request_body(content = String, content_type = "application/x-ndjson", description = "One JSON document per line"),
+    responses(
+        (status = 200, description = "The matching lines", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "The query is missing or invalid", body = ErrorResponse),
+        (status = 403, description = "The query breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn filter_ndjson(
+    State(state): State<AppState>,
+    caller: Caller,
+    QueryParams(params): QueryParams<NdjsonParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, ApiError> {
+    let query_text = match headers.get(QUERY_HEADER) {
+        Some(value) => value.to_str().map(str::to_string).map_err(|_| {
+            bad_request("InvalidQuery", "X-Sift-Query header must be valid UTF-8")
+        })?,
+        None => params.query.ok_or_else(|| {
+            bad_request("InvalidQuery", "Provide the query in the X-Sift-Query header or the query parameter")
+        })?,
+    };
+
+    let query_value: Value = serde_json::from_str(&query_text)
+        .map_err(|e| bad_request("InvalidQuery", &format!("Query is not valid JSON: {}", e)))?;
+    caller.check_query(&query_value)?;
+    let compiled = state
+        .cache
+        .get_or_compile(&query_value)
+        .map_err(|e| sift_error(&e, format!("Failed to compile query: {}", e)))?;
+
+    info!("Streaming NDJSON filter for query {}", query_text);
+    let lines = LineFilter {
+        body: Box::pin(body.into_data_stream()),
+        buffer: Vec::new(),
+        compiled,
+        metrics: state.metrics.clone(),
+        line: 0,
+        documents: 0,
+        max_documents: caller.max_batch_size(),
+        skip_invalid: params.skip_invalid,
+        finished: false,
+    };
+    let output = stream::unfold(lines, |lines| async move {
+        let (chunk, lines) = lines.next_output().await?;
+        Some((Ok::<_, Infallible>(chunk), lines))
+    });
+
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(output)).into_response())
+}
+
+/// Splits the request body into lines and yields the ones matching the query
+struct LineFilter {
+    body: std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, axum::Error>> + Send>>,
+    buffer: Vec<u8>,
+    compiled: Arc<CompiledQuery>,
+    metrics: Arc<Metrics>,
+    /// Number of the last line evaluated, starting at 1
+    line: usize,
+    /// Number of non-blank lines seen so far
+    documents: usize,
+    max_documents: Option<usize>,
+    skip_invalid: bool,
+    finished: bool,
+}
+
+impl LineFilter {
+    /// Matching lines from the next chunk(s) of the body, or `None` once it is exhausted
+    ///
+    /// Each chunk is evaluated on the blocking thread pool, since a query may run `$where` scripts.
+    async fn next_output(mut self) -> Option<(Bytes, Self)> {
+        let mut output = Vec::new();
+
+        while output.is_empty() && !self.finished {
+            let chunk = self.body.next().await;
+            (self, output) = tokio::task::spawn_blocking(move || {
+                let mut output = Vec::new();
+                self.consume(chunk, &mut output);
+                (self, output)
+            })
+            .await
+            .ok()?;
+        }
+
+        (!output.is_empty()).then(|| (Bytes::from(output), self))
+    }
+
+    /// Evaluate the lines completed by the next chunk of the body, or the last line once it ends
+    fn consume(&mut self, chunk: Option<Result<Bytes, axum::Error>>, output: &mut Vec<u8>) {
+        match chunk {
+            Some(Ok(chunk)) => {
+                self.buffer.extend_from_slice(&chunk);
+                self.process_complete_lines(output);
+                if !self.finished && self.buffer.len() > MAX_LINE_LENGTH {
+                    self.line += 1;
+                    self.fail(output, "LineTooLong", format!("Line exceeds {} bytes", MAX_LINE_LENGTH));
+                }
+            }
+            Some(Err(e)) => {
+                self.fail(output, "BodyError", format!("Failed to read request body: {}", e));
+            }
+            None => {
+                // The last line may not end with a newline
+                let line = std::mem::take(&mut self.buffer);
+                self.process(&line, output);
+                self.finished = true;
+            }
+        }
+    }
+
+    /// Evaluate every newline-terminated line in the buffer, keeping the incomplete rest
+    fn process_complete_lines(&mut self, output: &mut Vec<u8>) {
+        let buffer = std::mem::take(&mut self.buffer);
+        let mut start = 0;
+
+        while let Some(length) = buffer[start..].iter().position(|&byte| byte == b'\n') {
+            self.process(&buffer[start..start + length], output);
+            start += length + 1;
+            if self.finished {
+                return;
+            }
+        }
+
+        self.buffer = buffer;
+        self.buffer.drain(..start);
+    }
+
+    /// Evaluate one line, appending it to the output if it matches
+    fn process(&mut self, line: &[u8], output: &mut Vec<u8>) {
+        self.line += 1;
+        let line = line.trim_ascii();
+        if line.is_empty() {
+            return;
+        }
+
+        self.documents += 1;
+        if let Some(max) = self.max_documents.filter(|&max| self.documents > max) {
+            self.fail(output, "BatchTooLarge", format!("Stream exceeds the limit of {} documents", max));
+            return;
+        }
+
+        let result = serde_json::from_slice::<Value>(line)
+            .map_err(|e| ("InvalidJson", format!("Invalid JSON: {}", e)))
+            .and_then(|document| {
+                self.metrics
+                    .evaluate(&self.compiled, &document)
+                    .map_err(|e| (e.kind(), format!("Failed to filter line: {}", e)))
+            });
+
+        match result {
+            Ok(true) => {
+                output.extend_from_slice(line);
+                output.push(b'\n');
+            }
+            Ok(false) => {}
+            Err((_, message)) if self.skip_invalid => {
+                warn!("Skipping NDJSON line {}: {}", self.line, message);
+            }
+            Err((error, message)) => self.fail(output, error, message),
+        }
+    }
+
+    /// End the stream with an error line for the current line
+    fn fail(&mut self, output: &mut Vec<u8>, error: &str, message: String) {
+        warn!("Stopping NDJSON stream at line {}: {}", self.line, message);
+        self.finished = true;
+        output.extend_from_slice(json!({"error": error, "message": message, "line": self.line}).to_string().as_bytes());
+        output.push(b'\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    async fn run(body: Value) -> Result<FilterResponse, ApiError> {
+        let request = serde_json::from_value(body).unwrap();
+        filter(State(AppState::default()), Caller::anonymous(), Json(request))
+            .await
+            .map(|Json(response)| response)
+    }
+
+    fn people() -> Value {
+        json!([
+            {"_id": 1, "name": "Alice", "age": 30},
+            {"_id": 2, "name": "Bob", "age": 20},
+            {"_id": 3, "name": "Carol", "age": 41},
+            {"_id": 4, "name": "Dan", "age": 35}
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_filter_returns_matches_and_count() {
+        let response = run(json!({"query": {"age": {"$gte": 30}}, "documents": people()})).await.unwrap();
+        assert_eq!(response.count, 3);
+        assert_eq!(response.documents.len(), 3);
+        assert_eq!(response.documents[0]["name"], "Alice");
+    }
+
+    #[tokio::test]
+    async fn test_filter_sort_skip_limit_projection() {
+        let response = run(json!({
+            "query": {"age": {"$gte": 30}},
+            "documents": people(),
+            "sort": {"age": -1},
+            "skip": 1,
+            "limit": 1,
+            "projection": {"name": 1, "_id": 0}
+        }))
+        .await
+        .unwrap();
+
+        assert_eq!(response.count, 3);
+        assert_eq!(response.documents, vec![json!({"name": "Dan"})]);
+    }
+
+    #[tokio::test]
+    async fn test_filter_rejects_invalid_input() {
+        let (status, Json(error)) = run(json!({"query": {"age": {"$bogus": 1}}, "documents": []}))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, "UnsupportedOperation");
+
+        let (status, _) = run(json!({"query": {}, "documents": [], "sort": {"age": "desc"}}))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    async fn run_ndjson(uri: &str, headers: &[(&str, &str)], body: Body) -> (StatusCode, String) {
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let mut request = axum::http::Request::post(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let app = axum::Router::new()
+            .route("/filter/ndjson", post(filter_ndjson))
+            .with_state(AppState::default());
+        let response = app.oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_streams_matching_lines() {
+        // Lines are split across chunks, and the last one has no trailing newline
+        let chunks = ["{\"level\": \"error\", \"id\": 1}\n{\"lev", "el\": \"info\", \"id\": 2}\n\n", "{\"level\":\"error\",\"id\":3}"];
+        let body = Body::from_stream(stream::iter(chunks.map(Ok::<_, Infallible>)));
+
+        let (status, output) = run_ndjson("/filter/ndjson", &[("x-sift-query", r#"{"level": "error"}"#)], body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(output, "{\"level\": \"error\", \"id\": 1}\n{\"level\":\"error\",\"id\":3}\n");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_query_parameter_and_invalid_lines() {
+        let body = "{\"n\": 1}\nnot json\n{\"n\": 2}\n";
+        let uri = "/filter/ndjson?query=%7B%22n%22%3A%7B%22%24gt%22%3A0%7D%7D";
+
+        let (_, output) = run_ndjson(uri, &[], Body::from(body)).await;
+        let lines: Vec<Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines[0], json!({"n": 1}));
+        assert_eq!(lines[1]["error"], "InvalidJson");
+        assert_eq!(lines[1]["line"], 2);
+        assert_eq!(lines.len(), 2);
+
+        let (_, output) = run_ndjson(&format!("{}&skip_invalid=true", uri), &[], Body::from(body)).await;
+        assert_eq!(output, "{\"n\": 1}\n{\"n\": 2}\n");
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_requires_valid_query() {
+        let (status, _) = run_ndjson("/filter/ndjson", &[], Body::from("{}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = run_ndjson("/filter/ndjson", &[("x-sift-query", "{\"a\": {\"$bogus\": 1}}")], Body::from("{}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
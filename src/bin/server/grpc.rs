@@ -0,0 +1,500 @@
+// tonic::Status is large, but it is the error type of every RPC
+#![allow(clippy::result_large_err)]
+
+use crate::auth::{Caller, PolicyViolation};
+use crate::filter::FindOptions;
+use crate::validate::{ItemError, ValidationResult, Validator};
+use crate::{ApiError, AppState};
+use axum::http::StatusCode;
+use axum::response::Json;
+use futures_util::{stream, Stream};
+use serde_json::Value;
+use sift_rs::{CompiledQuery, SiftError};
+use std::pin::Pin;
+use std::sync::Arc;
+use tonic::{Code, Request, Response, Status, Streaming};
+use tracing::{info, warn};
+
+pub mod proto {
+    // The generated client is only used by tests
+    #![allow(dead_code)]
+    tonic::include_proto!("sift.v1");
+}
+
+use proto::filter_stream_request::Message as StreamMessage;
+use proto::sift_server::{Sift, SiftServer};
+
+/// Metadata key carrying the error code of a failed call, e.g. `InvalidQuery`
+const ERROR_CODE_KEY: &str = "sift-error";
+
+/// Build a status whose `sift-error` metadata holds the same error code the HTTP routes return
+fn status(code: Code, error: &str, message: impl Into<String>) -> Status {
+    let mut status = Status::new(code, message);
+    if let Ok(value) = error.parse() {
+        status.metadata_mut().insert(ERROR_CODE_KEY, value);
+    }
+    status
+}
+
+fn sift_status(error: &SiftError, message: String) -> Status {
+    status(Code::InvalidArgument, error.kind(), message)
+}
+
+impl From<PolicyViolation> for Status {
+    fn from(violation: PolicyViolation) -> Self {
+        status(Code::PermissionDenied, violation.error, violation.message)
+    }
+}
+
+/// Translate an HTTP error response into the matching gRPC status
+fn api_status((code, Json(body)): ApiError) -> Status {
+    let code = match code {
+        StatusCode::BAD_REQUEST => Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::FORBIDDEN => Code::PermissionDenied,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        _ => Code::Internal,
+    };
+    status(code, &body.error, body.message)
+}
+
+/// Parse a JSON field of a request, naming it in the error
+fn parse_json(text: &str, field: &str) -> Result<Value, Status> {
+    serde_json::from_str(text)
+        .map_err(|e| status(Code::InvalidArgument, "InvalidJson", format!("Invalid JSON in {}: {}", field, e)))
+}
+
+fn to_json(value: &Value) -> String {
+    serde_json::to_string(value).expect("JSON values serialize")
+}
+
+/// The gRPC `sift.v1.Sift` service, sharing the compile cache, metrics and API keys of the HTTP routes
+pub struct SiftService {
+    state: AppState,
+}
+
+impl SiftService {
+    pub fn server(state: AppState) -> SiftServer<SiftService> {
+        SiftServer::new(SiftService { state })
+    }
+
+    /// The caller identified by the request's `authorization` or `x-api-key` metadata
+    fn caller<T>(&self, request: &Request<T>) -> Result<Caller, Status> {
+        let Some(auth) = &self.state.auth else {
+            return Ok(Caller::anonymous());
+        };
+        auth.caller(&request.metadata().clone().into_headers()).ok_or_else(|| {
+            warn!("Rejected gRPC call without a valid API key");
+            status(Code::Unauthenticated, "Unauthorized", "A valid API key is required")
+        })
+    }
+
+    fn compile(&self, query: &Value) -> Result<Arc<CompiledQuery>, Status> {
+        self.state
+            .cache
+            .get_or_compile(query)
+            .map_err(|e| sift_status(&e, format!("Failed to compile query: {}", e)))
+    }
+
+    /// Run `work` on the blocking thread pool, since evaluating queries may run `$where` scripts
+    async fn blocking<T, F>(&self, work: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SiftService) -> Result<T, Status> + Send + 'static,
+    {
+        let service = SiftService {
+            state: self.state.clone(),
+        };
+        tokio::task::spawn_blocking(move || work(&service))
+            .await
+            .map_err(|e| status(Code::Internal, "EvaluationFailed", format!("Evaluation task failed: {}", e)))?
+    }
+
+    fn record<T>(&self, method: &str, result: &Result<T, Status>) {
+        let code = match result {
+            Ok(_) => Code::Ok,
+            Err(status) => status.code(),
+        };
+        self.state.metrics.record_grpc_request(method, &format!("{:?}", code));
+    }
+
+    fn validate_items(&self, caller: &Caller, request: proto::ValidateRequest) -> Result<proto::ValidateResponse, Status> {
+        caller.check_batch(request.items.len())?;
+
+        // Items with malformed JSON fail on their own, like items with invalid queries
+        let items: Vec<Result<(Value, Value), ItemError>> = request
+            .items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let parse = |text: &str, field: &str| {
+                    serde_json::from_str::<Value>(text).map_err(|e| ItemError {
+                        code: "InvalidJson".to_string(),
+                        message: format!("Invalid JSON: {}", e),
+                        path: format!("$[{}].{}", index, field),
+                    })
+                };
+                Ok((parse(&item.input, "input")?, parse(&item.query, "query")?))
+            })
+            .collect();
+        for (_, query) in items.iter().flatten() {
+            caller.check_query(query)?;
+        }
+
+        let mut validator = Validator::new(&self.state, request.dedupe);
+        let results = items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let result = match item {
+                    Ok((input, query)) => validator.validate(index, &input, &query),
+                    Err(error) => ValidationResult::Error { error },
+                };
+                let result = match result {
+                    ValidationResult::Valid { valid } => proto::validation_result::Result::Valid(valid),
+                    ValidationResult::Error { error } => proto::validation_result::Result::Error(proto::ItemError {
+                        code: error.code,
+                        message: error.message,
+                        path: error.path,
+                    }),
+                };
+                proto::ValidationResult { result: Some(result) }
+            })
+            .collect();
+        Ok(proto::ValidateResponse { results })
+    }
+
+    fn filter_documents(&self, caller: &Caller, request: proto::FilterRequest) -> Result<proto::FilterResponse, Status> {
+        let query = parse_json(&request.query, "query")?;
+        caller.check_query(&query)?;
+        caller.check_batch(request.documents.len())?;
+
+        let documents = request
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| parse_json(document, &format!("document {}", index)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let projection = request.projection.as_deref().map(|text| parse_json(text, "projection")).transpose()?;
+        let sort = request.sort.as_deref().map(|text| parse_json(text, "sort")).transpose()?;
+        let to_usize = |value: Option<u64>| value.map(|value| usize::try_from(value).unwrap_or(usize::MAX));
+
+        let compiled = self.compile(&query)?;
+        let options = FindOptions::parse(&projection, &sort, to_usize(request.skip), to_usize(request.limit))
+            .map_err(api_status)?;
+        let response = options
+            .select(&self.state.metrics, &compiled, &documents)
+            .map_err(api_status)?;
+
+        info!("gRPC filter completed: {} of {} documents matched", response.count, documents.len());
+        Ok(proto::FilterResponse {
+            documents: response.documents.iter().map(to_json).collect(),
+            count: response.count as u64,
+        })
+    }
+}
+
+type FilterStreamResult = Result<proto::FilterStreamResponse, Status>;
+
+/// Progress of one `FilterStream` call
+struct FilterStream {
+    state: AppState,
+    caller: Caller,
+    inbound: Streaming<proto::FilterStreamRequest>,
+    /// The current query and whether to skip invalid documents
+    query: Option<(Arc<CompiledQuery>, bool)>,
+    /// Number of documents received so far
+    received: u64,
+    finished: bool,
+}
+
+impl FilterStream {
+    /// Read messages until a document matches, the stream ends or a message fails
+    async fn next(&mut self) -> Option<FilterStreamResult> {
+        while !self.finished {
+            let message = match self.inbound.message().await {
+                Ok(Some(request)) => request.message,
+                Ok(None) => return None,
+                Err(status) => return self.fail(status),
+            };
+
+            let result = match message {
+                Some(StreamMessage::Query(query)) => self.set_query(query).map(|()| None),
+                Some(StreamMessage::Document(document)) => self.test(document).await,
+                None => Err(status(Code::InvalidArgument, "InvalidMessage", "Message must set a query or a document")),
+            };
+            match result {
+                Ok(Some(response)) => return Some(Ok(response)),
+                Ok(None) => continue,
+                Err(status) => return self.fail(status),
+            }
+        }
+        None
+    }
+
+    fn fail(&mut self, status: Status) -> Option<FilterStreamResult> {
+        self.finished = true;
+        Some(Err(status))
+    }
+
+    fn set_query(&mut self, query: proto::StreamQuery) -> Result<(), Status> {
+        let parsed = parse_json(&query.query, "query")?;
+        self.caller.check_query(&parsed)?;
+        let compiled = self
+            .state
+            .cache
+            .get_or_compile(&parsed)
+            .map_err(|e| sift_status(&e, format!("Failed to compile query: {}", e)))?;
+        self.query = Some((compiled, query.skip_invalid));
+        Ok(())
+    }
+
+    /// Test one document on the blocking thread pool, returning it when it matches
+    async fn test(&mut self, document: String) -> Result<Option<proto::FilterStreamResponse>, Status> {
+        let index = self.received;
+        self.received += 1;
+        self.caller
+            .check_batch(usize::try_from(self.received).unwrap_or(usize::MAX))?;
+        let Some((compiled, skip_invalid)) = &self.query else {
+            return Err(status(
+                Code::FailedPrecondition,
+                "InvalidQuery",
+                "Send a query before the first document",
+            ));
+        };
+
+        let skip_invalid = *skip_invalid;
+        let compiled = compiled.clone();
+        let metrics = self.state.metrics.clone();
+        let (document, result) = tokio::task::spawn_blocking(move || {
+            let result = serde_json::from_str::<Value>(&document)
+                .map_err(|e| status(Code::InvalidArgument, "InvalidJson", format!("Document {}: {}", index, e)))
+                .and_then(|value| {
+                    metrics
+                        .evaluate(&compiled, &value)
+                        .map_err(|e| sift_status(&e, format!("Document {}: {}", index, e)))
+                });
+            (document, result)
+        })
+        .await
+        .map_err(|e| status(Code::Internal, "EvaluationFailed", format!("Evaluation task failed: {}", e)))?;
+        match result {
+            Ok(true) => Ok(Some(proto::FilterStreamResponse { document, index })),
+            Ok(false) => Ok(None),
+            Err(_) if skip_invalid => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Sift for SiftService {
+    async fn validate(&self, request: Request<proto::ValidateRequest>) -> Result<Response<proto::ValidateResponse>, Status> {
+        let result = match self.caller(&request) {
+            Ok(caller) => {
+                let request = request.into_inner();
+                self.blocking(move |service| service.validate_items(&caller, request)).await
+            }
+            Err(status) => Err(status),
+        };
+        self.record("Validate", &result);
+        result.map(Response::new)
+    }
+
+    async fn filter(&self, request: Request<proto::FilterRequest>) -> Result<Response<proto::FilterResponse>, Status> {
+        let result = match self.caller(&request) {
+            Ok(caller) => {
+                let request = request.into_inner();
+                self.blocking(move |service| service.filter_documents(&caller, request)).await
+            }
+            Err(status) => Err(status),
+        };
+        self.record("Filter", &result);
+        result.map(Response::new)
+    }
+
+    type FilterStreamStream = Pin<Box<dyn Stream<Item = FilterStreamResult> + Send>>;
+
+    /// Documents are tested as they arrive; only matches are sent back
+    ///
+    /// Like `/filter/ndjson`, an invalid document ends the call with an error
+    /// unless the query set `skip_invalid`.
+    async fn filter_stream(
+        &self,
+        request: Request<Streaming<proto::FilterStreamRequest>>,
+    ) -> Result<Response<Self::FilterStreamStream>, Status> {
+        let caller = self.caller(&request);
+        self.record("FilterStream", &caller);
+        let stream = FilterStream {
+            state: self.state.clone(),
+            caller: caller?,
+            inbound: request.into_inner(),
+            query: None,
+            received: 0,
+            finished: false,
+        };
+
+        let responses = stream::unfold(stream, |mut stream| async move {
+            let response = stream.next().await?;
+            Some((response, stream))
+        });
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::sift_client::SiftClient;
+    use super::*;
+    use crate::auth::AuthConfig;
+    use serde_json::json;
+    use tonic::transport::{Channel, Server};
+
+    async fn connect(state: AppState) -> SiftClient<Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(SiftService::server(state))
+                .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener)),
+        );
+        SiftClient::connect(format!("http://{}", address)).await.unwrap()
+    }
+
+    fn error_code(status: &Status) -> &str {
+        status.metadata().get(ERROR_CODE_KEY).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_validate_and_filter() {
+        let state = AppState::default();
+        let mut client = connect(state.clone()).await;
+
+        let item = |input: &str, query: &str| proto::ValidationItem {
+            input: input.to_string(),
+            query: query.to_string(),
+        };
+        let request = proto::ValidateRequest {
+            items: vec![
+                item(r#"{"age": 30}"#, r#"{"age": {"$gte": 18}}"#),
+                item(r#"{"age": 30}"#, r#"{"age": {"$bogus": 1}}"#),
+                item("not json", "{}"),
+            ],
+            dedupe: false,
+        };
+        let results = client.validate(request).await.unwrap().into_inner().results;
+        let results: Vec<_> = results.into_iter().map(|result| result.result.unwrap()).collect();
+        assert_eq!(results[0], proto::validation_result::Result::Valid(true));
+        let proto::validation_result::Result::Error(error) = &results[1] else {
+            panic!("expected an error for item 1");
+        };
+        assert_eq!((error.code.as_str(), error.path.as_str()), ("UnsupportedOperation", "$[1].query"));
+        let proto::validation_result::Result::Error(error) = &results[2] else {
+            panic!("expected an error for item 2");
+        };
+        assert_eq!((error.code.as_str(), error.path.as_str()), ("InvalidJson", "$[2].input"));
+
+        let request = proto::FilterRequest {
+            query: r#"{"n": {"$gt": 1}}"#.to_string(),
+            documents: vec![r#"{"n": 1}"#.to_string(), r#"{"n": 3}"#.to_string(), r#"{"n": 2}"#.to_string()],
+            sort: Some(r#"{"n": 1}"#.to_string()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let response = client.filter(request).await.unwrap().into_inner();
+        assert_eq!((response.documents, response.count), (vec![r#"{"n":2}"#.to_string()], 2));
+
+        let request = proto::FilterRequest {
+            query: r#"{"n": {"$bogus": 1}}"#.to_string(),
+            ..Default::default()
+        };
+        let error = client.filter(request).await.unwrap_err();
+        assert_eq!((error.code(), error_code(&error)), (Code::InvalidArgument, "UnsupportedOperation"));
+
+        // Both transports share one cache and one set of metrics
+        let metrics = state.metrics.render();
+        assert!(metrics.contains(r#"sift_grpc_requests_total{code="Ok",method="Filter"} 1"#), "{}", metrics);
+        assert!(metrics.contains(r#"sift_documents_evaluated_total{result="match"} 3"#), "{}", metrics);
+    }
+
+    #[tokio::test]
+    async fn test_filter_stream() {
+        let mut client = connect(AppState::default()).await;
+        let query = |query: &str, skip_invalid: bool| proto::FilterStreamRequest {
+            message: Some(StreamMessage::Query(proto::StreamQuery {
+                query: query.to_string(),
+                skip_invalid,
+            })),
+        };
+        let document = |document: &str| proto::FilterStreamRequest {
+            message: Some(StreamMessage::Document(document.to_string())),
+        };
+
+        let requests = vec![
+            query(r#"{"level": "error"}"#, true),
+            document(r#"{"level": "info"}"#),
+            document("not json"),
+            document(r#"{"level": "error", "id": 1}"#),
+            query(r#"{"level": "info"}"#, false),
+            document(r#"{"level": "info", "id": 2}"#),
+        ];
+        let responses = collect(client.filter_stream(stream::iter(requests)).await.unwrap().into_inner()).await;
+        assert_eq!(
+            responses,
+            vec![
+                Ok((2, r#"{"level": "error", "id": 1}"#.to_string())),
+                Ok((3, r#"{"level": "info", "id": 2}"#.to_string())),
+            ]
+        );
+
+        let requests = vec![query("{}", false), document("{}"), document("not json"), document("{}")];
+        let responses = collect(client.filter_stream(stream::iter(requests)).await.unwrap().into_inner()).await;
+        assert_eq!(responses, vec![Ok((0, "{}".to_string())), Err(Code::InvalidArgument)]);
+
+        let requests = vec![document("{}")];
+        let responses = collect(client.filter_stream(stream::iter(requests)).await.unwrap().into_inner()).await;
+        assert_eq!(responses, vec![Err(Code::FailedPrecondition)]);
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_and_policies_apply() {
+        let auth: AuthConfig = serde_json::from_value(json!({
+            "keys": [{"name": "limited", "key": "secret", "denied_operators": ["$regex"]}]
+        }))
+        .unwrap();
+        let state = AppState {
+            auth: Some(Arc::new(auth)),
+            ..AppState::default()
+        };
+        let mut client = connect(state).await;
+        let filter = |query: &str| {
+            let mut request = Request::new(proto::FilterRequest {
+                query: query.to_string(),
+                ..Default::default()
+            });
+            request.metadata_mut().insert("x-api-key", "secret".parse().unwrap());
+            request
+        };
+
+        let error = client.filter(proto::FilterRequest::default()).await.unwrap_err();
+        assert_eq!(error.code(), Code::Unauthenticated);
+        assert!(client.filter(filter(r#"{"a": 1}"#)).await.is_ok());
+        let error = client.filter(filter(r#"{"a": {"$regex": "x"}}"#)).await.unwrap_err();
+        assert_eq!((error.code(), error_code(&error)), (Code::PermissionDenied, "OperatorNotAllowed"));
+    }
+
+    /// Collect `(index, document)` for each match, or the code of the error ending the stream
+    async fn collect(mut responses: Streaming<proto::FilterStreamResponse>) -> Vec<Result<(u64, String), Code>> {
+        let mut collected = Vec::new();
+        loop {
+            match responses.message().await {
+                Ok(Some(response)) => collected.push(Ok((response.index, response.document))),
+                Ok(None) => return collected,
+                Err(status) => {
+                    collected.push(Err(status.code()));
+                    return collected;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sift_rs::{Diagnostic, Severity};
+use tracing::info;
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LintRequest {
+    /// The MongoDB-style query to check
+    query: Value,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LintResponse {
+    /// Problems found in the query, in the order they appear in it
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+/// One problem found in the query
+#[derive(Debug, Serialize, PartialEq, ToSchema)]
+pub struct LintDiagnostic {
+    /// `info`, `warning`, or `error` when the query would be rejected
+    #[schema(value_type = String, example = "warning")]
+    severity: Severity,
+    /// Name of the check, e.g. `ExistsNotBoolean`
+    code: String,
+    message: String,
+    /// Location of the offending value in the query, e.g. `$.age.$in`
+    path: String,
+}
+
+impl From<Diagnostic> for LintDiagnostic {
+    fn from(diagnostic: Diagnostic) -> Self {
+        LintDiagnostic {
+            severity: diagnostic.severity,
+            code: diagnostic.code.to_string(),
+            message: diagnostic.message,
+            path: diagnostic.path,
+        }
+    }
+}
+
+/// Lint endpoint - reports invalid or suspicious parts of a query without running it
+///
+/// POST /lint
+/// Body: { "query": {...} }
+/// Response: { "diagnostics": [{ "severity", "code", "message", "path" }, ...] }
+///
+/// Unlike compiling, linting reports every problem at once, including valid
+/// queries that cannot match, such as `{"age": {"$gt": 65, "$lt": 18}}`.
+#[utoipa::path(
+    post,
+    path = "/lint",
+    tag = "queries",
+    request_body = LintRequest,
+    responses((status = 200, body = LintResponse))
+)]
+pub async fn lint(Json(request): Json<LintRequest>) -> Json<LintResponse> {
+    let diagnostics: Vec<LintDiagnostic> = sift_rs::lint(&request.query).into_iter().map(Into::into).collect();
+    info!("Lint completed: {} diagnostics", diagnostics.len());
+    Json(LintResponse { diagnostics })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_lint_reports_diagnostics() {
+        let request = LintRequest {
+            query: json!({"age": {"$gt": 65, "$lt": 18}, "deleted": {"$exists": "false"}}),
+        };
+        let Json(response) = lint(Json(request)).await;
+        let body = serde_json::to_value(&response).unwrap();
+        assert_eq!(body["diagnostics"][0]["code"], "UnsatisfiableRange");
+        assert_eq!(body["diagnostics"][0]["severity"], "warning");
+        assert_eq!(body["diagnostics"][1]["path"], "$.deleted.$exists");
+        assert_eq!(body["diagnostics"][1]["severity"], "error");
+    }
+}
@@ -0,0 +1,386 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    http::StatusCode,
+    middleware,
+    response::Json,
+    routing::{delete, get, patch, post},
+    Router,
+};
+use auth::AuthConfig;
+use cache::{CompileCache, DEFAULT_CAPACITY};
+use clap::Parser;
+use collections::Collections;
+use config::{Cli, LogFormat, ServerConfig};
+use metrics::Metrics;
+use queries::QueryStore;
+use subscriptions::Broker;
+use serde::Serialize;
+use sift_rs::{QueryOptions, SiftError};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceBuilder;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+mod auth;
+mod cache;
+mod collections;
+mod config;
+mod filter;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod lint;
+mod metrics;
+mod openapi;
+mod queries;
+mod subscriptions;
+mod validate;
+
+/// State shared by all request handlers
+#[derive(Clone)]
+pub struct AppState {
+    metrics: Arc<Metrics>,
+    cache: Arc<CompileCache>,
+    queries: Arc<QueryStore>,
+    collections: Arc<Collections>,
+    broker: Arc<Broker>,
+    /// API keys required by every route but `/health`; none when authentication is disabled
+    auth: Option<Arc<AuthConfig>>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        let metrics = Arc::new(Metrics::new());
+        AppState {
+            cache: Arc::new(CompileCache::new(DEFAULT_CAPACITY, metrics.clone())),
+            metrics,
+            queries: Arc::new(QueryStore::in_memory()),
+            collections: Arc::new(Collections::default()),
+            broker: Arc::new(Broker::default()),
+            auth: None,
+        }
+    }
+}
+
+/// Body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Machine-readable error code, e.g. `InvalidQuery` or `NotFound`
+    error: String,
+    message: String,
+}
+
+pub type ApiError = (StatusCode, Json<ErrorResponse>);
+
+/// Build an error response with the given status and error code
+fn api_error(status: StatusCode, error: &str, message: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message: message.to_string(),
+        }),
+    )
+}
+
+/// Build a 400 response with the given error code
+fn bad_request(error: &str, message: &str) -> ApiError {
+    api_error(StatusCode::BAD_REQUEST, error, message)
+}
+
+/// Build a 404 response
+fn not_found(message: &str) -> ApiError {
+    api_error(StatusCode::NOT_FOUND, "NotFound", message)
+}
+
+/// Build a 500 response with the given error code
+fn internal_error(error: &str, message: &str) -> ApiError {
+    api_error(StatusCode::INTERNAL_SERVER_ERROR, error, message)
+}
+
+/// Run query evaluation, which may execute `$where` scripts, on the blocking thread pool
+///
+/// Keeps a slow filter from stalling the async worker threads that serve every other request.
+async fn blocking<T, F>(work: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| internal_error("EvaluationFailed", &format!("Evaluation task failed: {}", e)))?
+}
+
+/// Longest accepted name for a stored query or collection
+const MAX_NAME_LENGTH: usize = 128;
+
+/// Check that a stored query or collection name is safe to use in URLs and files
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(bad_request(
+            "InvalidName",
+            &format!(
+                "Names must be 1 to {} characters of letters, digits, '_', '-' or '.'",
+                MAX_NAME_LENGTH
+            ),
+        ))
+    }
+}
+
+/// Build a 400 response for a sift error, using the error kind as the error code
+fn sift_error(error: &SiftError, message: String) -> ApiError {
+    bad_request(error.kind(), &message)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct HealthResponse {
+    status: String,
+    version: String,
+    message: String,
+}
+
+/// Health check endpoint
+#[utoipa::path(get, path = "/health", tag = "health", responses((status = 200, body = HealthResponse)))]
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        message: "Sift-rs API is running".to_string(),
+    })
+}
+
+/// Build the application routes
+fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/validate", post(validate::validate))
+        .route("/filter", post(filter::filter))
+        .route("/filter/ndjson", post(filter::filter_ndjson))
+        .route("/lint", post(lint::lint))
+        .route("/queries", get(queries::list))
+        .route(
+            "/queries/{name}",
+            get(queries::get).put(queries::put).delete(queries::delete),
+        )
+        .route("/queries/{name}/match", post(queries::match_documents))
+        .route("/collections", get(collections::list))
+        .route("/collections/{collection}", delete(collections::drop_collection))
+        .route("/collections/{collection}/documents", post(collections::insert))
+        .route("/collections/{collection}/find", post(collections::find))
+        .route("/collections/{collection}/update", patch(collections::update))
+        .route("/collections/{collection}/delete", post(collections::delete))
+        .route("/subscribe", get(subscriptions::subscribe))
+        .route("/publish", post(subscriptions::publish))
+        .route("/metrics", get(metrics::metrics))
+        .route("/openapi.json", get(openapi::openapi))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .route_layer(middleware::from_fn_with_state(state.clone(), metrics::track_requests))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() {
+    let config = ServerConfig::load(Cli::parse()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    // Initialize tracing
+    match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt().init(),
+        LogFormat::Json => tracing_subscriber::fmt().json().init(),
+    }
+    info!("Starting Sift-rs API server");
+
+    let metrics = Arc::new(Metrics::new());
+    let options = QueryOptions {
+        allow_where: !config.disable_where,
+        ..QueryOptions::default()
+    };
+    if config.disable_where {
+        info!("$where is disabled");
+    }
+    let cache = Arc::new(CompileCache::new(config.cache_capacity, metrics.clone()).with_options(options));
+    // Stored queries are kept in memory unless a file is configured
+    let queries = match &config.queries_file {
+        Some(path) => {
+            info!("Persisting stored queries to {}", path.display());
+            QueryStore::open(path, &cache).expect("Failed to load stored queries")
+        }
+        None => QueryStore::in_memory(),
+    };
+    // Every route but /health requires an API key once a key file is configured
+    let auth = config.auth_file.as_ref().map(|path| {
+        info!("Loading API keys from {}", path.display());
+        Arc::new(AuthConfig::load(path).expect("Failed to load API keys"))
+    });
+
+    // Cancelled on SIGTERM or Ctrl-C; every server and the live-query broker stop on it
+    let shutdown = CancellationToken::new();
+    let state = AppState {
+        metrics,
+        cache,
+        queries: Arc::new(queries),
+        collections: Arc::new(Collections::default()),
+        broker: Arc::new(Broker::new(shutdown.clone())),
+        auth,
+    };
+
+    // Build the application with routes
+    let cors = config.cors_layer().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let app = routes(state.clone()).layer(
+        ServiceBuilder::new()
+            .layer(cors)
+            .layer(DefaultBodyLimit::max(config.max_body)),
+    );
+
+    info!("Available endpoints:");
+    info!("  GET  /health    - Health check");
+    info!("  POST /validate  - Validate array of input/query pairs");
+    info!("  POST /filter    - Filter an array of documents with one query");
+    info!("  POST /filter/ndjson - Stream matching lines of an NDJSON body");
+    info!("  POST /lint      - Report invalid or suspicious parts of a query");
+    info!("  GET  /queries   - List stored queries");
+    info!("  PUT|GET|DELETE /queries/{{name}} - Manage a stored query");
+    info!("  POST /queries/{{name}}/match - Test documents against a stored query");
+    info!("  GET  /collections - List in-memory collections");
+    info!("  POST /collections/{{collection}}/documents - Insert documents");
+    info!("  POST /collections/{{collection}}/find - Find documents");
+    info!("  PATCH /collections/{{collection}}/update - Update documents");
+    info!("  POST /collections/{{collection}}/delete - Delete documents");
+    info!("  GET  /subscribe - WebSocket for live-query subscriptions");
+    info!("  POST /publish   - Publish documents to matching subscriptions");
+    info!("  GET  /metrics   - Prometheus metrics");
+    info!("  GET  /openapi.json - OpenAPI document for this API");
+
+    // Stop accepting connections on SIGTERM or Ctrl-C, close live-query
+    // sockets, and give in-flight requests time to finish
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        let timeout = config.shutdown_timeout;
+        async move {
+            shutdown_signal().await;
+            info!("Shutting down; waiting up to {}s for in-flight requests", timeout);
+            shutdown.cancel();
+        }
+    });
+
+    let http = async {
+        match config.tls() {
+            Some((cert, key)) => serve_tls(app, config.bind, cert, key, &shutdown, config.shutdown_timeout()).await,
+            None => serve_http(app, config.bind, &shutdown, config.shutdown_timeout()).await,
+        }
+    };
+    let grpc = async {
+        if let Some(bind) = config.grpc_bind {
+            serve_grpc(state, bind, &shutdown, config.shutdown_timeout()).await;
+        }
+    };
+    tokio::join!(http, grpc);
+    info!("Server stopped");
+}
+
+/// Resolve `timeout` after shutdown starts, to stop waiting for in-flight requests
+async fn shutdown_deadline(shutdown: &CancellationToken, timeout: Duration) {
+    shutdown.cancelled().await;
+    tokio::time::sleep(timeout).await;
+}
+
+async fn serve_http(app: Router, bind: SocketAddr, shutdown: &CancellationToken, timeout: Duration) {
+    let listener = tokio::net::TcpListener::bind(bind)
+        .await
+        .expect("Failed to bind to address");
+    info!("Server running on http://{}", bind);
+
+    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    tokio::select! {
+        result = server => result.expect("Server failed to start"),
+        _ = shutdown_deadline(shutdown, timeout) => warn!("In-flight requests did not finish in time"),
+    }
+}
+
+#[cfg(feature = "tls")]
+async fn serve_tls(
+    app: Router,
+    bind: SocketAddr,
+    cert: &Path,
+    key: &Path,
+    shutdown: &CancellationToken,
+    timeout: Duration,
+) {
+    let tls = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert, key)
+        .await
+        .expect("Failed to load TLS certificate and key");
+
+    let handle = axum_server::Handle::new();
+    let server = axum_server::bind_rustls(bind, tls)
+        .handle(handle.clone())
+        .serve(app.into_make_service());
+    let stopping = async {
+        shutdown.cancelled().await;
+        handle.graceful_shutdown(Some(timeout));
+        std::future::pending::<()>().await
+    };
+
+    info!("Server running on https://{}", bind);
+    tokio::select! {
+        result = server => result.expect("Server failed to start"),
+        _ = stopping => {}
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+async fn serve_tls(_: Router, _: SocketAddr, _: &Path, _: &Path, _: &CancellationToken, _: Duration) {
+    eprintln!("TLS requires building the server with the `tls` feature");
+    std::process::exit(2);
+}
+
+#[cfg(feature = "grpc")]
+async fn serve_grpc(state: AppState, bind: SocketAddr, shutdown: &CancellationToken, timeout: Duration) {
+    info!("gRPC service running on {}", bind);
+    let server = tonic::transport::Server::builder()
+        .add_service(grpc::SiftService::server(state))
+        .serve_with_shutdown(bind, shutdown.cancelled());
+    tokio::select! {
+        result = server => result.expect("gRPC server failed to start"),
+        _ = shutdown_deadline(shutdown, timeout) => warn!("In-flight gRPC calls did not finish in time"),
+    }
+}
+
+#[cfg(not(feature = "grpc"))]
+async fn serve_grpc(_: AppState, _: SocketAddr, _: &CancellationToken, _: Duration) {
+    eprintln!("gRPC requires building the server with the `grpc` feature");
+    std::process::exit(2);
+}
+
+/// Resolve on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for Ctrl-C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
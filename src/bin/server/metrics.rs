@@ -0,0 +1,296 @@
+use crate::AppState;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use serde_json::Value;
+use sift_rs::{CompiledQuery, CompiledScript, ScriptEngine, SiftError, SiftResult, WhereLimits};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Prometheus metrics for the server, exported by `GET /metrics`
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    #[cfg(feature = "grpc")]
+    grpc_requests: IntCounterVec,
+    documents: IntCounterVec,
+    compile_errors: IntCounterVec,
+    cache_lookups: IntCounterVec,
+    cache_hit_ratio: Gauge,
+    where_duration: Histogram,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("sift_http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("sift_http_request_duration_seconds", "Time to produce each HTTP response"),
+            &["method", "route"],
+        )
+        .unwrap();
+        #[cfg(feature = "grpc")]
+        let grpc_requests = IntCounterVec::new(
+            Opts::new("sift_grpc_requests_total", "gRPC calls by method and status code"),
+            &["method", "code"],
+        )
+        .unwrap();
+        let documents = IntCounterVec::new(
+            Opts::new(
+                "sift_documents_evaluated_total",
+                "Documents tested against a query, by result: match, no_match or error",
+            ),
+            &["result"],
+        )
+        .unwrap();
+        let compile_errors = IntCounterVec::new(
+            Opts::new("sift_compile_errors_total", "Queries that failed to compile, by error kind"),
+            &["kind"],
+        )
+        .unwrap();
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("sift_compile_cache_lookups_total", "Compile cache lookups, by result: hit or miss"),
+            &["result"],
+        )
+        .unwrap();
+        let cache_hit_ratio = Gauge::new(
+            "sift_compile_cache_hit_ratio",
+            "Fraction of compile cache lookups served from the cache",
+        )
+        .unwrap();
+        let where_duration = Histogram::with_opts(
+            HistogramOpts::new("sift_where_evaluation_seconds", "Time spent evaluating each $where script")
+                .buckets(prometheus::exponential_buckets(0.00001, 4.0, 10).unwrap()),
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        #[cfg(feature = "grpc")]
+        registry.register(Box::new(grpc_requests.clone())).unwrap();
+        registry.register(Box::new(documents.clone())).unwrap();
+        registry.register(Box::new(compile_errors.clone())).unwrap();
+        registry.register(Box::new(cache_lookups.clone())).unwrap();
+        registry.register(Box::new(cache_hit_ratio.clone())).unwrap();
+        registry.register(Box::new(where_duration.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            #[cfg(feature = "grpc")]
+            grpc_requests,
+            documents,
+            compile_errors,
+            cache_lookups,
+            cache_hit_ratio,
+            where_duration,
+        }
+    }
+
+    /// Test a document against a compiled query, counting the result
+    pub fn evaluate(&self, compiled: &CompiledQuery, document: &Value) -> SiftResult<bool> {
+        let result = compiled.test(document);
+        let label = match result {
+            Ok(true) => "match",
+            Ok(false) => "no_match",
+            Err(_) => "error",
+        };
+        self.documents.with_label_values(&[label]).inc();
+        result
+    }
+
+    /// Count a gRPC call by its method and status code, e.g. `Ok` or `InvalidArgument`
+    #[cfg(feature = "grpc")]
+    pub fn record_grpc_request(&self, method: &str, code: &str) {
+        self.grpc_requests.with_label_values(&[method, code]).inc();
+    }
+
+    pub fn record_compile_error(&self, error: &SiftError) {
+        self.compile_errors.with_label_values(&[error.kind()]).inc();
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let label = if hit { "hit" } else { "miss" };
+        self.cache_lookups.with_label_values(&[label]).inc();
+    }
+
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_lookups.with_label_values(&["hit"]).get()
+    }
+
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_lookups.with_label_values(&["miss"]).get()
+    }
+
+    /// Wrap a `$where` engine so the time spent in its scripts is recorded
+    pub fn timed_engine(&self, engine: Arc<dyn ScriptEngine>) -> Arc<dyn ScriptEngine> {
+        Arc::new(TimedEngine {
+            engine,
+            duration: self.where_duration.clone(),
+        })
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let lookups = self.cache_hits() + self.cache_misses();
+        if lookups > 0 {
+            self.cache_hit_ratio.set(self.cache_hits() as f64 / lookups as f64);
+        }
+
+        let mut output = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut output)
+            .expect("metrics encode as text");
+        String::from_utf8(output).expect("metrics text is UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+struct TimedEngine {
+    engine: Arc<dyn ScriptEngine>,
+    duration: Histogram,
+}
+
+impl ScriptEngine for TimedEngine {
+    fn compile(&self, source: &str, limits: &WhereLimits) -> SiftResult<Box<dyn CompiledScript>> {
+        Ok(Box::new(TimedScript {
+            script: self.engine.compile(source, limits)?,
+            duration: self.duration.clone(),
+        }))
+    }
+
+    fn name(&self) -> &'static str {
+        self.engine.name()
+    }
+}
+
+struct TimedScript {
+    script: Box<dyn CompiledScript>,
+    duration: Histogram,
+}
+
+impl CompiledScript for TimedScript {
+    fn evaluate(&self, value: &Value) -> SiftResult<bool> {
+        let _timer = self.duration.start_timer();
+        self.script.evaluate(value)
+    }
+}
+
+/// Middleware counting requests and timing responses per matched route
+///
+/// For streamed responses such as `/filter/ndjson` the time covers producing
+/// the response headers, not the whole body.
+pub async fn track_requests(
+    State(state): State<AppState>,
+    route: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = route.map_or_else(|| "unmatched".to_string(), |route| route.as_str().to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let metrics = &state.metrics;
+    metrics
+        .request_duration
+        .with_label_values(&[method.as_str(), route.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .requests
+        .with_label_values(&[method.as_str(), route.as_str(), response.status().as_str()])
+        .inc();
+    response
+}
+
+/// Metrics endpoint
+///
+/// GET /metrics
+/// Response: all metrics in the Prometheus text exposition format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "Prometheus text exposition format", body = String, content_type = "text/plain"))
+)]
+pub async fn metrics(State(state): State<AppState>) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, method: &str, uri: &str, body: Option<Value>) -> String {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = crate::routes(state.clone())
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_metrics_cover_requests_documents_and_cache() {
+        let state = AppState::default();
+        let request = json!({"query": {"age": {"$gte": 18}}, "documents": [{"age": 30}, {"age": 12}, {"age": 40}]});
+        send(&state, "POST", "/filter", Some(request.clone())).await;
+        send(&state, "POST", "/filter", Some(request)).await;
+        send(&state, "POST", "/filter", Some(json!({"query": {"a": {"$bogus": 1}}, "documents": []}))).await;
+
+        let text = send(&state, "GET", "/metrics", None).await;
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            r#"sift_http_requests_total{method="POST",route="/filter",status="200"} 2"#,
+            r#"sift_http_requests_total{method="POST",route="/filter",status="400"} 1"#,
+            r#"sift_http_request_duration_seconds_count{method="POST",route="/filter"} 3"#,
+            r#"sift_documents_evaluated_total{result="match"} 4"#,
+            r#"sift_documents_evaluated_total{result="no_match"} 2"#,
+            r#"sift_compile_errors_total{kind="UnsupportedOperation"} 1"#,
+            r#"sift_compile_cache_lookups_total{result="hit"} 1"#,
+            r#"sift_compile_cache_lookups_total{result="miss"} 2"#,
+        ] {
+            assert!(lines.contains(&expected), "missing {} in\n{}", expected, text);
+        }
+        assert!(text.contains("sift_compile_cache_hit_ratio 0.33"));
+    }
+
+    #[cfg(feature = "boa")]
+    #[tokio::test]
+    async fn test_where_evaluation_is_timed() {
+        let state = AppState::default();
+        let request = json!({"query": {"$where": "this.age > 18"}, "documents": [{"age": 30}, {"age": 12}]});
+        send(&state, "POST", "/filter", Some(request)).await;
+
+        let text = state.metrics.render();
+        assert!(text.lines().any(|line| line == "sift_where_evaluation_seconds_count 2"), "{}", text);
+    }
+}
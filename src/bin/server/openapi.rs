@@ -0,0 +1,183 @@
+use crate::{collections, filter, lint, metrics, queries, subscriptions, validate, AppState};
+use axum::extract::State;
+use axum::response::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::openapi::OpenApi as Document;
+use utoipa::OpenApi;
+
+/// The OpenAPI document, generated from the handler and request/response types
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Sift-rs API", description = "MongoDB-style queries over JSON documents"),
+    paths(
+        crate::health,
+        validate::validate,
+        filter::filter,
+        filter::filter_ndjson,
+        lint::lint,
+        queries::list,
+        queries::get,
+        queries::put,
+        queries::delete,
+        queries::match_documents,
+        collections::list,
+        collections::drop_collection,
+        collections::insert,
+        collections::find,
+        collections::update,
+        collections::delete,
+        subscriptions::subscribe,
+        subscriptions::publish,
+        metrics::metrics,
+        openapi,
+    )
+)]
+pub struct ApiDoc;
+
+/// Build the document for this server, declaring the API key schemes when authentication is enabled
+pub fn document(state: &AppState) -> Document {
+    let mut document = ApiDoc::openapi();
+    if state.auth.is_none() {
+        return document;
+    }
+
+    let components = document.components.get_or_insert_with(Default::default);
+    components.add_security_scheme(
+        "bearer",
+        SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+    );
+    components.add_security_scheme(
+        "api_key",
+        SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+    );
+    // Either scheme is accepted; /health, /metrics and this document stay public
+    document.security = Some(vec![
+        SecurityRequirement::new("bearer", Vec::<String>::new()),
+        SecurityRequirement::new("api_key", Vec::<String>::new()),
+    ]);
+    for route in crate::auth::PUBLIC_ROUTES {
+        if let Some(item) = document.paths.paths.get_mut(*route) {
+            if let Some(operation) = item.get.as_mut() {
+                operation.security = Some(vec![SecurityRequirement::default()]);
+            }
+        }
+    }
+    document
+}
+
+/// OpenAPI endpoint
+///
+/// GET /openapi.json
+/// Response: the OpenAPI 3.1 document describing every route of this server
+#[utoipa::path(get, path = "/openapi.json", tag = "health", responses((status = 200, description = "The OpenAPI document")))]
+pub async fn openapi(State(state): State<AppState>) -> Json<Document> {
+    Json(document(&state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthConfig;
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_document_covers_every_route() {
+        let document = serde_json::to_value(document(&AppState::default())).unwrap();
+        let paths = document["paths"].as_object().unwrap();
+        for (path, method) in [
+            ("/health", "get"),
+            ("/validate", "post"),
+            ("/filter", "post"),
+            ("/filter/ndjson", "post"),
+            ("/lint", "post"),
+            ("/queries", "get"),
+            ("/queries/{name}", "put"),
+            ("/queries/{name}/match", "post"),
+            ("/collections/{collection}", "delete"),
+            ("/collections/{collection}/update", "patch"),
+            ("/publish", "post"),
+            ("/metrics", "get"),
+        ] {
+            assert!(paths.get(path).and_then(|item| item.get(method)).is_some(), "missing {} {}", method, path);
+        }
+
+        let schemas = &document["components"]["schemas"];
+        assert_eq!(schemas["FilterRequest"]["required"], serde_json::json!(["query", "documents"]));
+        assert!(schemas["ValidationResult"]["oneOf"].is_array());
+        assert!(schemas["ErrorResponse"].is_object());
+        assert_eq!(document["security"], Value::Null);
+    }
+
+    #[test]
+    fn test_document_declares_api_keys_when_authentication_is_enabled() {
+        let auth: AuthConfig = serde_json::from_value(serde_json::json!({"keys": [{"name": "a", "key": "k"}]})).unwrap();
+        let state = AppState {
+            auth: Some(Arc::new(auth)),
+            ..AppState::default()
+        };
+        let document = serde_json::to_value(document(&state)).unwrap();
+
+        assert_eq!(document["components"]["securitySchemes"]["api_key"]["name"], "X-API-Key");
+        assert_eq!(document["security"].as_array().unwrap().len(), 2);
+        assert_eq!(document["paths"]["/health"]["get"]["security"], serde_json::json!([{}]));
+        assert_eq!(document["paths"]["/metrics"]["get"]["security"], serde_json::json!([{}]));
+        assert_eq!(document["paths"]["/filter"]["post"]["security"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_client_round_trips_against_the_server() {
+        use serde_json::json;
+        use sift_rs_client::{FilterRequest, FindRequest, SiftClient, UpdateRequest, ValidationItem, ValidationResult};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = SiftClient::new(format!("http://{}", listener.local_addr().unwrap()));
+        let app = crate::routes(AppState::default());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        assert_eq!(client.health().await.unwrap().status, "healthy");
+        assert!(client.matches(&json!({"age": {"$gte": 18}}), &json!({"age": 30})).await.unwrap());
+        let results = client
+            .validate(&[ValidationItem {
+                input: json!({}),
+                query: json!({"a": {"$bogus": 1}}),
+            }])
+            .await
+            .unwrap();
+        assert!(matches!(&results[0], ValidationResult::Error { error } if error.code == "UnsupportedOperation"));
+
+        let filtered = client
+            .filter(&FilterRequest {
+                query: json!({"n": {"$gt": 1}}),
+                documents: vec![json!({"n": 1}), json!({"n": 2}), json!({"n": 3})],
+                limit: Some(1),
+                ..FilterRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!((filtered.documents, filtered.count), (vec![json!({"n": 2})], 2));
+
+        let diagnostics = client.lint(&json!({"tags": {"$in": "a"}})).await.unwrap();
+        assert_eq!((diagnostics[0].code.as_str(), diagnostics[0].severity.as_str()), ("InNotArray", "error"));
+
+        client.put_query("adults", &json!({"age": {"$gte": 18}})).await.unwrap();
+        let matched = client.match_query("adults", &[json!({"age": 4}), json!({"age": 40})]).await.unwrap();
+        assert_eq!(matched.matches, vec![false, true]);
+        client.delete_query("adults").await.unwrap();
+        let error = client.get_query("adults").await.unwrap_err();
+        assert_eq!(error.code(), Some("NotFound"));
+
+        client.insert("people", &[json!({"_id": 1, "age": 30})]).await.unwrap();
+        let update = UpdateRequest {
+            update: json!({"$inc": {"age": 1}}),
+            ..UpdateRequest::default()
+        };
+        assert_eq!(client.update("people", &update).await.unwrap().modified, 1);
+        let found = client.find("people", &FindRequest::default()).await.unwrap();
+        assert_eq!(found.documents, vec![json!({"_id": 1, "age": 31})]);
+        assert_eq!(client.list_collections().await.unwrap()[0].count, 1);
+        client.drop_collection("people").await.unwrap();
+
+        assert_eq!(client.openapi().await.unwrap()["openapi"], "3.1.0");
+    }
+}
@@ -0,0 +1,429 @@
+use crate::auth::Caller;
+use crate::cache::CompileCache;
+use crate::{blocking, internal_error, not_found, sift_error, validate_name, ApiError, AppState, ErrorResponse};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+/// Named queries saved on the server, optionally persisted to a JSON file
+///
+/// The file holds a single object mapping each name to its query document.
+/// It is rewritten after every change, so it survives restarts.
+pub struct QueryStore {
+    queries: RwLock<Map<String, Value>>,
+    path: Option<PathBuf>,
+    // Serializes file writes so they land in the order the changes were made
+    persist: tokio::sync::Mutex<()>,
+}
+
+impl QueryStore {
+    /// Create an empty store that is not persisted
+    pub fn in_memory() -> Self {
+        QueryStore {
+            queries: RwLock::new(Map::new()),
+            path: None,
+            persist: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Open a store persisted to `path`, loading any queries already saved there
+    ///
+    /// Saved queries that no longer compile, e.g. `$where` queries after
+    /// restarting with `--disable-where`, are kept with a warning: they are
+    /// still listed and persisted, but matching against them fails.
+    pub fn open(path: impl Into<PathBuf>, cache: &CompileCache) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut queries = Map::new();
+
+        match std::fs::read(&path) {
+            Ok(contents) => {
+                let saved: Map<String, Value> = serde_json::from_slice(&contents)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                for (name, query) in saved {
+                    if let Err(e) = cache.get_or_compile(&query) {
+                        warn!("Stored query '{}' does not compile: {}", name, e);
+                    }
+                    queries.insert(name, query);
+                }
+                info!("Loaded {} stored queries from {}", queries.len(), path.display());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(QueryStore {
+            queries: RwLock::new(queries),
+            path: Some(path),
+            persist: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    // The map is only ever replaced in one assignment, so a poisoned lock still guards a whole map
+    fn read(&self) -> RwLockReadGuard<'_, Map<String, Value>> {
+        self.queries.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn replace(&self, queries: Map<String, Value>) {
+        *self.queries.write().unwrap_or_else(PoisonError::into_inner) = queries;
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.read().get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<StoredQuery> {
+        let queries = self.read();
+        queries
+            .iter()
+            .map(|(name, query)| StoredQuery { name: name.clone(), query: query.clone() })
+            .collect()
+    }
+
+    /// Save `query` under `name`, returning whether it replaced an existing query
+    ///
+    /// The change is only made once it is persisted, so a failed save leaves the store as it was.
+    pub async fn insert(&self, name: &str, query: Value) -> std::io::Result<bool> {
+        let _guard = self.persist.lock().await;
+        let mut queries = self.read().clone();
+        let replaced = queries.insert(name.to_string(), query).is_some();
+        self.save(&queries).await?;
+        self.replace(queries);
+        Ok(replaced)
+    }
+
+    /// Remove the query saved under `name`, returning whether it existed
+    pub async fn remove(&self, name: &str) -> std::io::Result<bool> {
+        let _guard = self.persist.lock().await;
+        let mut queries = self.read().clone();
+        if queries.shift_remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&queries).await?;
+        self.replace(queries);
+        Ok(true)
+    }
+
+    async fn save(&self, queries: &Map<String, Value>) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let contents = serde_json::to_vec_pretty(queries)?;
+
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let temporary = path.with_extension("json.tmp");
+        tokio::fs::write(&temporary, contents).await?;
+        tokio::fs::rename(&temporary, path).await
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct StoredQuery {
+    name: String,
+    query: Value,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SaveQueryRequest {
+    /// The MongoDB-style query to store
+    query: Value,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MatchRequest {
+    /// The documents to test against the stored query
+    documents: Vec<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MatchResponse {
+    /// Whether each document matches, in request order
+    matches: Vec<bool>,
+    /// Number of matching documents
+    count: usize,
+}
+
+fn query_not_found(name: &str) -> ApiError {
+    not_found(&format!("No stored query named '{}'", name))
+}
+
+fn storage_error(error: std::io::Error) -> ApiError {
+    warn!("Failed to persist stored queries: {}", error);
+    internal_error("StorageError", &format!("Failed to persist stored queries: {}", error))
+}
+
+/// List stored queries
+///
+/// GET /queries
+/// Response: [{ "name": "...", "query": {...} }, ...]
+#[utoipa::path(get, path = "/queries", tag = "stored queries", responses((status = 200, body = Vec<StoredQuery>)))]
+pub async fn list(State(state): State<AppState>) -> Json<Vec<StoredQuery>> {
+    Json(state.queries.list())
+}
+
+/// Get one stored query
+///
+/// GET /queries/{name}
+/// Response: { "name": "...", "query": {...} }
+#[utoipa::path(
+    get,
+    path = "/queries/{name}",
+    tag = "stored queries",
+    params(("name" = String, Path, description = "Name of the stored query")),
+    responses(
+        (status = 200, body = StoredQuery),
+        (status = 404, body = ErrorResponse),
+    )
+)]
+pub async fn get(State(state): State<AppState>, Path(name): Path<String>) -> Result<Json<StoredQuery>, ApiError> {
+    let query = state.queries.get(&name).ok_or_else(|| query_not_found(&name))?;
+    Ok(Json(StoredQuery { name, query }))
+}
+
+/// Store a query under a name, replacing any query already saved there
+///
+/// PUT /queries/{name}
+/// Body: { "query": {...} }
+/// Response: 201 when created, 200 when replaced, with { "name": "...", "query": {...} }
+///
+/// The query is compiled before it is stored, so invalid queries are rejected.
+#[utoipa::path(
+    put,
+    path = "/queries/{name}",
+    tag = "stored queries",
+    params(("name" = String, Path, description = "Name of the stored query")),
+    request_body = SaveQueryRequest,
+    responses(
+        (status = 200, description = "An existing query was replaced", body = StoredQuery),
+        (status = 201, description = "The query was created", body = StoredQuery),
+        (status = 400, description = "The name or query is invalid", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+        (status = 500, description = "The query could not be persisted", body = ErrorResponse),
+    )
+)]
+pub async fn put(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<SaveQueryRequest>,
+) -> Result<(StatusCode, Json<StoredQuery>), ApiError> {
+    validate_name(&name)?;
+    caller.check_query(&request.query)?;
+    state
+        .cache
+        .get_or_compile(&request.query)
+        .map_err(|e| sift_error(&e, format!("Failed to compile query: {}", e)))?;
+
+    let replaced = state
+        .queries
+        .insert(&name, request.query.clone())
+        .await
+        .map_err(storage_error)?;
+    info!("Stored query '{}'", name);
+
+    let status = if replaced { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(StoredQuery { name, query: request.query })))
+}
+
+/// Delete a stored query
+///
+/// DELETE /queries/{name}
+/// Response: 204 when deleted, 404 when there was no such query
+#[utoipa::path(
+    delete,
+    path = "/queries/{name}",
+    tag = "stored queries",
+    params(("name" = String, Path, description = "Name of the stored query")),
+    responses(
+        (status = 204, description = "The query was deleted"),
+        (status = 404, body = ErrorResponse),
+        (status = 500, description = "The deletion could not be persisted", body = ErrorResponse),
+    )
+)]
+pub async fn delete(State(state): State<AppState>, Path(name): Path<String>) -> Result<StatusCode, ApiError> {
+    if state.queries.remove(&name).await.map_err(storage_error)? {
+        info!("Deleted stored query '{}'", name);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(query_not_found(&name))
+    }
+}
+
+/// Test documents against a stored query
+///
+/// POST /queries/{name}/match
+/// Body: { "documents": [...] }
+/// Response: { "matches": [true, false, ...], "count": n }
+#[utoipa::path(
+    post,
+    path = "/queries/{name}/match",
+    tag = "stored queries",
+    params(("name" = String, Path, description = "Name of the stored query")),
+    request_body = MatchRequest,
+    responses(
+        (status = 200, body = MatchResponse),
+        (status = 400, description = "A document could not be evaluated", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+    )
+)]
+pub async fn match_documents(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(name): Path<String>,
+    Json(request): Json<MatchRequest>,
+) -> Result<Json<MatchResponse>, ApiError> {
+    let query = state.queries.get(&name).ok_or_else(|| query_not_found(&name))?;
+    // The query may have been stored by a key with a different policy
+    caller.check_query(&query)?;
+    caller.check_batch(request.documents.len())?;
+    let compiled = state
+        .cache
+        .get_or_compile(&query)
+        .map_err(|e| sift_error(&e, format!("Failed to compile query: {}", e)))?;
+
+    let metrics = state.metrics.clone();
+    let matches = blocking(move || {
+        request
+            .documents
+            .iter()
+            .map(|document| metrics.evaluate(&compiled, document))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| sift_error(&e, format!("Failed to evaluate query: {}", e)))
+    })
+    .await?;
+    let count = matches.iter().filter(|&&matched| matched).count();
+
+    info!("Matched {} of {} documents against '{}'", count, matches.len(), name);
+    debug!(
+        "Compile cache: {} hits, {} misses",
+        state.cache.hits(),
+        state.cache.misses()
+    );
+    Ok(Json(MatchResponse { matches, count }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = crate::routes(state.clone())
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_stored_query_lifecycle() {
+        let state = AppState::default();
+        let adults = json!({"query": {"age": {"$gte": 18}}});
+
+        let (status, _) = send(&state, "PUT", "/queries/adults", Some(adults.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = send(&state, "PUT", "/queries/adults", Some(adults)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(&state, "GET", "/queries", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([{"name": "adults", "query": {"age": {"$gte": 18}}}]));
+
+        let documents = json!({"documents": [{"age": 30}, {"age": 12}, {"age": 18}]});
+        let (status, body) = send(&state, "POST", "/queries/adults/match", Some(documents)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"matches": [true, false, true], "count": 2}));
+        // The second PUT and the match both reuse the compiled query
+        assert_eq!(state.cache.hits(), 2);
+
+        let (status, _) = send(&state, "DELETE", "/queries/adults", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, body) = send(&state, "GET", "/queries/adults", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "NotFound");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_queries_and_names_are_rejected() {
+        let state = AppState::default();
+
+        let (status, body) = send(&state, "PUT", "/queries/bad", Some(json!({"query": {"a": {"$bogus": 1}}}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "UnsupportedOperation");
+
+        let (status, body) = send(&state, "PUT", "/queries/no%20spaces", Some(json!({"query": {}}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "InvalidName");
+
+        let (status, _) = send(&state, "POST", "/queries/missing/match", Some(json!({"documents": []}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(state.queries.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_queries_persist_across_restarts() {
+        let path = std::env::temp_dir().join(format!("sift-stored-queries-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let cache = CompileCache::default();
+        let store = QueryStore::open(&path, &cache).unwrap();
+        store.insert("errors", json!({"level": "error"})).await.unwrap();
+        store.insert("recent", json!({"ts": {"$gt": 100}})).await.unwrap();
+        store.remove("recent").await.unwrap();
+
+        let reopened = QueryStore::open(&path, &CompileCache::default()).unwrap();
+        assert_eq!(reopened.list(), vec![StoredQuery { name: "errors".to_string(), query: json!({"level": "error"}) }]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queries_that_no_longer_compile_are_kept() {
+        let path = std::env::temp_dir().join(format!("sift-uncompilable-queries-{}.json", std::process::id()));
+        std::fs::write(&path, json!({"scripted": {"$where": "this.a > 1"}}).to_string()).unwrap();
+
+        let options = sift_rs::QueryOptions {
+            allow_where: false,
+            ..sift_rs::QueryOptions::default()
+        };
+        let metrics = std::sync::Arc::new(crate::metrics::Metrics::new());
+        let cache = CompileCache::new(crate::cache::DEFAULT_CAPACITY, metrics).with_options(options);
+        let store = QueryStore::open(&path, &cache).unwrap();
+        assert_eq!(store.get("scripted"), Some(json!({"$where": "this.a > 1"})));
+        store.insert("errors", json!({"level": "error"})).await.unwrap();
+
+        let saved: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved, json!({"scripted": {"$where": "this.a > 1"}, "errors": {"level": "error"}}));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_save_leaves_store_unchanged() {
+        let directory = std::env::temp_dir().join(format!("sift-stored-queries-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let store = QueryStore::open(directory.join("queries.json"), &CompileCache::default()).unwrap();
+        store.insert("errors", json!({"level": "error"})).await.unwrap();
+
+        // Saving fails once the directory is gone
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(store.insert("recent", json!({"ts": {"$gt": 100}})).await.is_err());
+        assert!(store.insert("errors", json!({"level": "warn"})).await.is_err());
+        assert!(store.remove("errors").await.is_err());
+        assert_eq!(store.list(), vec![StoredQuery { name: "errors".to_string(), query: json!({"level": "error"}) }]);
+    }
+}
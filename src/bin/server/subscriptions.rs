@@ -0,0 +1,456 @@
+use crate::auth::Caller;
+use crate::{bad_request, ApiError, AppState, ErrorResponse};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::{Json, Response};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sift_rs::CompiledQuery;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Published documents buffered per connection before a slow client starts missing them,
+/// and so also the most documents a single publish may hold
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Most subscriptions a single connection may hold
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// Routes published documents to every open WebSocket connection
+pub struct Broker {
+    sender: broadcast::Sender<Arc<Value>>,
+    /// Cancelled when the server starts shutting down, closing every connection
+    shutdown: CancellationToken,
+}
+
+impl Default for Broker {
+    fn default() -> Self {
+        Broker::new(CancellationToken::new())
+    }
+}
+
+impl Broker {
+    /// A broker whose connections close once `shutdown` is cancelled
+    pub fn new(shutdown: CancellationToken) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Broker { sender, shutdown }
+    }
+
+    /// Publish documents to all connections, returning how many connections they were sent to
+    ///
+    /// A batch larger than [`CHANNEL_CAPACITY`] is rejected as a whole: no
+    /// connection could buffer it, so even an idle one would miss its start.
+    pub fn publish(&self, documents: Vec<Value>) -> Result<usize, String> {
+        if documents.len() > CHANNEL_CAPACITY {
+            return Err(format!(
+                "Batch of {} exceeds the publish limit of {}",
+                documents.len(),
+                CHANNEL_CAPACITY
+            ));
+        }
+        if documents.is_empty() {
+            return Ok(0);
+        }
+
+        let receivers = self.sender.receiver_count();
+        for document in documents {
+            // Sending only fails when nobody is connected
+            let _ = self.sender.send(Arc::new(document));
+        }
+        Ok(receivers)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Value>> {
+        self.sender.subscribe()
+    }
+
+    /// Wait until the server starts shutting down
+    async fn closed(&self) {
+        self.shutdown.cancelled().await
+    }
+}
+
+/// Messages sent by clients over the WebSocket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Start receiving published documents matching `query`, tagged with `id`
+    Subscribe { id: String, query: Value },
+    Unsubscribe { id: String },
+    /// Publish documents, exactly like `POST /publish`
+    Publish { documents: Vec<Value> },
+}
+
+/// Messages sent by the server over the WebSocket
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        id: String,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    Published {
+        count: usize,
+    },
+    /// A published document matching one or more of this connection's subscriptions
+    Match {
+        subscriptions: Vec<String>,
+        document: Value,
+    },
+    /// The connection fell more than [`CHANNEL_CAPACITY`] documents behind and missed this many of them
+    Lagged {
+        skipped: u64,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        error: String,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    fn error(id: Option<String>, error: &str, message: String) -> Self {
+        ServerMessage::Error {
+            id,
+            error: error.to_string(),
+            message,
+        }
+    }
+}
+
+/// The subscriptions held by one WebSocket connection
+///
+/// Cloning is cheap, so published documents can be matched on the blocking thread pool.
+#[derive(Clone)]
+pub struct Session {
+    state: AppState,
+    caller: Caller,
+    subscriptions: Arc<IndexMap<String, Arc<CompiledQuery>>>,
+}
+
+impl Session {
+    pub fn new(state: AppState, caller: Caller) -> Self {
+        Session {
+            state,
+            caller,
+            subscriptions: Arc::default(),
+        }
+    }
+
+    /// Handle a text frame from the client
+    pub fn handle_text(&mut self, text: &str) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return ServerMessage::error(None, "InvalidMessage", format!("Invalid message: {}", e)),
+        };
+
+        match message {
+            ClientMessage::Subscribe { id, query } => {
+                if !self.subscriptions.contains_key(&id) && self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return ServerMessage::error(
+                        Some(id),
+                        "TooManySubscriptions",
+                        format!("A connection may hold at most {} subscriptions", MAX_SUBSCRIPTIONS),
+                    );
+                }
+                if let Err(violation) = self.caller.check_query(&query) {
+                    return ServerMessage::error(Some(id), violation.error, violation.message);
+                }
+                match self.state.cache.get_or_compile(&query) {
+                    Ok(compiled) => {
+                        Arc::make_mut(&mut self.subscriptions).insert(id.clone(), compiled);
+                        ServerMessage::Subscribed { id }
+                    }
+                    Err(e) => ServerMessage::error(Some(id), e.kind(), format!("Failed to compile query: {}", e)),
+                }
+            }
+            ClientMessage::Unsubscribe { id } => match Arc::make_mut(&mut self.subscriptions).shift_remove(&id) {
+                Some(_) => ServerMessage::Unsubscribed { id },
+                None => ServerMessage::error(Some(id.clone()), "NotFound", format!("No subscription named '{}'", id)),
+            },
+            ClientMessage::Publish { documents } => {
+                if let Err(violation) = self.caller.check_batch(documents.len()) {
+                    return ServerMessage::error(None, violation.error, violation.message);
+                }
+                let count = documents.len();
+                match self.state.broker.publish(documents) {
+                    Ok(_) => ServerMessage::Published { count },
+                    Err(message) => ServerMessage::error(None, "BatchTooLarge", message),
+                }
+            }
+        }
+    }
+
+    /// The message to send for a published document, if any subscription matches it
+    ///
+    /// Documents that fail to evaluate against a subscription simply do not match it.
+    pub fn handle_published(&self, document: &Value) -> Option<ServerMessage> {
+        let subscriptions: Vec<String> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, compiled)| self.state.metrics.evaluate(compiled, document).unwrap_or(false))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        (!subscriptions.is_empty()).then(|| ServerMessage::Match {
+            subscriptions,
+            document: document.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PublishRequest {
+    /// The documents to route to matching subscriptions
+    documents: Vec<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PublishResponse {
+    /// Number of documents published
+    published: usize,
+    /// Number of connections the documents were delivered to
+    connections: usize,
+}
+
+/// Publish documents to live-query subscribers
+///
+/// POST /publish
+/// Body: { "documents": [...] }
+/// Response: { "published": n, "connections": n }
+#[utoipa::path(
+    post,
+    path = "/publish",
+    tag = "live queries",
+    request_body = PublishRequest,
+    responses(
+        (status = 200, body = PublishResponse),
+        (status = 400, description = "The batch exceeds the publish limit", body = ErrorResponse),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn publish(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<PublishRequest>,
+) -> Result<Json<PublishResponse>, ApiError> {
+    caller.check_batch(request.documents.len())?;
+    let published = request.documents.len();
+    let connections = state
+        .broker
+        .publish(request.documents)
+        .map_err(|message| bad_request("BatchTooLarge", &message))?;
+    info!("Published {} documents to {} connections", published, connections);
+    Ok(Json(PublishResponse { published, connections }))
+}
+
+/// Open a live-query WebSocket
+///
+/// GET /subscribe (WebSocket upgrade)
+///
+/// Clients send `subscribe`, `unsubscribe` and `publish` messages and receive
+/// a `match` message for each published document matching their subscriptions.
+#[utoipa::path(
+    get,
+    path = "/subscribe",
+    tag = "live queries",
+    responses((status = 101, description = "Switching to the live-query WebSocket protocol"))
+)]
+pub async fn subscribe(State(state): State<AppState>, caller: Caller, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| run_session(socket, state, caller))
+}
+
+async fn run_session(mut socket: WebSocket, state: AppState, caller: Caller) {
+    let mut published = state.broker.subscribe();
+    let broker = state.broker.clone();
+    info!("Live-query connection opened for {}", caller.name());
+    let mut session = Session::new(state, caller);
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => Some(session.handle_text(&text)),
+                Some(Ok(Message::Binary(_))) => Some(ServerMessage::error(
+                    None,
+                    "InvalidMessage",
+                    "Messages must be JSON text frames".to_string(),
+                )),
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => None,
+                Some(Err(e)) => {
+                    warn!("Live-query connection failed: {}", e);
+                    break;
+                }
+            },
+            document = published.recv() => match document {
+                Ok(document) => {
+                    // Subscriptions may run `$where` scripts, which must not block the async workers
+                    let session = session.clone();
+                    tokio::task::spawn_blocking(move || session.handle_published(&document))
+                        .await
+                        .unwrap_or(None)
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Live-query connection for {} missed {} published documents", session.caller.name(), skipped);
+                    Some(ServerMessage::Lagged { skipped })
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = broker.closed() => {
+                let close = CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "Server shutting down".into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                break;
+            }
+        };
+
+        if let Some(reply) = reply {
+            let text = serde_json::to_string(&reply).expect("server messages serialize");
+            if socket.send(Message::Text(text.into())).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    info!("Live-query connection closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use tokio_tungstenite::tungstenite;
+
+    #[test]
+    fn test_session_routes_matching_documents() {
+        let mut session = Session::new(AppState::default(), Caller::anonymous());
+        let reply = session.handle_text(r#"{"type": "subscribe", "id": "errors", "query": {"level": "error"}}"#);
+        assert_eq!(reply, ServerMessage::Subscribed { id: "errors".to_string() });
+        session.handle_text(r#"{"type": "subscribe", "id": "db", "query": {"service": "db"}}"#);
+
+        assert_eq!(session.handle_published(&json!({"level": "info", "service": "web"})), None);
+        assert_eq!(
+            session.handle_published(&json!({"level": "error", "service": "db"})),
+            Some(ServerMessage::Match {
+                subscriptions: vec!["errors".to_string(), "db".to_string()],
+                document: json!({"level": "error", "service": "db"}),
+            })
+        );
+
+        let reply = session.handle_text(r#"{"type": "unsubscribe", "id": "errors"}"#);
+        assert_eq!(reply, ServerMessage::Unsubscribed { id: "errors".to_string() });
+        assert_eq!(session.handle_published(&json!({"level": "error"})), None);
+    }
+
+    #[test]
+    fn test_session_reports_errors() {
+        let mut session = Session::new(AppState::default(), Caller::anonymous());
+        let ServerMessage::Error { id, error, .. } =
+            session.handle_text(r#"{"type": "subscribe", "id": "bad", "query": {"a": {"$bogus": 1}}}"#)
+        else {
+            panic!("expected an error");
+        };
+        assert_eq!((id.as_deref(), error.as_str()), (Some("bad"), "UnsupportedOperation"));
+
+        let ServerMessage::Error { error, .. } = session.handle_text("not json") else {
+            panic!("expected an error");
+        };
+        assert_eq!(error, "InvalidMessage");
+
+        let ServerMessage::Error { error, .. } = session.handle_text(r#"{"type": "unsubscribe", "id": "none"}"#) else {
+            panic!("expected an error");
+        };
+        assert_eq!(error, "NotFound");
+    }
+
+    #[tokio::test]
+    async fn test_publish_is_capped_at_the_channel_capacity() {
+        let state = AppState::default();
+        let mut receiver = state.broker.subscribe();
+
+        let documents: Vec<Value> = (0..CHANNEL_CAPACITY).map(|index| json!({"index": index})).collect();
+        assert_eq!(state.broker.publish(documents), Ok(1));
+        for index in 0..CHANNEL_CAPACITY {
+            assert_eq!(*receiver.try_recv().unwrap(), json!({"index": index}));
+        }
+
+        let documents: Vec<Value> = (0..=CHANNEL_CAPACITY).map(|index| json!({"index": index})).collect();
+        let request = PublishRequest { documents };
+        let Err((status, Json(body))) = publish(State(state.clone()), Caller::anonymous(), Json(request)).await else {
+            panic!("expected the batch to be rejected");
+        };
+        assert_eq!((status, body.error.as_str()), (axum::http::StatusCode::BAD_REQUEST, "BatchTooLarge"));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    type Client = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+    async fn next_message(socket: &mut Client) -> Value {
+        match socket.next().await.unwrap().unwrap() {
+            tungstenite::Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_websocket_receives_published_matches() {
+        let state = AppState::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = crate::routes(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/subscribe", address))
+            .await
+            .unwrap();
+        let subscribe = json!({"type": "subscribe", "id": "big", "query": {"amount": {"$gt": 100}}});
+        socket.send(tungstenite::Message::text(subscribe.to_string())).await.unwrap();
+        assert_eq!(next_message(&mut socket).await, json!({"type": "subscribed", "id": "big"}));
+
+        let request = json!({"documents": [{"amount": 5}, {"amount": 500}]});
+        let Json(response) = publish(State(state), Caller::anonymous(), Json(serde_json::from_value(request).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(response.connections, 1);
+        assert_eq!(
+            next_message(&mut socket).await,
+            json!({"type": "match", "subscriptions": ["big"], "document": {"amount": 500}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_websocket_closes_on_shutdown() {
+        let shutdown = CancellationToken::new();
+        let state = AppState {
+            broker: Arc::new(Broker::new(shutdown.clone())),
+            ..AppState::default()
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = crate::routes(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/subscribe", address))
+            .await
+            .unwrap();
+        let subscribe = json!({"type": "subscribe", "id": "all", "query": {}});
+        socket.send(tungstenite::Message::text(subscribe.to_string())).await.unwrap();
+        next_message(&mut socket).await;
+
+        shutdown.cancel();
+        match socket.next().await.unwrap().unwrap() {
+            tungstenite::Message::Close(Some(frame)) => assert_eq!(u16::from(frame.code), close_code::AWAY),
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::auth::Caller;
+use crate::{blocking, ApiError, AppState, ErrorResponse};
+use axum::extract::{Query as QueryParams, State};
+use axum::response::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sift_rs::{CompiledQuery, SiftResult};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ValidationItem {
+    /// The input object to validate
+    input: Value,
+    /// The MongoDB-style query to validate against
+    query: Value,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ValidateParams {
+    /// Compile each distinct query in the batch only once
+    #[serde(default)]
+    dedupe: bool,
+}
+
+/// Outcome for one item: either whether it matched or why it could not be validated
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(untagged)]
+pub enum ValidationResult {
+    Valid {
+        /// Whether the input matches the query
+        valid: bool,
+    },
+    Error {
+        error: ItemError,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ItemError {
+    /// The kind of error, e.g. `InvalidQuery` or `EvaluationError`
+    pub(crate) code: String,
+    pub(crate) message: String,
+    /// Location of the offending value in the request body, e.g. `$[2].query`
+    pub(crate) path: String,
+}
+
+impl ValidationResult {
+    fn from_result(index: usize, result: SiftResult<bool>) -> Self {
+        match result {
+            Ok(valid) => ValidationResult::Valid { valid },
+            Err(e) => {
+                warn!("Validation failed for item {}: {}", index, e);
+                ValidationResult::Error {
+                    error: ItemError {
+                        code: e.kind().to_string(),
+                        message: e.to_string(),
+                        path: format!("$[{}].query", index),
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Validates input/query pairs one at a time, for both the HTTP and gRPC transports
+pub struct Validator<'a> {
+    state: &'a AppState,
+    /// Compile results by query text, kept when deduplicating
+    compiled: Option<HashMap<String, SiftResult<Arc<CompiledQuery>>>>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(state: &'a AppState, dedupe: bool) -> Self {
+        Validator {
+            state,
+            compiled: dedupe.then(HashMap::new),
+        }
+    }
+
+    /// Validate the item at `index` of the batch
+    pub fn validate(&mut self, index: usize, input: &Value, query: &Value) -> ValidationResult {
+        let state = self.state;
+        let result = match &mut self.compiled {
+            Some(compiled) => {
+                let compiled = compiled
+                    .entry(query.to_string())
+                    .or_insert_with(|| state.cache.get_or_compile(query));
+                match compiled {
+                    Ok(compiled) => state.metrics.evaluate(compiled, input),
+                    Err(e) => Err(e.clone()),
+                }
+            }
+            None => state
+                .cache
+                .get_or_compile(query)
+                .and_then(|compiled| state.metrics.evaluate(&compiled, input)),
+        };
+        ValidationResult::from_result(index, result)
+    }
+}
+
+/// Validate endpoint - validates an array of input/query pairs
+///
+/// POST /validate[?dedupe=true]
+/// Body: [{ "input": {...}, "query": {...} }, ...]
+/// Response: [{ "valid": true/false } | { "error": { "code", "message", "path" } }, ...]
+///
+/// Items are validated independently, so one bad query does not fail the batch.
+/// Only a request breaking the caller's policy is rejected as a whole.
+/// Valid queries are compiled through the shared cache; with `dedupe`, an
+/// invalid query repeated in the batch is also only compiled once.
+#[utoipa::path(
+    post,
+    path = "/validate",
+    tag = "queries",
+    params(ValidateParams),
+    request_body = Vec<ValidationItem>,
+    responses(
+        (status = 200, description = "One result per item, in request order", body = Vec<ValidationResult>),
+        (status = 403, description = "The request breaks the caller's policy", body = ErrorResponse),
+    )
+)]
+pub async fn validate(
+    State(state): State<AppState>,
+    caller: Caller,
+    QueryParams(params): QueryParams<ValidateParams>,
+    Json(payload): Json<Vec<ValidationItem>>,
+) -> Result<Json<Vec<ValidationResult>>, ApiError> {
+    info!("Processing validation request with {} items", payload.len());
+    caller.check_batch(payload.len())?;
+    for item in &payload {
+        caller.check_query(&item.query)?;
+    }
+
+    let results = blocking(move || {
+        let mut validator = Validator::new(&state, params.dedupe);
+        Ok(payload
+            .iter()
+            .enumerate()
+            .map(|(index, item)| validator.validate(index, &item.input, &item.query))
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    let failed = results.iter().filter(|result| matches!(result, ValidationResult::Error { .. })).count();
+    info!("Validation completed: processed {} items, {} failed", results.len(), failed);
+    Ok(Json(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn run(dedupe: bool, body: Value) -> Vec<ValidationResult> {
+        let payload = serde_json::from_value(body).unwrap();
+        let Json(results) = validate(
+            State(AppState::default()),
+            Caller::anonymous(),
+            QueryParams(ValidateParams { dedupe }),
+            Json(payload),
+        )
+        .await
+        .unwrap();
+        results
+    }
+
+    #[tokio::test]
+    async fn test_bad_items_do_not_fail_the_batch() {
+        let body = json!([
+            {"input": {"age": 30}, "query": {"age": {"$gte": 25}}},
+            {"input": {"age": 30}, "query": {"age": {"$bogus": 1}}},
+            {"input": {"age": 20}, "query": {"age": {"$gte": 25}}}
+        ]);
+
+        for dedupe in [false, true] {
+            let results = run(dedupe, body.clone()).await;
+            assert_eq!(results[0], ValidationResult::Valid { valid: true });
+            assert_eq!(results[2], ValidationResult::Valid { valid: false });
+
+            let ValidationResult::Error { error } = &results[1] else {
+                panic!("expected an error for item 1");
+            };
+            assert_eq!(error.code, "UnsupportedOperation");
+            assert_eq!(error.path, "$[1].query");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_reports_errors_for_every_item() {
+        let body = json!([
+            {"input": {}, "query": {"a": {"$exists": "yes"}}},
+            {"input": {}, "query": {"a": {"$exists": "yes"}}}
+        ]);
+        let results = run(true, body).await;
+        assert!(results.iter().all(|result| matches!(result, ValidationResult::Error { .. })));
+    }
+
+    #[test]
+    fn test_result_serialization() {
+        assert_eq!(serde_json::to_value(ValidationResult::Valid { valid: true }).unwrap(), json!({"valid": true}));
+        let error = ValidationResult::from_result(3, Err(sift_rs::SiftError::InvalidQuery("bad".to_string())));
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            json!({"error": {"code": "InvalidQuery", "message": "Invalid query: bad", "path": "$[3].query"}})
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use serde_json::{json, Map, Value};
+use sift_rs::{estimate_cost, CompiledQuery, Query, SiftResult, COST_EQUALITY};
+use std::time::Instant;
+
+/// Per-clause statistics for `--explain`
+///
+/// Each root clause of the query is compiled on its own and evaluated in
+/// source order, the order the compiled query uses, so the report shows how
+/// many documents reached each clause and how many it let through.
+pub struct Explain {
+    query: Value,
+    clauses: Vec<Clause>,
+    started: Instant,
+}
+
+pub struct Clause {
+    pub clause: Value,
+    pub cost: u32,
+    compiled: CompiledQuery,
+    /// Documents that passed every earlier clause
+    pub examined: usize,
+    pub matched: usize,
+}
+
+/// Totals for the whole run, reported next to the clauses
+#[derive(Debug, Default)]
+pub struct Totals {
+    pub documents: usize,
+    pub matched: usize,
+    pub returned: usize,
+    pub invalid: usize,
+}
+
+impl Explain {
+    pub fn new(query: &Value) -> SiftResult<Self> {
+        let clauses = match query {
+            Value::Object(fields) if fields.len() > 1 => fields
+                .iter()
+                .map(|(key, value)| Map::from_iter([(key.clone(), value.clone())]).into())
+                .collect(),
+            _ => vec![query.clone()],
+        };
+        let clauses = clauses
+            .into_iter()
+            .map(|clause| {
+                Ok(Clause {
+                    cost: clause_cost(&clause),
+                    compiled: Query::from_value(&clause)?.compile()?,
+                    clause,
+                    examined: 0,
+                    matched: 0,
+                })
+            })
+            .collect::<SiftResult<_>>()?;
+
+        Ok(Explain {
+            query: query.clone(),
+            clauses,
+            started: Instant::now(),
+        })
+    }
+
+    /// Run `document` through the clauses until one rejects it
+    pub fn record(&mut self, document: &Value) {
+        for clause in &mut self.clauses {
+            clause.examined += 1;
+            if !clause.compiled.test(document).unwrap_or(false) {
+                return;
+            }
+            clause.matched += 1;
+        }
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    pub fn report(&self, totals: &Totals) -> Value {
+        let clauses: Vec<Value> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                json!({
+                    "clause": clause.clause,
+                    "cost": clause.cost,
+                    "examined": clause.examined,
+                    "matched": clause.matched,
+                })
+            })
+            .collect();
+
+        json!({
+            "query": self.query,
+            "clauses": clauses,
+            "documents": totals.documents,
+            "matched": totals.matched,
+            "returned": totals.returned,
+            "invalid": totals.invalid,
+            "elapsed_ms": self.started.elapsed().as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// Estimated cost of one root clause, the most expensive of its operators
+fn clause_cost(clause: &Value) -> u32 {
+    let Value::Object(fields) = clause else {
+        return COST_EQUALITY;
+    };
+    fields
+        .iter()
+        .map(|(key, value)| match value {
+            _ if key.starts_with('$') => estimate_cost(key, value),
+            Value::Object(operators) => operators
+                .iter()
+                .filter(|(name, _)| name.starts_with('$'))
+                .map(|(name, params)| estimate_cost(name, params))
+                .max()
+                .unwrap_or(COST_EQUALITY),
+            _ => COST_EQUALITY,
+        })
+        .max()
+        .unwrap_or(COST_EQUALITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_documents_reaching_each_clause() {
+        let mut explain = Explain::new(&json!({"level": "error", "message": {"$regex": "disk"}})).unwrap();
+        for document in [
+            json!({"level": "info", "message": "disk full"}),
+            json!({"level": "error", "message": "timeout"}),
+            json!({"level": "error", "message": "disk full"}),
+        ] {
+            explain.record(&document);
+        }
+
+        let report = explain.report(&Totals::default());
+        let clauses = report["clauses"].as_array().unwrap();
+        assert_eq!(clauses[0]["clause"], json!({"level": "error"}));
+        assert_eq!((clauses[0]["examined"].clone(), clauses[0]["matched"].clone()), (json!(3), json!(2)));
+        assert_eq!((clauses[1]["examined"].clone(), clauses[1]["matched"].clone()), (json!(2), json!(1)));
+        assert_eq!((clauses[0]["cost"].clone(), clauses[1]["cost"].clone()), (json!(1), json!(3)));
+    }
+}
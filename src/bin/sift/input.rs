@@ -0,0 +1,175 @@
+use serde::de::{Deserializer as _, SeqAccess, Visitor};
+use serde_json::Value;
+use std::fmt;
+use std::io::BufRead;
+use std::ops::ControlFlow;
+
+/// What to do after a document: keep reading, or stop with the overall outcome
+pub type Flow = ControlFlow<Result<(), String>>;
+
+/// Where a document was found in its input, for error messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Position {
+    /// 1-based line of an NDJSON input
+    Line(usize),
+    /// 0-based index in a JSON array input
+    Element(usize),
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Position::Line(line) => write!(f, "line {}", line),
+            Position::Element(index) => write!(f, "element {}", index),
+        }
+    }
+}
+
+/// Stream the documents of `reader` into `handle`, one at a time
+///
+/// Input starting with `[` is read as one JSON array, element by element;
+/// anything else as NDJSON, skipping blank lines. A line that is not valid
+/// JSON is passed to `handle` as an error so the caller can skip it. A
+/// malformed array cannot be resumed and stops reading.
+pub fn read_documents<R, F>(mut reader: R, source: &str, mut handle: F) -> Flow
+where
+    R: BufRead,
+    F: FnMut(Position, Result<Value, serde_json::Error>) -> Flow,
+{
+    match starts_with_array(&mut reader) {
+        Ok(true) => read_array(reader, source, &mut handle),
+        Ok(false) => read_lines(reader, source, &mut handle),
+        Err(e) => ControlFlow::Break(Err(format!("{}: {}", source, e))),
+    }
+}
+
+/// Skip leading whitespace and report whether the next byte opens an array
+fn starts_with_array(reader: &mut impl BufRead) -> std::io::Result<bool> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        let Some(&first) = buffer.first() else {
+            return Ok(false);
+        };
+        if !first.is_ascii_whitespace() {
+            return Ok(first == b'[');
+        }
+        reader.consume(1);
+    }
+}
+
+fn read_lines<F>(reader: impl BufRead, source: &str, handle: &mut F) -> Flow
+where
+    F: FnMut(Position, Result<Value, serde_json::Error>) -> Flow,
+{
+    for (index, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return ControlFlow::Break(Err(format!("{}: {}", source, e))),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        handle(Position::Line(index + 1), serde_json::from_str(&line))?;
+    }
+    ControlFlow::Continue(())
+}
+
+fn read_array<F>(reader: impl BufRead, source: &str, handle: &mut F) -> Flow
+where
+    F: FnMut(Position, Result<Value, serde_json::Error>) -> Flow,
+{
+    let mut stopped = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = (&mut deserializer)
+        .deserialize_seq(Elements {
+            handle,
+            stopped: &mut stopped,
+        })
+        .and_then(|()| deserializer.end());
+
+    // Stopping early leaves the rest of the array unread, which serde reports as an error
+    if let Some(outcome) = stopped {
+        return ControlFlow::Break(outcome);
+    }
+    match result {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(e) => ControlFlow::Break(Err(format!("{}: {}", source, e))),
+    }
+}
+
+/// Visits the elements of a top-level array without collecting them
+struct Elements<'a, F> {
+    handle: &'a mut F,
+    stopped: &'a mut Option<Result<(), String>>,
+}
+
+impl<'de, F> Visitor<'de> for Elements<'_, F>
+where
+    F: FnMut(Position, Result<Value, serde_json::Error>) -> Flow,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of documents")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        while let Some(document) = seq.next_element::<Value>()? {
+            if let ControlFlow::Break(outcome) = (self.handle)(Position::Element(index), Ok(document)) {
+                *self.stopped = Some(outcome);
+                return Ok(());
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    type Read = Vec<(Position, Result<Value, String>)>;
+
+    fn read(input: &str, stop_after: usize) -> (Read, Flow) {
+        let mut documents = Vec::new();
+        let flow = read_documents(input.as_bytes(), "input", |position, document| {
+            documents.push((position, document.map_err(|e| e.to_string())));
+            if documents.len() == stop_after {
+                ControlFlow::Break(Ok(()))
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        (documents, flow)
+    }
+
+    #[test]
+    fn test_reads_ndjson_lines() {
+        let (documents, flow) = read("{\"a\": 1}\n\n  {\"a\": 2}\nnot json\n", usize::MAX);
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(documents.len(), 3);
+        assert_eq!(documents[0], (Position::Line(1), Ok(json!({"a": 1}))));
+        assert_eq!(documents[1], (Position::Line(3), Ok(json!({"a": 2}))));
+        assert_eq!(documents[2].0, Position::Line(4));
+        assert!(documents[2].1.is_err());
+    }
+
+    #[test]
+    fn test_reads_array_elements() {
+        let (documents, flow) = read("\n [{\"a\": 1},\n {\"a\": 2}]\n", usize::MAX);
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(
+            documents,
+            vec![(Position::Element(0), Ok(json!({"a": 1}))), (Position::Element(1), Ok(json!({"a": 2})))]
+        );
+
+        let (documents, flow) = read("[1, 2, 3]", 2);
+        assert_eq!((documents.len(), flow), (2, ControlFlow::Break(Ok(()))));
+
+        let (_, flow) = read("[1, oops]", usize::MAX);
+        assert!(matches!(flow, ControlFlow::Break(Err(message)) if message.starts_with("input: ")));
+    }
+}
@@ -0,0 +1,346 @@
+use clap::{Parser, Subcommand};
+use explain::{Explain, Totals};
+use input::{read_documents, Flow, Position};
+use serde_json::Value;
+use sift_rs::{CompiledQuery, Projection, Query, Sort};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod explain;
+mod input;
+mod repl;
+
+/// Print the JSON documents matching a MongoDB-style query
+///
+/// Reads NDJSON or a JSON array from each file, or from standard input, and
+/// prints the matches as NDJSON. Exits with 0 when something matched, 1 when
+/// nothing did and 2 on errors.
+#[derive(Debug, Parser)]
+#[command(
+    name = "sift",
+    version,
+    about = "Filter JSON and NDJSON with MongoDB-style queries",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// The query, e.g. '{"level": "error"}'
+    #[arg(value_parser = parse_json, required = true)]
+    pub query: Option<Value>,
+    /// Files to read; standard input when none is given or for `-`
+    pub files: Vec<PathBuf>,
+    /// Projection applied to each printed document, e.g. '{"message": 1}'
+    #[arg(long, value_parser = parse_projection)]
+    pub project: Option<Projection>,
+    /// Sort order, e.g. '{"time": -1}'; holds every match in memory until the input ends
+    #[arg(long, value_parser = parse_sort)]
+    pub sort: Option<Sort>,
+    /// Print at most this many documents
+    #[arg(long)]
+    pub limit: Option<usize>,
+    /// Print how many documents would be printed instead of the documents
+    #[arg(long, short = 'c')]
+    pub count: bool,
+    /// Print per-clause match statistics instead of the documents
+    #[arg(long, conflicts_with = "count")]
+    pub explain: bool,
+    /// Select the documents that do not match
+    #[arg(long, short = 'v')]
+    pub invert: bool,
+    /// Skip documents that are not valid JSON or fail to evaluate instead of stopping
+    #[arg(long)]
+    pub skip_invalid: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Try queries interactively against JSON or NDJSON files
+    Repl {
+        /// Files to load
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Number of matching documents shown for each query
+        #[arg(long, default_value_t = 5)]
+        sample: usize,
+    },
+}
+
+fn parse_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))
+}
+
+fn parse_projection(text: &str) -> Result<Projection, String> {
+    Projection::from_value(&parse_json(text)?).map_err(|e| e.to_string())
+}
+
+fn parse_sort(text: &str) -> Result<Sort, String> {
+    Sort::from_value(&parse_json(text)?).map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Some(Command::Repl { files, sample }) => repl::run(files, *sample).map(|()| true),
+        None => run(&cli, &mut io::stdout().lock()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("sift: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Filter every input named on the command line; `Ok(true)` when any document was selected
+fn run(cli: &Cli, out: &mut impl Write) -> Result<bool, String> {
+    let mut filter = Filter::new(cli)?;
+    let stdin = [PathBuf::from("-")];
+    let files = if cli.files.is_empty() { &stdin[..] } else { &cli.files };
+
+    for path in files {
+        let flow = if path.as_os_str() == "-" {
+            filter.read(io::stdin().lock(), "<stdin>", out)
+        } else {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            filter.read(BufReader::new(file), &path.display().to_string(), out)
+        };
+        if let ControlFlow::Break(outcome) = flow {
+            outcome?;
+            break;
+        }
+    }
+    filter.finish(out)
+}
+
+/// The query, compiled once, and the state of one run over the inputs
+struct Filter<'a> {
+    cli: &'a Cli,
+    query: CompiledQuery,
+    explain: Option<Explain>,
+    /// Selected documents held back for `--sort`
+    sorted: Vec<Value>,
+    totals: Totals,
+    /// Number of documents selected, after `--invert`
+    selected: usize,
+}
+
+impl<'a> Filter<'a> {
+    fn new(cli: &'a Cli) -> Result<Self, String> {
+        let Some(query) = &cli.query else {
+            return Err("a query is required".to_string());
+        };
+        let explain = match cli.explain {
+            true => Some(Explain::new(query).map_err(|e| format!("invalid query: {}", e))?),
+            false => None,
+        };
+        let query = Query::from_value(query)
+            .and_then(|query| query.compile())
+            .map_err(|e| format!("invalid query: {}", e))?;
+        Ok(Filter {
+            cli,
+            query,
+            explain,
+            sorted: Vec::new(),
+            totals: Totals::default(),
+            selected: 0,
+        })
+    }
+
+    fn limit(&self) -> usize {
+        self.cli.limit.unwrap_or(usize::MAX)
+    }
+
+    fn read(&mut self, reader: impl BufRead, source: &str, out: &mut impl Write) -> Flow {
+        if self.selected >= self.limit() && self.explain.is_none() {
+            return ControlFlow::Break(Ok(()));
+        }
+        read_documents(reader, source, |position, document| {
+            let document = match document {
+                Ok(document) => document,
+                Err(e) => return self.invalid(source, position, e.to_string()),
+            };
+            self.process(document, out)
+                .unwrap_or_else(|e| self.invalid(source, position, e.to_string()))
+        })
+    }
+
+    /// Skip an unusable document, or stop when `--skip-invalid` is off
+    fn invalid(&mut self, source: &str, position: Position, message: String) -> Flow {
+        if self.cli.skip_invalid {
+            self.totals.invalid += 1;
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(Err(format!("{}, {}: {}", source, position, message)))
+        }
+    }
+
+    fn process(&mut self, document: Value, out: &mut impl Write) -> Result<Flow, sift_rs::SiftError> {
+        let matched = self.query.test(&document)?;
+        self.totals.documents += 1;
+        if let Some(explain) = &mut self.explain {
+            explain.record(&document);
+        }
+        if matched {
+            self.totals.matched += 1;
+        }
+        if matched == self.cli.invert {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        self.selected += 1;
+        // Explain reads all the input so its statistics are complete
+        if self.explain.is_some() {
+            return Ok(ControlFlow::Continue(()));
+        }
+        if self.cli.sort.is_some() {
+            self.sorted.push(document);
+            return Ok(ControlFlow::Continue(()));
+        }
+        if !self.cli.count {
+            if let ControlFlow::Break(outcome) = self.print(&document, out) {
+                return Ok(ControlFlow::Break(outcome));
+            }
+        }
+        match self.selected >= self.limit() {
+            true => Ok(ControlFlow::Break(Ok(()))),
+            false => Ok(ControlFlow::Continue(())),
+        }
+    }
+
+    fn print(&self, document: &Value, out: &mut impl Write) -> Flow {
+        let projected;
+        let document = match &self.cli.project {
+            Some(projection) => {
+                projected = projection.apply(document);
+                &projected
+            }
+            None => document,
+        };
+        let written = serde_json::to_writer(&mut *out, document)
+            .map_err(io::Error::from)
+            .and_then(|()| out.write_all(b"\n"));
+        match written {
+            Ok(()) => ControlFlow::Continue(()),
+            // The reader went away, e.g. `sift ... | head`
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ControlFlow::Break(Ok(())),
+            Err(e) => ControlFlow::Break(Err(format!("failed to write output: {}", e))),
+        }
+    }
+
+    /// Print whatever was held back until the end of the input
+    fn finish(mut self, out: &mut impl Write) -> Result<bool, String> {
+        let returned = self.selected.min(self.limit());
+        if let Some(explain) = &self.explain {
+            self.totals.returned = returned;
+            let report = serde_json::to_string_pretty(&explain.report(&self.totals)).unwrap_or_default();
+            writeln!(out, "{}", report).map_err(|e| format!("failed to write output: {}", e))?;
+        } else if self.cli.count {
+            writeln!(out, "{}", returned).map_err(|e| format!("failed to write output: {}", e))?;
+        } else if let Some(sort) = &self.cli.sort {
+            let mut sorted = std::mem::take(&mut self.sorted);
+            sort.sort(&mut sorted);
+            for document in sorted.iter().take(returned) {
+                if let ControlFlow::Break(outcome) = self.print(document, out) {
+                    outcome?;
+                    break;
+                }
+            }
+        }
+        Ok(self.selected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGS: &str = r#"{"level": "info", "time": 1, "message": "started"}
+{"level": "error", "time": 3, "message": "disk full"}
+{"level": "warn", "time": 2, "message": "slow"}
+{"level": "error", "time": 2, "message": "timeout"}
+"#;
+
+    /// Run `sift` with `args` over `inputs`, returning the exit status and output
+    fn sift(args: &[&str], inputs: &[&str]) -> (Result<bool, String>, String) {
+        let cli = Cli::parse_from(std::iter::once("sift").chain(args.iter().copied()));
+        let mut out = Vec::new();
+        let result = Filter::new(&cli).and_then(|mut filter| {
+            for input in inputs {
+                if let ControlFlow::Break(outcome) = filter.read(input.as_bytes(), "input", &mut out) {
+                    outcome?;
+                    break;
+                }
+            }
+            filter.finish(&mut out)
+        });
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_prints_matches_as_ndjson() {
+        let (result, out) = sift(&[r#"{"level": "error"}"#], &[LOGS]);
+        assert_eq!(result, Ok(true));
+        assert_eq!(
+            out,
+            "{\"level\":\"error\",\"time\":3,\"message\":\"disk full\"}\n{\"level\":\"error\",\"time\":2,\"message\":\"timeout\"}\n"
+        );
+
+        let (result, out) = sift(&[r#"{"level": "debug"}"#], &[LOGS]);
+        assert_eq!((result, out.as_str()), (Ok(false), ""));
+    }
+
+    #[test]
+    fn test_project_sort_limit_and_invert() {
+        let args = [r#"{"level": {"$ne": "info"}}"#, "--sort", r#"{"time": 1}"#, "--project", r#"{"message": 1}"#];
+        let (_, out) = sift(&args, &[LOGS]);
+        assert_eq!(out, "{\"message\":\"slow\"}\n{\"message\":\"timeout\"}\n{\"message\":\"disk full\"}\n");
+
+        let (_, out) = sift(&[&args[..], &["--limit", "1"]].concat(), &[LOGS]);
+        assert_eq!(out, "{\"message\":\"slow\"}\n");
+
+        let (_, out) = sift(&[r#"{"level": "error"}"#, "-v", "--project", r#"{"level": 1}"#], &[LOGS]);
+        assert_eq!(out, "{\"level\":\"info\"}\n{\"level\":\"warn\"}\n");
+    }
+
+    #[test]
+    fn test_count_across_inputs() {
+        let array = r#"[{"level": "error"}, {"level": "info"}]"#;
+        let (_, out) = sift(&[r#"{"level": "error"}"#, "--count"], &[LOGS, array]);
+        assert_eq!(out, "3\n");
+        let (_, out) = sift(&[r#"{"level": "error"}"#, "--count", "--limit", "1"], &[LOGS, array]);
+        assert_eq!(out, "1\n");
+    }
+
+    #[test]
+    fn test_explain_reports_clause_statistics() {
+        let (_, out) = sift(&[r#"{"level": "error", "time": {"$gt": 2}}"#, "--explain", "--limit", "5"], &[LOGS]);
+        let report: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!((report["documents"].as_u64(), report["matched"].as_u64()), (Some(4), Some(1)));
+        assert_eq!(report["clauses"][0]["matched"], 2);
+        assert_eq!(report["clauses"][1]["examined"], 2);
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        let input = "{\"level\": \"error\"}\nnot json\n{\"level\": \"error\"}\n";
+        let (result, out) = sift(&[r#"{"level": "error"}"#], &[input]);
+        assert_eq!(result.unwrap_err().split(':').next(), Some("input, line 2"));
+        assert_eq!(out, "{\"level\":\"error\"}\n");
+
+        let (result, out) = sift(&[r#"{"level": "error"}"#, "--skip-invalid", "--count"], &[input]);
+        assert_eq!((result, out.as_str()), (Ok(true), "2\n"));
+
+        assert!(Cli::try_parse_from(["sift", "{not json"]).is_err());
+        assert!(Cli::try_parse_from(["sift", "{}", "--sort", r#"{"a": 2}"#]).is_err());
+        assert!(Cli::try_parse_from(["sift"]).is_err());
+        let (result, _) = sift(&[r#"{"a": {"$bogus": 1}}"#], &[]);
+        assert!(result.unwrap_err().starts_with("invalid query"));
+    }
+}
@@ -0,0 +1,367 @@
+use crate::explain::Explain;
+use crate::input::read_documents;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::Value;
+use sift_rs::{CompiledQuery, OperatorRegistry, Query};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "\
+Type a query such as {\"level\": \"error\"} to see how many documents match,
+a sample of them and how many documents each clause let through. While you
+type, the count is shown after the cursor. Tab completes field paths and
+operator names.
+
+  :fields      list the field paths found in the data
+  :sample <n>  show up to n matching documents per query
+  :help        show this help
+  :quit        leave (or Ctrl-D)";
+
+/// The loaded documents and what completion offers for them
+pub struct Session {
+    documents: Vec<Value>,
+    /// Dotted paths of every field in the documents, sorted
+    fields: Vec<String>,
+    operators: Vec<String>,
+    sample: usize,
+}
+
+impl Session {
+    /// Load every document of `files`, skipping the lines that are not valid JSON
+    pub fn load(files: &[PathBuf], sample: usize) -> Result<(Self, usize), String> {
+        let mut documents = Vec::new();
+        let mut skipped = 0;
+        for path in files {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let flow = read_documents(BufReader::new(file), &path.display().to_string(), |_, document| {
+                match document {
+                    Ok(document) => documents.push(document),
+                    Err(_) => skipped += 1,
+                }
+                ControlFlow::Continue(())
+            });
+            if let ControlFlow::Break(outcome) = flow {
+                outcome?;
+            }
+        }
+        Ok((Session::new(documents, sample), skipped))
+    }
+
+    pub fn new(documents: Vec<Value>, sample: usize) -> Self {
+        let mut fields = BTreeSet::new();
+        for document in &documents {
+            collect_fields(document, "", &mut fields);
+        }
+        let operators = OperatorRegistry::new()
+            .operator_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        Session {
+            documents,
+            fields: fields.into_iter().collect(),
+            operators,
+            sample,
+        }
+    }
+
+    /// Field paths or operator names completing the word before `pos`, and where that word starts
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<&str>) {
+        let before = &line[..pos];
+        let start = before
+            .rfind(|c: char| matches!(c, '"' | '\'' | '{' | '[' | ',' | ':') || c.is_whitespace())
+            .map_or(0, |index| index + 1);
+        let word = &before[start..];
+        let names = if word.starts_with('$') { &self.operators } else { &self.fields };
+        let candidates = names
+            .iter()
+            .filter(|name| name.starts_with(word))
+            .map(String::as_str)
+            .collect();
+        (start, candidates)
+    }
+
+    fn compile(&self, line: &str) -> Result<(Value, CompiledQuery), String> {
+        let query: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+        let compiled = Query::from_value(&query)
+            .and_then(|parsed| parsed.compile())
+            .map_err(|e| e.to_string())?;
+        Ok((query, compiled))
+    }
+
+    fn count(&self, compiled: &CompiledQuery) -> usize {
+        self.documents
+            .iter()
+            .filter(|document| compiled.test(document).unwrap_or(false))
+            .count()
+    }
+
+    /// Run a `:command` or a query, writing what it shows
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> io::Result<()> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [":help"] => writeln!(out, "{}", HELP),
+            [":fields"] => self.fields.iter().try_for_each(|field| writeln!(out, "{}", field)),
+            [":sample", n] => match n.parse() {
+                Ok(n) => {
+                    self.sample = n;
+                    Ok(())
+                }
+                Err(_) => writeln!(out, "error: ':sample' needs a number"),
+            },
+            [command, ..] if command.starts_with(':') => {
+                writeln!(out, "error: unknown command '{}', try :help", command)
+            }
+            _ => self.query(line, out),
+        }
+    }
+
+    fn query(&self, line: &str, out: &mut impl Write) -> io::Result<()> {
+        let (query, compiled) = match self.compile(line) {
+            Ok(compiled) => compiled,
+            Err(message) => return writeln!(out, "error: {}", message),
+        };
+        let mut explain = match Explain::new(&query) {
+            Ok(explain) => explain,
+            Err(e) => return writeln!(out, "error: {}", e),
+        };
+
+        let mut matched = Vec::new();
+        let mut failed = 0;
+        for document in &self.documents {
+            match compiled.test(document) {
+                Ok(true) => matched.push(document),
+                Ok(false) => {}
+                Err(_) => failed += 1,
+            }
+            explain.record(document);
+        }
+
+        write!(out, "{} of {} documents match", matched.len(), self.documents.len())?;
+        if failed > 0 {
+            write!(out, ", {} failed to evaluate", failed)?;
+        }
+        writeln!(out)?;
+        for document in matched.iter().take(self.sample) {
+            writeln!(out, "  {}", document)?;
+        }
+        if matched.len() > self.sample {
+            writeln!(out, "  ... {} more", matched.len() - self.sample)?;
+        }
+
+        if explain.clauses().len() > 1 {
+            writeln!(out, "clauses, in evaluation order:")?;
+            for clause in explain.clauses() {
+                writeln!(
+                    out,
+                    "  {}  cost {}, {} examined, {} passed",
+                    clause.clause, clause.cost, clause.examined, clause.matched
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Add the dotted path of every field under `value`, looking inside arrays as queries do
+fn collect_fields(value: &Value, prefix: &str, fields: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object {
+                let path = match prefix {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", prefix, key),
+                };
+                collect_fields(child, &path, fields);
+                fields.insert(path);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_fields(item, prefix, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Completer for Session {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, candidates) = self.candidates(line, pos);
+        let candidates = candidates
+            .into_iter()
+            .map(|name| Pair {
+                display: name.to_string(),
+                replacement: name.to_string(),
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Session {
+    type Hint = String;
+
+    /// The live match count for the query typed so far, once it is valid JSON
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() || line.trim_start().starts_with(':') {
+            return None;
+        }
+        match self.compile(line) {
+            Ok((_, compiled)) => Some(format!("  → {} of {}", self.count(&compiled), self.documents.len())),
+            Err(message) if !message.starts_with("invalid JSON") => Some(format!("  ✗ {}", message)),
+            Err(_) => None,
+        }
+    }
+}
+
+impl Highlighter for Session {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for Session {}
+
+impl Helper for Session {}
+
+/// File the REPL history is kept in, when there is a home directory
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".sift_history"))
+}
+
+/// Load `files` and read queries until `:quit` or end of input
+pub fn run(files: &[PathBuf], sample: usize) -> Result<(), String> {
+    let (session, skipped) = Session::load(files, sample)?;
+    print!("Loaded {} documents", session.documents.len());
+    if skipped > 0 {
+        print!(" (skipped {} invalid lines)", skipped);
+    }
+    println!(". Type a query, :help or :quit.");
+
+    let mut editor: Editor<Session, DefaultHistory> = Editor::new().map_err(|e| e.to_string())?;
+    editor.set_helper(Some(session));
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history yet on first use
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        let line = match editor.readline("sift> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, ":quit" | ":q") {
+            break;
+        }
+        if let Some(session) = editor.helper_mut() {
+            session
+                .execute(line, &mut io::stdout().lock())
+                .map_err(|e| format!("failed to write output: {}", e))?;
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("sift: could not save history to {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn session() -> Session {
+        Session::new(
+            vec![
+                json!({"level": "error", "user": {"name": "ana", "roles": [{"id": 1}]}}),
+                json!({"level": "info", "user": {"name": "bo"}}),
+                json!({"level": "error", "code": 500}),
+            ],
+            1,
+        )
+    }
+
+    fn execute(session: &mut Session, line: &str) -> String {
+        let mut out = Vec::new();
+        session.execute(line, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_infers_field_paths() {
+        assert_eq!(
+            session().fields,
+            vec!["code", "level", "user", "user.name", "user.roles", "user.roles.id"]
+        );
+    }
+
+    #[test]
+    fn test_completes_fields_and_operators() {
+        let session = session();
+        assert_eq!(session.candidates(r#"{"user.r"#, 8), (2, vec!["user.roles", "user.roles.id"]));
+        assert_eq!(session.candidates(r#"{"code": {"$g"#, 13), (11, vec!["$gt", "$gte"]));
+        assert_eq!(session.candidates("", 0), (0, session.fields.iter().map(String::as_str).collect()));
+    }
+
+    #[test]
+    fn test_hints_the_live_count() {
+        let session = session();
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let hint = |line: &str| session.hint(line, line.len(), &ctx);
+
+        assert_eq!(hint(r#"{"level": "error"}"#).as_deref(), Some("  → 2 of 3"));
+        assert_eq!(hint(r#"{"level": "err"#), None);
+        assert!(hint(r#"{"level": {"$bogus": 1}}"#).unwrap().starts_with("  ✗"));
+        assert_eq!(hint(":fields"), None);
+    }
+
+    #[test]
+    fn test_shows_count_sample_and_clauses() {
+        let mut session = session();
+        let out = execute(&mut session, r#"{"level": "error", "code": {"$gte": 500}}"#);
+        assert_eq!(
+            out,
+            concat!(
+                "1 of 3 documents match\n",
+                "  {\"level\":\"error\",\"code\":500}\n",
+                "clauses, in evaluation order:\n",
+                "  {\"level\":\"error\"}  cost 1, 3 examined, 2 passed\n",
+                "  {\"code\":{\"$gte\":500}}  cost 2, 2 examined, 1 passed\n",
+            )
+        );
+
+        let out = execute(&mut session, r#"{"level": "error"}"#);
+        assert!(out.ends_with("  ... 1 more\n"), "{}", out);
+        execute(&mut session, ":sample 0");
+        assert_eq!(execute(&mut session, r#"{"level": "error"}"#), "2 of 3 documents match\n  ... 2 more\n");
+
+        assert!(execute(&mut session, "{oops").starts_with("error: invalid JSON"));
+        assert!(execute(&mut session, ":nope").starts_with("error: unknown command"));
+        assert_eq!(execute(&mut session, ":fields").lines().count(), 6);
+    }
+}
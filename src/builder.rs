@@ -0,0 +1,415 @@
+use crate::query::Query;
+use crate::SiftResult;
+use serde_json::{Map, Value};
+
+/// Fluent builder for MongoDB-style queries
+///
+/// Field conditions are added with [`QueryBuilder::field`], which returns a
+/// [`FieldBuilder`] exposing one method per operator. Calling `field` again with
+/// the same path adds further operators to that field.
+///
+/// # Examples
+///
+/// ```rust
+/// use sift_rs::{Condition, QueryBuilder};
+/// use serde_json::json;
+///
+/// let query = QueryBuilder::new()
+///     .field("age").gte(30)
+///     .field("status").is_in(["active", "pending"])
+///     .field("email").exists(true)
+///     .field("score").not(Condition::new().lt(50))
+///     .or([
+///         QueryBuilder::new().field("role").eq("admin"),
+///         QueryBuilder::new().field("tags").elem_match(QueryBuilder::new().field("name").eq("vip")),
+///     ]);
+///
+/// assert_eq!(query.to_value(), json!({
+///     "age": {"$gte": 30},
+///     "status": {"$in": ["active", "pending"]},
+///     "email": {"$exists": true},
+///     "score": {"$not": {"$lt": 50}},
+///     "$or": [
+///         {"role": "admin"},
+///         {"tags": {"$elemMatch": {"name": "vip"}}}
+///     ]
+/// }));
+///
+/// let compiled = query.build().unwrap();
+/// assert!(compiled.test(&json!({
+///     "age": 31, "status": "active", "email": "a@b.c", "score": 70, "role": "admin"
+/// })).unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    fields: Vec<(String, Condition)>,
+    and: Vec<Value>,
+    or: Vec<Vec<Value>>,
+    nor: Vec<Value>,
+    where_expression: Option<String>,
+}
+
+impl QueryBuilder {
+    /// Create a new empty query builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a condition on a (possibly dotted) field path
+    pub fn field(self, path: impl Into<String>) -> FieldBuilder {
+        FieldBuilder {
+            builder: self,
+            path: path.into(),
+        }
+    }
+
+    /// Require all of the given sub-queries to match (`$and`)
+    ///
+    /// Repeated calls extend the same `$and` list.
+    pub fn and(mut self, queries: impl IntoIterator<Item = QueryBuilder>) -> Self {
+        self.and.extend(queries.into_iter().map(|query| query.to_value()));
+        self
+    }
+
+    /// Require at least one of the given sub-queries to match (`$or`)
+    ///
+    /// Repeated calls are AND-ed together rather than merged into one list.
+    pub fn or(mut self, queries: impl IntoIterator<Item = QueryBuilder>) -> Self {
+        self.or
+            .push(queries.into_iter().map(|query| query.to_value()).collect());
+        self
+    }
+
+    /// Require none of the given sub-queries to match (`$nor`)
+    pub fn nor(mut self, queries: impl IntoIterator<Item = QueryBuilder>) -> Self {
+        self.nor.extend(queries.into_iter().map(|query| query.to_value()));
+        self
+    }
+
+    /// Add a JavaScript `$where` expression evaluated against the document
+    pub fn where_expression(mut self, expression: impl Into<String>) -> Self {
+        self.where_expression = Some(expression.into());
+        self
+    }
+
+    /// Apply a prebuilt [`Condition`] to a field, merging with existing operators
+    fn with_condition(mut self, path: String, condition: Condition) -> Self {
+        match self.fields.iter_mut().find(|(existing, _)| *existing == path) {
+            Some((_, existing)) => existing.operators.extend(condition.operators),
+            None => self.fields.push((path, condition)),
+        }
+        self
+    }
+
+    /// Render the query as canonical MongoDB-style JSON
+    ///
+    /// A field whose only operator is `$eq` on a non-object value is written as a
+    /// plain value. When both `$and` and `$or` clauses are present, every `$or`
+    /// group is nested inside `$and` so that all clauses keep AND semantics.
+    pub fn to_value(&self) -> Value {
+        let mut obj = Map::new();
+
+        for (path, condition) in &self.fields {
+            obj.insert(path.clone(), condition.to_field_value());
+        }
+
+        if self.and.is_empty() && self.or.len() == 1 {
+            obj.insert("$or".to_string(), Value::Array(self.or[0].clone()));
+        } else {
+            let mut and = self.and.clone();
+            for group in &self.or {
+                let mut or_obj = Map::new();
+                or_obj.insert("$or".to_string(), Value::Array(group.clone()));
+                and.push(Value::Object(or_obj));
+            }
+            if !and.is_empty() {
+                obj.insert("$and".to_string(), Value::Array(and));
+            }
+        }
+
+        if !self.nor.is_empty() {
+            obj.insert("$nor".to_string(), Value::Array(self.nor.clone()));
+        }
+
+        if let Some(expression) = &self.where_expression {
+            obj.insert("$where".to_string(), Value::String(expression.clone()));
+        }
+
+        Value::Object(obj)
+    }
+
+    /// Parse the built JSON into a [`Query`]
+    pub fn build(&self) -> SiftResult<Query> {
+        Query::from_value(&self.to_value())
+    }
+}
+
+impl From<QueryBuilder> for Value {
+    fn from(builder: QueryBuilder) -> Self {
+        builder.to_value()
+    }
+}
+
+/// Builder for the operators applied to a single field
+///
+/// Every operator method returns the parent [`QueryBuilder`] so further fields
+/// and clauses can be chained.
+#[derive(Debug, Clone)]
+pub struct FieldBuilder {
+    builder: QueryBuilder,
+    path: String,
+}
+
+impl FieldBuilder {
+    /// Apply a prebuilt [`Condition`] to this field
+    pub fn matches(self, condition: Condition) -> QueryBuilder {
+        self.builder.with_condition(self.path, condition)
+    }
+
+    pub fn eq(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().eq(value))
+    }
+
+    pub fn ne(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().ne(value))
+    }
+
+    pub fn gt(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().gt(value))
+    }
+
+    pub fn gte(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().gte(value))
+    }
+
+    pub fn lt(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().lt(value))
+    }
+
+    pub fn lte(self, value: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().lte(value))
+    }
+
+    pub fn is_in<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> QueryBuilder {
+        self.matches(Condition::new().is_in(values))
+    }
+
+    pub fn nin<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> QueryBuilder {
+        self.matches(Condition::new().nin(values))
+    }
+
+    pub fn all<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> QueryBuilder {
+        self.matches(Condition::new().all(values))
+    }
+
+    pub fn exists(self, should_exist: bool) -> QueryBuilder {
+        self.matches(Condition::new().exists(should_exist))
+    }
+
+    pub fn regex(self, pattern: impl Into<String>) -> QueryBuilder {
+        self.matches(Condition::new().regex(pattern))
+    }
+
+    pub fn size(self, size: u64) -> QueryBuilder {
+        self.matches(Condition::new().size(size))
+    }
+
+    pub fn modulo(self, divisor: impl Into<Value>, remainder: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().modulo(divisor, remainder))
+    }
+
+    pub fn type_of(self, type_name: impl Into<Value>) -> QueryBuilder {
+        self.matches(Condition::new().type_of(type_name))
+    }
+
+    pub fn elem_match(self, query: QueryBuilder) -> QueryBuilder {
+        self.matches(Condition::new().elem_match(query))
+    }
+
+    pub fn not(self, condition: Condition) -> QueryBuilder {
+        self.matches(Condition::new().not(condition))
+    }
+}
+
+/// A set of operators applied to one value, e.g. `{"$gte": 18, "$lt": 65}`
+///
+/// Used directly for `$not` and with [`FieldBuilder::matches`] to attach
+/// several operators to a field at once.
+#[derive(Debug, Clone, Default)]
+pub struct Condition {
+    operators: Vec<(&'static str, Value)>,
+}
+
+impl Condition {
+    /// Create a condition with no operators
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with(mut self, operator: &'static str, value: Value) -> Self {
+        match self.operators.iter_mut().find(|(name, _)| *name == operator) {
+            Some((_, existing)) => *existing = value,
+            None => self.operators.push((operator, value)),
+        }
+        self
+    }
+
+    pub fn eq(self, value: impl Into<Value>) -> Self {
+        self.with("$eq", value.into())
+    }
+
+    pub fn ne(self, value: impl Into<Value>) -> Self {
+        self.with("$ne", value.into())
+    }
+
+    pub fn gt(self, value: impl Into<Value>) -> Self {
+        self.with("$gt", value.into())
+    }
+
+    pub fn gte(self, value: impl Into<Value>) -> Self {
+        self.with("$gte", value.into())
+    }
+
+    pub fn lt(self, value: impl Into<Value>) -> Self {
+        self.with("$lt", value.into())
+    }
+
+    pub fn lte(self, value: impl Into<Value>) -> Self {
+        self.with("$lte", value.into())
+    }
+
+    pub fn is_in<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Self {
+        self.with("$in", values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn nin<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Self {
+        self.with("$nin", values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn all<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Self {
+        self.with("$all", values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn exists(self, should_exist: bool) -> Self {
+        self.with("$exists", Value::Bool(should_exist))
+    }
+
+    pub fn regex(self, pattern: impl Into<String>) -> Self {
+        self.with("$regex", Value::String(pattern.into()))
+    }
+
+    pub fn size(self, size: u64) -> Self {
+        self.with("$size", Value::from(size))
+    }
+
+    pub fn modulo(self, divisor: impl Into<Value>, remainder: impl Into<Value>) -> Self {
+        self.with("$mod", Value::Array(vec![divisor.into(), remainder.into()]))
+    }
+
+    /// `$type` accepts either a type name or a numeric BSON type
+    pub fn type_of(self, type_name: impl Into<Value>) -> Self {
+        self.with("$type", type_name.into())
+    }
+
+    pub fn elem_match(self, query: QueryBuilder) -> Self {
+        self.with("$elemMatch", query.to_value())
+    }
+
+    pub fn not(self, condition: Condition) -> Self {
+        self.with("$not", condition.to_value())
+    }
+
+    /// Render the operators as a JSON object
+    pub fn to_value(&self) -> Value {
+        let obj: Map<String, Value> = self
+            .operators
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        Value::Object(obj)
+    }
+
+    /// Render the condition as the value of a field, using the implicit `$eq`
+    /// form when possible
+    fn to_field_value(&self) -> Value {
+        match self.operators.as_slice() {
+            [("$eq", value)] if !value.is_object() => value.clone(),
+            _ => self.to_value(),
+        }
+    }
+}
+
+impl From<Condition> for Value {
+    fn from(condition: Condition) -> Self {
+        condition.to_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_field_operators() {
+        let query = QueryBuilder::new()
+            .field("name").eq("Alice")
+            .field("age").gte(18)
+            .field("age").lt(65)
+            .field("tags").all(["a", "b"])
+            .field("count").modulo(2, 0);
+
+        assert_eq!(
+            query.to_value(),
+            json!({
+                "name": "Alice",
+                "age": {"$gte": 18, "$lt": 65},
+                "tags": {"$all": ["a", "b"]},
+                "count": {"$mod": [2, 0]}
+            })
+        );
+    }
+
+    #[test]
+    fn test_eq_object_keeps_operator() {
+        let query = QueryBuilder::new().field("meta").eq(json!({"a": 1}));
+        assert_eq!(query.to_value(), json!({"meta": {"$eq": {"a": 1}}}));
+    }
+
+    #[test]
+    fn test_and_with_or_keeps_and_semantics() {
+        let query = QueryBuilder::new()
+            .and([QueryBuilder::new().field("a").eq(1)])
+            .or([
+                QueryBuilder::new().field("b").eq(1),
+                QueryBuilder::new().field("c").eq(1),
+            ]);
+
+        assert_eq!(
+            query.to_value(),
+            json!({"$and": [{"a": 1}, {"$or": [{"b": 1}, {"c": 1}]}]})
+        );
+
+        let built = query.build().unwrap();
+        assert!(built.test(&json!({"a": 1, "c": 1})).unwrap());
+        assert!(!built.test(&json!({"a": 2, "c": 1})).unwrap());
+    }
+
+    #[test]
+    fn test_built_query_matches() {
+        let query = QueryBuilder::new()
+            .field("status").nin(["banned"])
+            .field("score").not(Condition::new().lt(10))
+            .field("items").elem_match(QueryBuilder::new().field("qty").gt(5))
+            .field("name").regex("^A")
+            .build()
+            .unwrap();
+
+        assert!(query
+            .test(&json!({"status": "active", "score": 12, "name": "Ann", "items": [{"qty": 7}]}))
+            .unwrap());
+        assert!(!query
+            .test(&json!({"status": "active", "score": 5, "name": "Ann", "items": [{"qty": 7}]}))
+            .unwrap());
+    }
+}
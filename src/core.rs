@@ -1,9 +1,13 @@
 use crate::{SiftError, SiftResult};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Represents a query operation that can test values
-pub trait Operation {
+///
+/// Operations must be `Send + Sync` so a compiled query can be shared across threads.
+pub trait Operation: Send + Sync {
     fn test(&self, value: &Value, key: Option<&str>, parent: Option<&Value>) -> SiftResult<bool>;
     fn reset(&mut self) {}
 }
@@ -15,12 +19,33 @@ pub trait QueryOperator: Send + Sync {
         params: &Value,
         parent_query: &Value,
     ) -> SiftResult<Box<dyn Operation>>;
+
+    /// Create the operation with access to the context the query is compiled in
+    ///
+    /// Operators that compile sub-queries or depend on [`QueryOptions`] override
+    /// this so nested queries share the caller's registry and options.
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        parent_query: &Value,
+        _context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation(params, parent_query)
+    }
+
     fn name(&self) -> &'static str;
 }
 
-/// Registry for query operators
+/// A native Rust predicate that queries can call by name
+///
+/// It receives the value being tested and the resolved arguments of a
+/// `$function` call (empty for `{"$where": {"$fn": name}}`).
+pub type NativeFunction = Arc<dyn Fn(&Value, &[Value]) -> bool + Send + Sync>;
+
+/// Registry for query operators and native functions
 pub struct OperatorRegistry {
     operators: HashMap<String, Box<dyn QueryOperator>>,
+    functions: HashMap<String, NativeFunction>,
 }
 
 impl Default for OperatorRegistry {
@@ -33,6 +58,7 @@ impl OperatorRegistry {
     pub fn new() -> Self {
         let mut registry = OperatorRegistry {
             operators: HashMap::new(),
+            functions: HashMap::new(),
         };
 
         // Register default operators
@@ -44,13 +70,44 @@ impl OperatorRegistry {
         self.operators.insert(name, operator);
     }
 
+    // Returns the box itself to keep the signature downstream code already relies on
+    #[allow(clippy::borrowed_box)]
     pub fn get(&self, name: &str) -> Option<&Box<dyn QueryOperator>> {
         self.operators.get(name)
     }
 
+    /// Names of the registered operators, such as `$eq`, sorted
+    pub fn operator_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.operators.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Register a native predicate callable via `{"$where": {"$fn": name}}` or `$function`
+    pub fn register_function<F>(&mut self, name: impl Into<String>, function: F)
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        self.functions
+            .insert(name.into(), Arc::new(move |value: &Value, _args: &[Value]| function(value)));
+    }
+
+    /// Register a native predicate that also receives the arguments of a `$function` call
+    pub fn register_function_with_args<F>(&mut self, name: impl Into<String>, function: F)
+    where
+        F: Fn(&Value, &[Value]) -> bool + Send + Sync + 'static,
+    {
+        self.functions.insert(name.into(), Arc::new(function));
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<&NativeFunction> {
+        self.functions.get(name)
+    }
+
     fn register_default_operators(&mut self) {
         use crate::operation_modules::elem_match_operation::ElemMatchOperator;
         use crate::operation_modules::exists_operation::ExistsOperator;
+        use crate::operation_modules::function_operation::FunctionOperator;
         use crate::operation_modules::logic_operations::{
             AndOperator, NorOperator, NotOperator, OrOperator,
         };
@@ -58,7 +115,7 @@ impl OperatorRegistry {
         use crate::operation_modules::regex_operation::RegexOperator;
         use crate::operation_modules::size_operation::SizeOperator;
         use crate::operation_modules::type_operation::TypeOperator;
-        use crate::operation_modules::where_operation::WhereOperator;
+        use crate::operation_modules::where_operation::{default_script_engine, WhereOperator};
         use crate::operations::*;
 
         self.register("$eq".to_string(), Box::new(EqOperator));
@@ -80,7 +137,12 @@ impl OperatorRegistry {
         self.register("$type".to_string(), Box::new(TypeOperator));
         self.register("$elemMatch".to_string(), Box::new(ElemMatchOperator));
         self.register("$nor".to_string(), Box::new(NorOperator));
-        self.register("$where".to_string(), Box::new(WhereOperator));
+        let where_operator = match default_script_engine() {
+            Some(engine) => WhereOperator::with_shared_engine(engine),
+            None => WhereOperator::native_only(),
+        };
+        self.register("$where".to_string(), Box::new(where_operator));
+        self.register("$function".to_string(), Box::new(FunctionOperator));
     }
 }
 
@@ -89,6 +151,15 @@ impl OperatorRegistry {
 pub struct QueryOptions {
     pub case_sensitive: bool,
     pub strict_arrays: bool,
+    /// Evaluate root-level AND-ed operations cheapest first instead of in source order
+    pub reorder_by_cost: bool,
+    /// Accept `$where` JavaScript; disable when compiling queries from untrusted input
+    ///
+    /// Native functions called through `{"$where": {"$fn": name}}` and `$function`
+    /// are not affected.
+    pub allow_where: bool,
+    /// Resource limits applied to every `$where` evaluation
+    pub where_limits: WhereLimits,
 }
 
 impl Default for QueryOptions {
@@ -96,6 +167,51 @@ impl Default for QueryOptions {
         QueryOptions {
             case_sensitive: true,
             strict_arrays: false,
+            reorder_by_cost: false,
+            allow_where: true,
+            where_limits: WhereLimits::default(),
+        }
+    }
+}
+
+/// Resource limits for evaluating `$where` JavaScript
+///
+/// Exceeding any limit fails the evaluation with
+/// [`SiftError::ResourceLimitExceeded`]. Scripts only see the ECMAScript
+/// built-ins; no host functions (console, timers, I/O, module loading) are
+/// exposed.
+///
+/// Memory is not limited. A single call into a built-in, such as
+/// `"x".repeat(2e8)` or sorting a large array, cannot be interrupted: the
+/// caller gets its timeout error, but the call keeps running, and holding
+/// its memory, on a background thread until it returns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhereLimits {
+    /// Maximum loop iterations within a single function call
+    pub loop_iteration_limit: u64,
+    /// Maximum number of interpreter instruction units per evaluation
+    pub instruction_budget: Option<u64>,
+    /// Maximum wall-clock time per evaluation
+    pub timeout: Option<Duration>,
+    /// Maximum depth of nested function calls
+    pub recursion_limit: usize,
+    /// Maximum length of the interpreter's value stack
+    pub stack_size_limit: usize,
+    /// Maximum length of a string, array or object a script builds
+    ///
+    /// Only enforced by engines that can meter allocations, such as Rhai; Boa cannot.
+    pub max_data_size: Option<usize>,
+}
+
+impl Default for WhereLimits {
+    fn default() -> Self {
+        WhereLimits {
+            loop_iteration_limit: 1_000_000,
+            instruction_budget: Some(50_000_000),
+            timeout: Some(Duration::from_secs(1)),
+            recursion_limit: 256,
+            stack_size_limit: 10 * 1024,
+            max_data_size: Some(1_000_000),
         }
     }
 }
@@ -126,6 +242,22 @@ impl QueryContext {
             options,
         }
     }
+
+    /// Register a native predicate on this context's registry
+    pub fn register_function<F>(&mut self, name: impl Into<String>, function: F)
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        self.registry.register_function(name, function);
+    }
+
+    /// Register a native predicate taking `$function` arguments on this context's registry
+    pub fn register_function_with_args<F>(&mut self, name: impl Into<String>, function: F)
+    where
+        F: Fn(&Value, &[Value]) -> bool + Send + Sync + 'static,
+    {
+        self.registry.register_function_with_args(name, function);
+    }
 }
 
 /// Represents a compiled query that can be executed against values
@@ -143,19 +275,19 @@ impl CompiledQuery {
     }
 
     pub fn test(&self, value: &Value) -> SiftResult<bool> {
-        if self.operations.is_empty() {
-            return Ok(true);
-        }
+        test_all(&self.operations, value)
+    }
+}
 
-        // For multiple operations at the root level, they're implicitly AND-ed
-        for operation in &self.operations {
-            if !operation.test(value, None, None)? {
-                return Ok(false);
-            }
+/// Test a value against operations that are implicitly AND-ed
+pub(crate) fn test_all(operations: &[Box<dyn Operation>], value: &Value) -> SiftResult<bool> {
+    for operation in operations {
+        if !operation.test(value, None, None)? {
+            return Ok(false);
         }
-
-        Ok(true)
     }
+
+    Ok(true)
 }
 
 /// Utility functions for value comparison and manipulation
@@ -372,6 +504,47 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_compiled_query_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CompiledQuery>();
+    }
+
+    #[test]
+    fn test_where_registered_with_compiled_in_engine() {
+        let registry = OperatorRegistry::new();
+        let where_operator = registry.get("$where").unwrap();
+        assert_eq!(where_operator.name(), "$where");
+        assert!(registry.get("$function").is_some());
+    }
+
+    #[test]
+    fn test_native_where_without_script_engine() {
+        let mut context = QueryContext::new();
+        context.register_function("isAdult", |doc: &Value| doc["age"].as_u64().is_some_and(|age| age >= 18));
+        let query = crate::Query::from_value(&json!({"$where": {"$fn": "isAdult"}})).unwrap();
+        let compiled = query.compile_with_context(context).unwrap();
+        assert!(compiled.test(&json!({"age": 30})).unwrap());
+        assert!(!compiled.test(&json!({"age": 12})).unwrap());
+
+        let script = crate::Query::from_value(&json!({"$where": "this.age >= 18"})).unwrap().compile();
+        if cfg!(any(feature = "boa", feature = "rhai")) {
+            assert!(script.is_ok());
+        } else {
+            assert!(matches!(script, Err(SiftError::UnsupportedOperation(_))));
+        }
+    }
+
+    #[test]
+    fn test_operator_names_are_sorted() {
+        let mut registry = OperatorRegistry::new();
+        registry.register("$always".to_string(), Box::new(crate::operations::EqOperator));
+        let names = registry.operator_names();
+        assert_eq!(names[0], "$all");
+        assert_eq!(names[1], "$always");
+        assert!(names.contains(&"$elemMatch"));
+    }
+
     #[test]
     fn test_values_equal() {
         assert!(utils::values_equal(&json!(null), &json!(null)));
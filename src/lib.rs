@@ -7,7 +7,7 @@
 //! 
 //! - Supports most MongoDB query operators: `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`, `$exists`, `$regex`, `$and`, `$or`, `$not`, `$all`, `$size`, `$mod`, `$type`, `$elemMatch`
 //! - Works with any data structure that implements `serde::Serialize`
-//! - Type-safe query construction
+//! - Type-safe query construction with [`QueryBuilder`]
 //! - Performance optimized for Rust
 //! 
 //! ## Basic Usage
@@ -30,9 +30,17 @@
 //! assert_eq!(results.len(), 2);
 //! ```
 
+pub mod analysis;
+pub mod builder;
 pub mod core;
+pub mod lint;
 pub mod operations;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod projection;
 pub mod query;
+pub mod sort;
+pub mod update;
 pub mod utils;
 
 // Import modular operations
@@ -41,27 +49,46 @@ pub mod operation_modules {
     pub mod elem_match_operation;
     pub mod logic_operations;
     pub mod exists_operation;
+    pub mod function_operation;
     pub mod regex_operation;
     pub mod mod_operation;
     pub mod where_operation;
     pub mod type_operation;
 }
 
+// Script engines available to $where
+pub mod script_engines {
+    #[cfg(feature = "boa")]
+    pub mod boa;
+    #[cfg(feature = "rhai")]
+    pub mod rhai;
+}
+
 // Re-export all operators
 pub use operation_modules::size_operation::SizeOperator;
 pub use operation_modules::elem_match_operation::ElemMatchOperator;
 pub use operation_modules::logic_operations::{AndOperator, OrOperator, NorOperator, NotOperator};
 pub use operation_modules::exists_operation::ExistsOperator;
+pub use operation_modules::function_operation::FunctionOperator;
 pub use operation_modules::regex_operation::RegexOperator;
 pub use operation_modules::mod_operation::ModOperator;
-#[cfg(feature = "server")]
-pub use operation_modules::where_operation::WhereOperator;
-#[cfg(not(feature = "server"))]
-pub use operation_modules::where_operation::WhereOperator;
+pub use operation_modules::where_operation::{default_script_engine, CompiledScript, ScriptEngine, WhereOperator};
 pub use operation_modules::type_operation::TypeOperator;
 
+pub use analysis::{InList, OperatorUse, QueryAnalysis};
+pub use builder::{Condition, FieldBuilder, QueryBuilder};
 pub use core::*;
+pub use lint::{lint, Diagnostic, Severity};
+#[cfg(feature = "parallel")]
+pub use parallel::{ParallelSiftExt, SiftFilter};
+pub use projection::Projection;
 pub use query::*;
+#[cfg(feature = "boa")]
+pub use script_engines::boa::BoaEngine;
+#[cfg(feature = "rhai")]
+pub use script_engines::rhai::RhaiEngine;
+pub use sort::{Sort, SortOrder};
+pub use update::Update;
 
 use serde_json::Value;
 use std::error::Error;
@@ -75,6 +102,22 @@ pub enum SiftError {
     UnsupportedOperation(String),
     SerializationError(String),
     EvaluationError(String),
+    /// A `$where` evaluation exceeded its [`WhereLimits`]
+    ResourceLimitExceeded(String),
+}
+
+impl SiftError {
+    /// Name of the error variant, for reporting errors by kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SiftError::InvalidQuery(_) => "InvalidQuery",
+            SiftError::InvalidValue(_) => "InvalidValue",
+            SiftError::UnsupportedOperation(_) => "UnsupportedOperation",
+            SiftError::SerializationError(_) => "SerializationError",
+            SiftError::EvaluationError(_) => "EvaluationError",
+            SiftError::ResourceLimitExceeded(_) => "ResourceLimitExceeded",
+        }
+    }
 }
 
 impl fmt::Display for SiftError {
@@ -85,6 +128,7 @@ impl fmt::Display for SiftError {
             SiftError::UnsupportedOperation(msg) => write!(f, "Unsupported operation: {}", msg),
             SiftError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
             SiftError::EvaluationError(msg) => write!(f, "Evaluation error: {}", msg),
+            SiftError::ResourceLimitExceeded(msg) => write!(f, "Resource limit exceeded: {}", msg),
         }
     }
 }
@@ -0,0 +1,467 @@
+//! Checks for queries that are suspicious or cannot work as written
+//!
+//! [`lint`] walks a query without compiling it and reports every problem it
+//! finds, each with the JSON path of the offending value. Some of these the
+//! compiler would also reject, but only one at a time and without a location;
+//! others, such as an always-false range, compile and silently match nothing.
+
+use crate::core::{utils, OperatorRegistry};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+/// How serious a [`Diagnostic`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Redundant but harmless, e.g. `$and` with a single condition
+    Info,
+    /// Valid, but unlikely to do what was meant
+    Warning,
+    /// The query is rejected when compiled
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// One problem found by [`lint`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable name of the check, e.g. `ExistsNotBoolean`
+    pub code: &'static str,
+    pub message: String,
+    /// JSON path of the offending value within the query, e.g. `$.age.$in`
+    pub path: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} at {}: {}", self.severity, self.code, self.path, self.message)
+    }
+}
+
+/// Type names `$type` understands
+const TYPE_NAMES: [&str; 11] = [
+    "null", "bool", "boolean", "number", "double", "int", "integer", "long", "string", "array", "object",
+];
+
+/// Check a query for operators used in ways that are invalid or probably unintended
+///
+/// ```rust
+/// use serde_json::json;
+/// use sift_rs::{lint, Severity};
+///
+/// let diagnostics = lint(&json!({"deleted": {"$exists": "false"}, "age": {"$gt": 65, "$lt": 18}}));
+/// assert_eq!(diagnostics[0].code, "ExistsNotBoolean");
+/// assert_eq!(diagnostics[0].severity, Severity::Error);
+/// assert_eq!(diagnostics[0].path, "$.deleted.$exists");
+/// assert_eq!(diagnostics[1].code, "UnsatisfiableRange");
+/// ```
+pub fn lint(query: &Value) -> Vec<Diagnostic> {
+    let registry = OperatorRegistry::new();
+    let mut linter = Linter {
+        registry: &registry,
+        diagnostics: Vec::new(),
+        numeric_fields: HashSet::new(),
+        regexes: Vec::new(),
+    };
+    linter.query(query, "$", "");
+
+    // Fields are only known to hold numbers once the whole query has been seen
+    for (field, path) in std::mem::take(&mut linter.regexes) {
+        if linter.numeric_fields.contains(&field) {
+            linter.report(
+                Severity::Warning,
+                "RegexOnNumericField",
+                &path,
+                format!("'{}' is compared with numbers elsewhere in the query, but $regex only matches strings", field),
+            );
+        }
+    }
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    registry: &'a OperatorRegistry,
+    diagnostics: Vec<Diagnostic>,
+    /// Fields compared with a number somewhere in the query
+    numeric_fields: HashSet<String>,
+    /// Field and path of every `$regex`
+    regexes: Vec<(String, String)>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, severity: Severity, code: &'static str, path: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            message,
+            path: path.to_string(),
+        });
+    }
+
+    /// Check a query object; `prefix` is the field the query applies to inside `$elemMatch`
+    fn query(&mut self, query: &Value, path: &str, prefix: &str) {
+        let Value::Object(clauses) = query else {
+            return;
+        };
+        for (key, value) in clauses {
+            let child = child_path(path, key);
+            match key.as_str() {
+                "$and" | "$or" | "$nor" => self.logical(key, value, &child, prefix),
+                _ if key.starts_with('$') => self.unknown_operator(key, &child),
+                _ => {
+                    let field = match prefix {
+                        "" => key.clone(),
+                        _ => format!("{}.{}", prefix, key),
+                    };
+                    self.condition(&field, value, &child);
+                }
+            }
+        }
+    }
+
+    fn logical(&mut self, operator: &str, value: &Value, path: &str, prefix: &str) {
+        let Value::Array(queries) = value else {
+            return self.report(
+                Severity::Error,
+                "LogicalNotArray",
+                path,
+                format!("{} takes an array of queries", operator),
+            );
+        };
+        match (operator, queries.len()) {
+            ("$or", 0) => self.report(
+                Severity::Warning,
+                "EmptyLogical",
+                path,
+                "$or with no conditions matches no document".to_string(),
+            ),
+            (_, 0) => self.report(
+                Severity::Warning,
+                "EmptyLogical",
+                path,
+                format!("{} with no conditions matches every document", operator),
+            ),
+            ("$nor", 1) => self.report(
+                Severity::Info,
+                "SingleElementLogical",
+                path,
+                "$nor with one condition can be written as $not".to_string(),
+            ),
+            (_, 1) => self.report(
+                Severity::Info,
+                "SingleElementLogical",
+                path,
+                format!("{} with one condition is the same as the condition itself", operator),
+            ),
+            _ => {}
+        }
+        for (index, query) in queries.iter().enumerate() {
+            self.query(query, &format!("{}[{}]", path, index), prefix);
+        }
+    }
+
+    fn unknown_operator(&mut self, operator: &str, path: &str) {
+        if self.registry.get(operator).is_none() {
+            self.report(
+                Severity::Error,
+                "UnknownOperator",
+                path,
+                format!("Unknown operator {}", operator),
+            );
+        }
+    }
+
+    /// Check the condition on one field, e.g. `{"$gt": 1}` or a literal value
+    fn condition(&mut self, field: &str, condition: &Value, path: &str) {
+        let operators = match condition {
+            Value::Number(_) => {
+                self.numeric_fields.insert(field.to_string());
+                return;
+            }
+            Value::Object(operators) if operators.keys().any(|key| key.starts_with('$')) => operators,
+            _ => return,
+        };
+
+        let literals: Vec<&str> = operators
+            .keys()
+            .filter(|key| !key.starts_with('$'))
+            .map(String::as_str)
+            .collect();
+        if !literals.is_empty() {
+            self.report(
+                Severity::Warning,
+                "MixedOperatorsAndFields",
+                path,
+                format!(
+                    "'{}' is mixed with operators, so '{}' must equal an object with exactly those keys; \
+                     use dotted paths such as '{}.{}' to query inside it",
+                    literals.join("', '"),
+                    field,
+                    field,
+                    literals[0]
+                ),
+            );
+        }
+
+        for (operator, operand) in operators.iter().filter(|(key, _)| key.starts_with('$')) {
+            self.operator(field, operator, operand, &child_path(path, operator));
+        }
+        self.range(operators, path);
+    }
+
+    fn operator(&mut self, field: &str, operator: &str, operand: &Value, path: &str) {
+        match operator {
+            "$eq" | "$ne" | "$gt" | "$gte" | "$lt" | "$lte" => {
+                if operand.is_number() {
+                    self.numeric_fields.insert(field.to_string());
+                }
+            }
+            "$exists" => {
+                if !operand.is_boolean() {
+                    let message = match operand.as_str() {
+                        Some(text @ ("true" | "false")) => {
+                            format!("$exists takes a boolean; write {} without quotes", text)
+                        }
+                        _ => "$exists takes true or false".to_string(),
+                    };
+                    self.report(Severity::Error, "ExistsNotBoolean", path, message);
+                }
+            }
+            "$in" | "$nin" | "$all" => self.values(field, operator, operand, path),
+            "$size" => {
+                if operand.as_u64().is_none() {
+                    self.report(
+                        Severity::Error,
+                        "SizeNotInteger",
+                        path,
+                        format!("$size takes a non-negative whole number, not {}", operand),
+                    );
+                }
+            }
+            "$regex" => match operand.as_str() {
+                None => self.report(
+                    Severity::Error,
+                    "RegexNotString",
+                    path,
+                    "$regex takes the pattern as a string".to_string(),
+                ),
+                Some(pattern) => match regex::Regex::new(pattern) {
+                    Ok(_) => self.regexes.push((field.to_string(), path.to_string())),
+                    Err(e) => self.report(Severity::Error, "InvalidRegex", path, format!("Invalid regex pattern: {}", e)),
+                },
+            },
+            "$type" => {
+                if let Some(name) = operand.as_str().filter(|name| !TYPE_NAMES.contains(name)) {
+                    self.report(
+                        Severity::Warning,
+                        "UnknownType",
+                        path,
+                        format!("'{}' is not a type name $type knows, so it matches nothing", name),
+                    );
+                }
+            }
+            "$not" => self.condition(field, operand, path),
+            "$elemMatch" => match operand {
+                Value::Object(query) if query.keys().all(|key| key.starts_with('$')) => {
+                    self.condition(field, operand, path)
+                }
+                _ => self.query(operand, path, field),
+            },
+            _ => self.unknown_operator(operator, path),
+        }
+    }
+
+    /// Check the array operand of `$in`, `$nin` or `$all`
+    fn values(&mut self, field: &str, operator: &str, operand: &Value, path: &str) {
+        let Value::Array(values) = operand else {
+            return self.report(
+                Severity::Error,
+                "InNotArray",
+                path,
+                format!("{} takes an array of values; write [{}] to match that one value", operator, operand),
+            );
+        };
+        if values.is_empty() {
+            let (severity, outcome) = match operator {
+                "$nin" => (Severity::Info, "every"),
+                _ => (Severity::Warning, "no"),
+            };
+            self.report(
+                severity,
+                "EmptyIn",
+                path,
+                format!("{} with no values matches {} document", operator, outcome),
+            );
+        }
+        if values.iter().any(Value::is_number) {
+            self.numeric_fields.insert(field.to_string());
+        }
+    }
+
+    /// Warn when the lower and upper bounds on a field leave no value in between
+    fn range(&mut self, operators: &Map<String, Value>, path: &str) {
+        let bound = |names: [&'static str; 2]| {
+            names
+                .into_iter()
+                .find_map(|name| operators.get(name).map(|value| (name, value)))
+        };
+        let (Some((low_op, low)), Some((high_op, high))) = (bound(["$gt", "$gte"]), bound(["$lt", "$lte"])) else {
+            return;
+        };
+        let comparable = (low.is_number() && high.is_number()) || (low.is_string() && high.is_string());
+        let empty = comparable
+            && match utils::compare_values(low, high) {
+                Some(Ordering::Greater) => true,
+                Some(Ordering::Equal) => low_op == "$gt" || high_op == "$lt",
+                _ => false,
+            };
+        if empty {
+            self.report(
+                Severity::Warning,
+                "UnsatisfiableRange",
+                path,
+                format!(
+                    "No value is both {} {} and {} {}; only an array with elements on either side can match",
+                    low_op, low, high_op, high
+                ),
+            );
+        }
+    }
+}
+
+/// Append an object key to a JSON path, quoting keys that are not plain names
+pub(crate) fn child_path(path: &str, key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|first| !first.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn codes(query: Value) -> Vec<(&'static str, String)> {
+        lint(&query)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.path))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_queries_have_no_diagnostics() {
+        assert!(lint(&json!({})).is_empty());
+        assert!(lint(&json!({
+            "age": {"$gte": 18, "$lt": 65},
+            "name": {"$regex": "^A"},
+            "tags": {"$in": ["a", "b"], "$size": 2},
+            "deleted": {"$exists": false},
+            "$or": [{"role": "admin"}, {"scores": {"$elemMatch": {"$gt": 90}}}],
+            "address": {"city": "Paris"},
+        }))
+        .is_empty());
+    }
+
+    #[test]
+    fn test_operand_types() {
+        assert_eq!(
+            codes(json!({"a": {"$exists": "true"}, "b": {"$in": 1}, "c": {"$size": {"$gt": 1}}, "d": {"$regex": 5}})),
+            vec![
+                ("ExistsNotBoolean", "$.a.$exists".to_string()),
+                ("InNotArray", "$.b.$in".to_string()),
+                ("SizeNotInteger", "$.c.$size".to_string()),
+                ("RegexNotString", "$.d.$regex".to_string()),
+            ]
+        );
+        let diagnostics = lint(&json!({"a": {"$exists": "false"}}));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("write false without quotes"), "{}", diagnostics[0]);
+
+        assert_eq!(
+            codes(json!({"a": {"$regex": "("}, "b": {"$type": "strnig"}, "c": {"$bogus": 1}, "$text": {}})),
+            vec![
+                ("InvalidRegex", "$.a.$regex".to_string()),
+                ("UnknownType", "$.b.$type".to_string()),
+                ("UnknownOperator", "$.c.$bogus".to_string()),
+                ("UnknownOperator", "$.$text".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regex_on_numeric_field() {
+        let diagnostics = lint(&json!({"$or": [{"zip": {"$regex": "^75"}}, {"zip": {"$in": [75001, 75002]}}]}));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            (diagnostics[0].code, diagnostics[0].severity, diagnostics[0].path.as_str()),
+            ("RegexOnNumericField", Severity::Warning, "$.$or[0].zip.$regex")
+        );
+        assert!(lint(&json!({"zip": {"$regex": "^75"}, "code": 75})).is_empty());
+    }
+
+    #[test]
+    fn test_mixed_operators_and_fields() {
+        let diagnostics = lint(&json!({"user": {"$exists": true, "name": "ana"}}));
+        assert_eq!((diagnostics[0].code, diagnostics[0].path.as_str()), ("MixedOperatorsAndFields", "$.user"));
+        assert!(diagnostics[0].message.contains("'user.name'"), "{}", diagnostics[0]);
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        assert_eq!(
+            codes(json!({"$and": [{"a": 1}], "$or": [], "$nor": {"a": 1}})),
+            vec![
+                ("SingleElementLogical", "$.$and".to_string()),
+                ("EmptyLogical", "$.$or".to_string()),
+                ("LogicalNotArray", "$.$nor".to_string()),
+            ]
+        );
+        let diagnostics = lint(&json!({"$or": [{"a": 1}, {"b": {"$in": []}}]}));
+        assert_eq!((diagnostics[0].code, diagnostics[0].path.as_str()), ("EmptyIn", "$.$or[1].b.$in"));
+        assert_eq!(lint(&json!({"a": {"$nin": []}}))[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn test_unsatisfiable_ranges() {
+        assert_eq!(codes(json!({"age": {"$gt": 65, "$lt": 18}})), vec![("UnsatisfiableRange", "$.age".to_string())]);
+        assert_eq!(codes(json!({"age": {"$gte": 5, "$lt": 5}})).len(), 1);
+        assert_eq!(codes(json!({"day": {"$gt": "2024-02", "$lte": "2024-01"}})).len(), 1);
+        assert!(lint(&json!({"age": {"$gte": 5, "$lte": 5}})).is_empty());
+        assert!(lint(&json!({"age": {"$gt": 5, "$lt": "10"}})).is_empty());
+    }
+
+    #[test]
+    fn test_nested_queries_and_paths() {
+        assert_eq!(
+            codes(json!({
+                "items": {"$elemMatch": {"qty": {"$size": -1}, "tags.v": {"$not": {"$exists": 1}}}},
+                "a.b": {"$in": 2},
+            })),
+            vec![
+                ("SizeNotInteger", "$.items.$elemMatch.qty.$size".to_string()),
+                ("ExistsNotBoolean", "$.items.$elemMatch[\"tags.v\"].$not.$exists".to_string()),
+                ("InNotArray", "$[\"a.b\"].$in".to_string()),
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::core::{Operation, QueryOperator, CompiledQuery};
+use crate::core::{test_all, Operation, QueryContext, QueryOperator};
 use crate::SiftResult;
 use serde_json::Value;
 
@@ -6,10 +6,19 @@ use serde_json::Value;
 pub struct ElemMatchOperator;
 
 impl QueryOperator for ElemMatchOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        let query = crate::query::Query::from_value(&params)?;
-        let compiled_subquery = query.compile()?;
-        Ok(Box::new(ElemMatchOperation { compiled_subquery }))
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
+    }
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        let query = crate::query::Query::from_value(params)?;
+        let subquery = query.compile_operations(context)?;
+        Ok(Box::new(ElemMatchOperation { subquery }))
     }
     
     fn name(&self) -> &'static str {
@@ -18,14 +27,14 @@ impl QueryOperator for ElemMatchOperator {
 }
 
 struct ElemMatchOperation {
-    compiled_subquery: CompiledQuery,
+    subquery: Vec<Box<dyn Operation>>,
 }
 
 impl Operation for ElemMatchOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
         if let Value::Array(array) = value {
             for item in array {
-                if self.compiled_subquery.test(item)? {
+                if test_all(&self.subquery, item)? {
                     return Ok(true);
                 }
             }
@@ -0,0 +1,123 @@
+use crate::core::{utils, NativeFunction, Operation, QueryContext, QueryOperator};
+use crate::{SiftError, SiftResult};
+use serde_json::Value;
+
+/// $function operator - calls a native function registered on the query context
+///
+/// `{"$function": {"name": "near", "args": ["$loc", 5]}}` calls `near` with the
+/// tested value and the arguments, where strings starting with `$` are resolved
+/// as field paths on the tested value.
+pub struct FunctionOperator;
+
+impl QueryOperator for FunctionOperator {
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
+    }
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        let name = params.get("name").and_then(Value::as_str).ok_or_else(|| {
+            SiftError::InvalidQuery("$function requires a function name".to_string())
+        })?;
+
+        let args = match params.get("args") {
+            None => Vec::new(),
+            Some(Value::Array(args)) => args.clone(),
+            Some(_) => {
+                return Err(SiftError::InvalidQuery("$function args must be an array".to_string()))
+            }
+        };
+
+        Ok(Box::new(NativeFunctionOperation::new(name, args, context)?))
+    }
+
+    fn name(&self) -> &'static str {
+        "$function"
+    }
+}
+
+/// Calls a registered native function, shared by `$function` and `{"$where": {"$fn": name}}`
+pub(crate) struct NativeFunctionOperation {
+    function: NativeFunction,
+    args: Vec<Value>,
+}
+
+impl NativeFunctionOperation {
+    pub(crate) fn new(name: &str, args: Vec<Value>, context: &QueryContext) -> SiftResult<Self> {
+        let function = context.registry.get_function(name).cloned().ok_or_else(|| {
+            SiftError::InvalidQuery(format!("Unknown function: {}", name))
+        })?;
+
+        Ok(NativeFunctionOperation { function, args })
+    }
+}
+
+impl Operation for NativeFunctionOperation {
+    fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
+        let args: Vec<Value> = self
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Value::String(path) if path.starts_with('$') => {
+                    utils::get_nested_value(value, &path[1..]).cloned().unwrap_or(Value::Null)
+                }
+                _ => arg.clone(),
+            })
+            .collect();
+
+        Ok((self.function)(value, &args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Query;
+    use serde_json::json;
+
+    fn near_context() -> QueryContext {
+        let mut context = QueryContext::new();
+        context.register_function_with_args("near", |_doc, args| {
+            match (args[0].as_f64(), args[1].as_f64()) {
+                (Some(position), Some(radius)) => position.abs() <= radius,
+                _ => false,
+            }
+        });
+        context
+    }
+
+    #[test]
+    fn test_function_resolves_field_arguments() {
+        let query = Query::from_value(&json!({"$function": {"name": "near", "args": ["$loc.x", 5]}})).unwrap();
+        let compiled = query.compile_with_context(near_context()).unwrap();
+
+        assert!(compiled.test(&json!({"loc": {"x": -3}})).unwrap());
+        assert!(!compiled.test(&json!({"loc": {"x": 7}})).unwrap());
+        assert!(!compiled.test(&json!({})).unwrap());
+    }
+
+    #[test]
+    fn test_function_at_field_level() {
+        let query = Query::from_value(&json!({"loc": {"$function": {"name": "near", "args": ["$x", 5]}}})).unwrap();
+        let compiled = query.compile_with_context(near_context()).unwrap();
+
+        assert!(compiled.test(&json!({"loc": {"x": 2}})).unwrap());
+        assert!(!compiled.test(&json!({"loc": {"x": 9}})).unwrap());
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let unknown = Query::from_value(&json!({"$function": {"name": "missing"}})).unwrap();
+        assert!(matches!(unknown.compile(), Err(SiftError::InvalidQuery(_))));
+
+        let bad_args = Query::from_value(&json!({"$function": {"name": "near", "args": 5}})).unwrap();
+        assert!(matches!(
+            bad_args.compile_with_context(near_context()),
+            Err(SiftError::InvalidQuery(_))
+        ));
+    }
+}
@@ -1,34 +1,54 @@
-use crate::core::{Operation, QueryOperator};
+use crate::core::{test_all, Operation, QueryContext, QueryOperator};
+use crate::query::Query;
 use crate::{SiftError, SiftResult};
 use serde_json::Value;
 
+/// Compile each sub-query of a logical operator with the caller's context
+fn compile_queries(
+    name: &str,
+    params: &Value,
+    context: &QueryContext,
+) -> SiftResult<Vec<Vec<Box<dyn Operation>>>> {
+    if let Value::Array(queries) = params {
+        queries
+            .iter()
+            .map(|query_value| Query::from_value(query_value)?.compile_operations(context))
+            .collect()
+    } else {
+        Err(SiftError::InvalidQuery(format!("{} requires an array of queries", name)))
+    }
+}
+
 /// $and operator - all queries must match
 pub struct AndOperator;
 
 impl QueryOperator for AndOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        if let Value::Array(queries) = params {
-            Ok(Box::new(AndOperation { queries: queries.clone() }))
-        } else {
-            Err(SiftError::InvalidQuery("$and requires an array of queries".to_string()))
-        }
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
     }
-    
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        Ok(Box::new(AndOperation { queries: compile_queries("$and", params, context)? }))
+    }
+
     fn name(&self) -> &'static str {
         "$and"
     }
 }
 
 struct AndOperation {
-    queries: Vec<Value>,
+    queries: Vec<Vec<Box<dyn Operation>>>,
 }
 
 impl Operation for AndOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
-        for query_value in &self.queries {
-            // Create a temporary query for each sub-query
-            let query = crate::query::Query::from_value(query_value)?;
-            if !query.test(value)? {
+        for query in &self.queries {
+            if !test_all(query, value)? {
                 return Ok(false);
             }
         }
@@ -40,28 +60,32 @@ impl Operation for AndOperation {
 pub struct OrOperator;
 
 impl QueryOperator for OrOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        if let Value::Array(queries) = params {
-            Ok(Box::new(OrOperation { queries: queries.clone() }))
-        } else {
-            Err(SiftError::InvalidQuery("$or requires an array of queries".to_string()))
-        }
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
+    }
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        Ok(Box::new(OrOperation { queries: compile_queries("$or", params, context)? }))
     }
-    
+
     fn name(&self) -> &'static str {
         "$or"
     }
 }
 
 struct OrOperation {
-    queries: Vec<Value>,
+    queries: Vec<Vec<Box<dyn Operation>>>,
 }
 
 impl Operation for OrOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
-        for query_value in &self.queries {
-            let query = crate::query::Query::from_value(query_value)?;
-            if query.test(value)? {
+        for query in &self.queries {
+            if test_all(query, value)? {
                 return Ok(true);
             }
         }
@@ -73,28 +97,32 @@ impl Operation for OrOperation {
 pub struct NorOperator;
 
 impl QueryOperator for NorOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        if let Value::Array(queries) = params {
-            Ok(Box::new(NorOperation { queries: queries.clone() }))
-        } else {
-            Err(SiftError::InvalidQuery("$nor requires an array of queries".to_string()))
-        }
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
+    }
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        Ok(Box::new(NorOperation { queries: compile_queries("$nor", params, context)? }))
     }
-    
+
     fn name(&self) -> &'static str {
         "$nor"
     }
 }
 
 struct NorOperation {
-    queries: Vec<Value>,
+    queries: Vec<Vec<Box<dyn Operation>>>,
 }
 
 impl Operation for NorOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
-        for query_value in &self.queries {
-            let query = crate::query::Query::from_value(query_value)?;
-            if query.test(value)? {
+        for query in &self.queries {
+            if test_all(query, value)? {
                 return Ok(false);
             }
         }
@@ -106,40 +134,51 @@ impl Operation for NorOperation {
 pub struct NotOperator;
 
 impl QueryOperator for NotOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        Ok(Box::new(NotOperation { query: params.clone() }))
-    }
-    
-    fn name(&self) -> &'static str {
-        "$not"
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
     }
-}
-
-struct NotOperation {
-    query: Value,
-}
 
-impl Operation for NotOperation {
-    fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
         // Check if the query is a field-level operation (contains operators like $eq, $gt, etc.)
-        if let Value::Object(obj) = &self.query {
+        if let Value::Object(obj) = params {
             // If it's a single operation (like {"$eq": "active"}), apply it directly to the value
             if obj.len() == 1 && obj.keys().next().unwrap().starts_with('$') {
                 let (op_name, op_value) = obj.iter().next().unwrap();
-                
-                // Get the operator from the registry
-                let context = crate::core::QueryContext::new();
+
                 if let Some(operator) = context.registry.get(op_name) {
-                    let operation = operator.create_operation(op_value, &Value::Null)?;
-                    let result = operation.test(value, _key, _parent)?;
-                    return Ok(!result);
+                    let operation = operator.create_operation_with_context(op_value, &Value::Null, context)?;
+                    return Ok(Box::new(NotOperation::Operator(operation)));
                 }
             }
         }
-        
+
         // Fall back to treating it as a full query (for complex nested queries)
-        let query = crate::query::Query::from_value(&self.query)?;
-        let result = query.test(value)?;
+        let operations = Query::from_value(params)?.compile_operations(context)?;
+        Ok(Box::new(NotOperation::Query(operations)))
+    }
+
+    fn name(&self) -> &'static str {
+        "$not"
+    }
+}
+
+enum NotOperation {
+    /// A single operator applied to the value itself, e.g. `{"$not": {"$gt": 5}}`
+    Operator(Box<dyn Operation>),
+    Query(Vec<Box<dyn Operation>>),
+}
+
+impl Operation for NotOperation {
+    fn test(&self, value: &Value, key: Option<&str>, parent: Option<&Value>) -> SiftResult<bool> {
+        let result = match self {
+            NotOperation::Operator(operation) => operation.test(value, key, parent)?,
+            NotOperation::Query(operations) => test_all(operations, value)?,
+        };
         Ok(!result)
     }
 }
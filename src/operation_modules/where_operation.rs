@@ -1,68 +1,127 @@
-use crate::core::{Operation, QueryOperator};
+use crate::core::{Operation, QueryContext, QueryOperator, QueryOptions, WhereLimits};
+use crate::operation_modules::function_operation::NativeFunctionOperation;
 use crate::{SiftError, SiftResult};
 use serde_json::Value;
+use std::sync::Arc;
 
-/// $where operator - evaluates JavaScript-like expressions
-pub struct WhereOperator;
+/// A scripting language `$where` sources can be written in
+///
+/// Engines apply the [`WhereLimits`] they can enforce and report violations as
+/// [`SiftError::ResourceLimitExceeded`].
+pub trait ScriptEngine: Send + Sync {
+    /// Compile a `$where` source, reporting syntax errors as [`SiftError::InvalidQuery`]
+    fn compile(&self, source: &str, limits: &WhereLimits) -> SiftResult<Box<dyn CompiledScript>>;
+
+    fn name(&self) -> &'static str;
+}
+
+/// A `$where` source compiled by a [`ScriptEngine`]
+pub trait CompiledScript: Send + Sync {
+    /// Evaluate the script with the document bound to it, returning whether it matched
+    fn evaluate(&self, value: &Value) -> SiftResult<bool>;
+}
+
+/// The engine `$where` uses by default, if one is compiled in
+///
+/// Boa is preferred over Rhai when both features are enabled.
+#[cfg(feature = "boa")]
+pub fn default_script_engine() -> Option<Arc<dyn ScriptEngine>> {
+    Some(Arc::new(crate::script_engines::boa::BoaEngine))
+}
+
+/// The engine `$where` uses by default, if one is compiled in
+#[cfg(all(feature = "rhai", not(feature = "boa")))]
+pub fn default_script_engine() -> Option<Arc<dyn ScriptEngine>> {
+    Some(Arc::new(crate::script_engines::rhai::RhaiEngine))
+}
+
+/// The engine `$where` uses by default, if one is compiled in
+#[cfg(not(any(feature = "boa", feature = "rhai")))]
+pub fn default_script_engine() -> Option<Arc<dyn ScriptEngine>> {
+    None
+}
+
+/// $where operator - evaluates scripts with a [`ScriptEngine`]
+///
+/// `{"$where": {"$fn": name}}` calls a native function registered on the query
+/// context instead, without involving the engine, so it works without one.
+pub struct WhereOperator {
+    engine: Option<Arc<dyn ScriptEngine>>,
+}
+
+impl WhereOperator {
+    pub fn new(engine: impl ScriptEngine + 'static) -> Self {
+        WhereOperator {
+            engine: Some(Arc::new(engine)),
+        }
+    }
+
+    pub fn with_shared_engine(engine: Arc<dyn ScriptEngine>) -> Self {
+        WhereOperator { engine: Some(engine) }
+    }
+
+    /// Accept only native functions; script sources fail to compile
+    pub fn native_only() -> Self {
+        WhereOperator { engine: None }
+    }
+
+    pub fn engine(&self) -> Option<&dyn ScriptEngine> {
+        self.engine.as_deref()
+    }
+}
 
 impl QueryOperator for WhereOperator {
-    fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
-        if let Some(expression) = params.as_str() {
-            Ok(Box::new(WhereOperation { expression: expression.to_string() }))
+    fn create_operation(&self, params: &Value, parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
+        self.create_operation_with_context(params, parent_query, &QueryContext::new())
+    }
+
+    fn create_operation_with_context(
+        &self,
+        params: &Value,
+        _parent_query: &Value,
+        context: &QueryContext,
+    ) -> SiftResult<Box<dyn Operation>> {
+        if let Some(name) = params.get("$fn") {
+            let name = name.as_str().ok_or_else(|| {
+                SiftError::InvalidQuery("$fn requires a function name".to_string())
+            })?;
+            return Ok(Box::new(NativeFunctionOperation::new(name, Vec::new(), context)?));
+        }
+
+        let QueryOptions { allow_where, where_limits, .. } = &context.options;
+        if !allow_where {
+            return Err(SiftError::UnsupportedOperation(
+                "$where is disabled by the query options".to_string(),
+            ));
+        }
+
+        if let Some(source) = params.as_str() {
+            let engine = self.engine.as_ref().ok_or_else(|| {
+                SiftError::UnsupportedOperation(
+                    "$where scripts need a script engine; enable the `boa` or `rhai` feature".to_string(),
+                )
+            })?;
+            // Compile up front so syntax errors surface when the query is compiled
+            let script = engine.compile(source, where_limits)?;
+            Ok(Box::new(WhereOperation { script }))
         } else {
-            Err(SiftError::InvalidQuery("$where requires a JavaScript expression string".to_string()))
+            Err(SiftError::InvalidQuery(
+                "$where requires a script string or {\"$fn\": name}".to_string(),
+            ))
         }
     }
-    
+
     fn name(&self) -> &'static str {
         "$where"
     }
 }
 
 struct WhereOperation {
-    expression: String,
+    script: Box<dyn CompiledScript>,
 }
 
 impl Operation for WhereOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
-        // Use Boa to evaluate the JavaScript expression
-        self.evaluate_expression(&self.expression, value)
-    }
-}
-
-impl WhereOperation {
-    fn evaluate_expression(&self, expr: &str, value: &Value) -> SiftResult<bool> {
-        use boa_engine::{Context, JsValue, Source};
-        
-        // Create a JavaScript context
-        let mut context = Context::default();
-        
-        // Convert the JSON value to a JavaScript object string
-        let js_object_str = serde_json::to_string(value)
-            .map_err(|e| SiftError::EvaluationError(format!("Failed to serialize JSON: {}", e)))?;
-        
-        // Create a script that sets 'this' to our JSON object and evaluates the expression
-        let script_code = format!(
-            "const thisObj = {}; (function() {{ return {}; }}).call(thisObj);",
-            js_object_str, expr
-        );
-
-        // Evaluate the script
-        let result = context.eval(Source::from_bytes(&script_code))
-            .map_err(|e| SiftError::EvaluationError(format!("JavaScript execution error: {:?}", e)))?;
-
-        // Convert the result to a boolean
-        match result {
-            JsValue::Boolean(b) => Ok(b),
-            JsValue::Integer(n) => Ok(n != 0),
-            JsValue::Rational(n) => Ok(n != 0.0),
-            JsValue::String(s) => {
-                let s_str = s.as_str();
-                Ok(!s_str.is_empty())
-            },
-            JsValue::Null | JsValue::Undefined => Ok(false),
-            JsValue::Object(_) => Ok(true), // Objects are truthy in JavaScript
-            _ => Ok(true), // Other types are generally truthy
-        }
+        self.script.evaluate(value)
     }
 }
@@ -1,7 +1,8 @@
 use crate::core::{Operation, QueryOperator, utils};
 use crate::{SiftError, SiftResult};
-use serde_json::Value;
+use serde_json::{Number, Value};
 use std::cmp::Ordering;
+use std::collections::HashSet;
 
 // Import operations from modules - these will be added separately to lib.rs
 
@@ -157,13 +158,110 @@ impl Operation for LteOperation {
     }
 }
 
+/// Largest magnitude at which every integer is exactly representable as `f64`
+const MAX_EXACT_F64_INT: f64 = 9_007_199_254_740_992.0;
+
+/// Hash key for a number whose `values_equal` comparison is exact
+///
+/// Numbers are keyed by their `f64` bits so `1` and `1.0` share a key. Fractions
+/// below 1 (compared with an epsilon) and integers too large for `f64` return
+/// `None` and must be compared linearly.
+fn number_key(number: &Number) -> Option<u64> {
+    let float = number.as_f64()?;
+    let exact = if let Some(int) = number.as_i64() {
+        int.unsigned_abs() <= MAX_EXACT_F64_INT as u64
+    } else if number.is_u64() {
+        false
+    } else if float.fract() == 0.0 {
+        float.abs() <= MAX_EXACT_F64_INT
+    } else {
+        float.abs() >= 1.0
+    };
+    // Adding 0.0 folds -0.0 into 0.0
+    exact.then(|| (float + 0.0).to_bits())
+}
+
+/// Precomputed membership lookup for `$in` / `$nin` lists
+///
+/// Scalar members are hashed; numbers that need epsilon comparison and
+/// array/object members fall back to a linear `values_equal` scan. A probe
+/// without a hash key may be within epsilon of any number, so it is compared
+/// against the hashed numbers too.
+struct ValueLookup {
+    has_null: bool,
+    has_true: bool,
+    has_false: bool,
+    numbers: HashSet<u64>,
+    hashed_numbers: Vec<Value>,
+    strings: HashSet<String>,
+    linear_numbers: Vec<Value>,
+    linear_compound: Vec<Value>,
+}
+
+impl ValueLookup {
+    fn new(values: &[Value]) -> Self {
+        let mut lookup = ValueLookup {
+            has_null: false,
+            has_true: false,
+            has_false: false,
+            numbers: HashSet::new(),
+            hashed_numbers: Vec::new(),
+            strings: HashSet::new(),
+            linear_numbers: Vec::new(),
+            linear_compound: Vec::new(),
+        };
+
+        for value in values {
+            match value {
+                Value::Null => lookup.has_null = true,
+                Value::Bool(true) => lookup.has_true = true,
+                Value::Bool(false) => lookup.has_false = true,
+                Value::Number(n) => match number_key(n) {
+                    Some(key) => {
+                        if lookup.numbers.insert(key) {
+                            lookup.hashed_numbers.push(value.clone());
+                        }
+                    }
+                    None => lookup.linear_numbers.push(value.clone()),
+                },
+                Value::String(s) => {
+                    lookup.strings.insert(s.clone());
+                }
+                Value::Array(_) | Value::Object(_) => lookup.linear_compound.push(value.clone()),
+            }
+        }
+
+        lookup
+    }
+
+    fn contains(&self, value: &Value) -> bool {
+        match value {
+            Value::Null => self.has_null,
+            Value::Bool(true) => self.has_true,
+            Value::Bool(false) => self.has_false,
+            Value::Number(n) => {
+                let equal = |expected: &Value| utils::values_equal(value, expected);
+                match number_key(n) {
+                    Some(key) => self.numbers.contains(&key) || self.linear_numbers.iter().any(equal),
+                    None => self.hashed_numbers.iter().chain(&self.linear_numbers).any(equal),
+                }
+            }
+            Value::String(s) => self.strings.contains(s.as_str()),
+            Value::Array(_) | Value::Object(_) => self
+                .linear_compound
+                .iter()
+                .any(|expected| utils::values_equal(value, expected)),
+        }
+    }
+}
+
 /// $in operator - tests if value is in the given array
 pub struct InOperator;
 
 impl QueryOperator for InOperator {
     fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
         if let Value::Array(values) = params {
-            Ok(Box::new(InOperation { values: values.clone() }))
+            Ok(Box::new(InOperation { values: ValueLookup::new(values) }))
         } else {
             Err(SiftError::InvalidQuery("$in requires an array".to_string()))
         }
@@ -175,30 +273,18 @@ impl QueryOperator for InOperator {
 }
 
 struct InOperation {
-    values: Vec<Value>,
+    values: ValueLookup,
 }
 
 impl Operation for InOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
         // If the field value is an array, check if any element of the array is in the expected values
         if let Value::Array(array) = value {
-            for item in array {
-                for expected in &self.values {
-                    if utils::values_equal(item, expected) {
-                        return Ok(true);
-                    }
-                }
-            }
-            return Ok(false);
+            return Ok(array.iter().any(|item| self.values.contains(item)));
         }
         
         // For non-array values, check if the value itself is in the expected values
-        for expected in &self.values {
-            if utils::values_equal(value, expected) {
-                return Ok(true);
-            }
-        }
-        Ok(false)
+        Ok(self.values.contains(value))
     }
 }
 
@@ -208,7 +294,7 @@ pub struct NinOperator;
 impl QueryOperator for NinOperator {
     fn create_operation(&self, params: &Value, _parent_query: &Value) -> SiftResult<Box<dyn Operation>> {
         if let Value::Array(values) = params {
-            Ok(Box::new(NinOperation { values: values.clone() }))
+            Ok(Box::new(NinOperation { values: ValueLookup::new(values) }))
         } else {
             Err(SiftError::InvalidQuery("$nin requires an array".to_string()))
         }
@@ -220,30 +306,18 @@ impl QueryOperator for NinOperator {
 }
 
 struct NinOperation {
-    values: Vec<Value>,
+    values: ValueLookup,
 }
 
 impl Operation for NinOperation {
     fn test(&self, value: &Value, _key: Option<&str>, _parent: Option<&Value>) -> SiftResult<bool> {
-        // If the field value is an array, check if any element of the array is in the excluded values
+        // If the field value is an array, it fails if any element of the array is in the excluded values
         if let Value::Array(array) = value {
-            for item in array {
-                for expected in &self.values {
-                    if utils::values_equal(item, expected) {
-                        return Ok(false); // Found a match, so $nin fails
-                    }
-                }
-            }
-            return Ok(true); // No matches found, so $nin succeeds
+            return Ok(!array.iter().any(|item| self.values.contains(item)));
         }
         
         // For non-array values, check if the value itself is in the excluded values
-        for expected in &self.values {
-            if utils::values_equal(value, expected) {
-                return Ok(false);
-            }
-        }
-        Ok(true)
+        Ok(!self.values.contains(value))
     }
 }
 
@@ -289,3 +363,51 @@ impl Operation for AllOperation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lookup(values: Value) -> ValueLookup {
+        ValueLookup::new(values.as_array().unwrap())
+    }
+
+    #[test]
+    fn test_lookup_matches_values_equal() {
+        let members = json!([1, 2.5, -0.0, 0.1, 9007199254740993u64, "a", null, false, [1, 2], {"k": 1}]);
+        let set = lookup(members.clone());
+        let probes = json!([1, 1.0, 2.5, 0, 0.0, 0.1, 0.30000000000000004, 9007199254740993u64,
+            9007199254740992u64, "a", "b", null, false, true, [1, 2], [1.0, 2], {"k": 1}, {"k": 2}, 3,
+            1e-17, -1e-17, 0.9999999999999999, 2.4999999999999996, 9007199254740993.0]);
+
+        for probe in probes.as_array().unwrap() {
+            let expected = members
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|member| utils::values_equal(probe, member));
+            assert_eq!(set.contains(probe), expected, "probe {}", probe);
+        }
+    }
+
+    #[test]
+    fn test_in_compares_small_fractions_with_epsilon() {
+        let operation = InOperator.create_operation(&json!([0]), &Value::Null).unwrap();
+        assert!(operation.test(&json!(1e-17), None, None).unwrap());
+        let operation = InOperator.create_operation(&json!([1]), &Value::Null).unwrap();
+        assert!(operation.test(&json!(0.9999999999999999), None, None).unwrap());
+        let operation = NinOperator.create_operation(&json!([1]), &Value::Null).unwrap();
+        assert!(!operation.test(&json!(0.9999999999999999), None, None).unwrap());
+    }
+
+    #[test]
+    fn test_large_in_list() {
+        let ids: Vec<Value> = (0..10_000).map(|id| json!(id)).collect();
+        let operation = InOperator.create_operation(&Value::Array(ids), &Value::Null).unwrap();
+
+        assert!(operation.test(&json!(9_999.0), None, None).unwrap());
+        assert!(operation.test(&json!([20_000, 42]), None, None).unwrap());
+        assert!(!operation.test(&json!(10_000), None, None).unwrap());
+    }
+}
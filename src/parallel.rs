@@ -0,0 +1,98 @@
+use crate::core::CompiledQuery;
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::prelude::*;
+use serde_json::Value;
+use std::borrow::Borrow;
+
+impl CompiledQuery {
+    /// Return the values matching this query, testing them across the rayon thread pool
+    ///
+    /// Values whose evaluation fails are treated as non-matching, as with
+    /// [`create_filter`](crate::create_filter). Result order follows `values`.
+    pub fn filter_par<'a>(&self, values: &'a [Value]) -> Vec<&'a Value> {
+        values.par_iter().sift(self).collect()
+    }
+
+    /// Count the values matching this query, testing them across the rayon thread pool
+    pub fn count_par(&self, values: &[Value]) -> usize {
+        values.par_iter().sift(self).count()
+    }
+}
+
+/// Adds [`sift`](ParallelSiftExt::sift) to parallel iterators over JSON values
+pub trait ParallelSiftExt: ParallelIterator
+where
+    Self::Item: Borrow<Value>,
+{
+    /// Keep only the values matching `query`
+    fn sift(self, query: &CompiledQuery) -> SiftFilter<'_, Self> {
+        SiftFilter { base: self, query }
+    }
+}
+
+impl<I> ParallelSiftExt for I
+where
+    I: ParallelIterator,
+    I::Item: Borrow<Value>,
+{
+}
+
+/// Parallel iterator returned by [`ParallelSiftExt::sift`]
+pub struct SiftFilter<'q, I> {
+    base: I,
+    query: &'q CompiledQuery,
+}
+
+impl<I> ParallelIterator for SiftFilter<'_, I>
+where
+    I: ParallelIterator,
+    I::Item: Borrow<Value>,
+{
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let query = self.query;
+        self.base
+            .filter(move |value| query.test(value.borrow()).unwrap_or(false))
+            .drive_unindexed(consumer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Query;
+    use serde_json::json;
+
+    fn documents() -> Vec<Value> {
+        (0..1_000).map(|i| json!({"id": i, "even": i % 2 == 0})).collect()
+    }
+
+    #[test]
+    fn test_filter_par_matches_sequential() {
+        let docs = documents();
+        let query = Query::from_value(&json!({"even": true, "id": {"$lt": 100}}))
+            .unwrap()
+            .compile()
+            .unwrap();
+
+        let sequential: Vec<&Value> = docs.iter().filter(|doc| query.test(doc).unwrap()).collect();
+        assert_eq!(query.filter_par(&docs), sequential);
+        assert_eq!(query.count_par(&docs), 50);
+    }
+
+    #[test]
+    fn test_parallel_iterator_adapter() {
+        let docs = documents();
+        let query = Query::from_value(&json!({"id": {"$in": [1, 2, 3]}}))
+            .unwrap()
+            .compile()
+            .unwrap();
+
+        let ids: Vec<_> = docs.par_iter().sift(&query).map(|doc| doc["id"].clone()).collect();
+        assert_eq!(ids, vec![json!(1), json!(2), json!(3)]);
+    }
+}
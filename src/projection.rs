@@ -0,0 +1,209 @@
+use crate::{SiftError, SiftResult};
+use indexmap::map::Entry;
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+
+/// A MongoDB-style projection selecting which fields of a document to return
+///
+/// `{"name": 1, "address.city": 1}` keeps only the listed fields, while
+/// `{"password": 0}` removes them. As in MongoDB, `_id` is kept by inclusion
+/// projections unless it is explicitly excluded, and dotted paths reach into
+/// nested objects and arrays of objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projection {
+    include: bool,
+    paths: PathTree,
+}
+
+/// Projected paths, split on `.`; an empty subtree marks the end of a path
+#[derive(Clone, Debug, Default, PartialEq)]
+struct PathTree(IndexMap<String, PathTree>);
+
+impl PathTree {
+    /// Add `path`, refusing one that is a prefix of another projected path or extends one
+    fn insert(&mut self, path: &str) -> SiftResult<()> {
+        let mut node = self;
+        let mut segments = path.split('.').peekable();
+        while let Some(segment) = segments.next() {
+            node = match node.0.entry(segment.to_string()) {
+                Entry::Occupied(entry) if entry.get().is_leaf() || segments.peek().is_none() => {
+                    return Err(SiftError::InvalidQuery(format!("Path collision at '{}' in projection", path)))
+                }
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(PathTree::default()),
+            };
+        }
+        Ok(())
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Projection {
+    /// Parse a projection document such as `{"name": 1, "_id": 0}`
+    pub fn from_value(spec: &Value) -> SiftResult<Self> {
+        let Value::Object(fields) = spec else {
+            return Err(SiftError::InvalidQuery("Projection must be an object".to_string()));
+        };
+
+        let mut include = None;
+        let mut include_id = true;
+        let mut paths = PathTree::default();
+
+        for (path, flag) in fields {
+            let keep = match flag {
+                Value::Bool(keep) => *keep,
+                Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+                _ => {
+                    return Err(SiftError::InvalidQuery(format!(
+                        "Projection value for '{}' must be 0, 1, true or false",
+                        path
+                    )))
+                }
+            };
+
+            // `_id` may be toggled in either kind of projection
+            if path == "_id" {
+                include_id = keep;
+                continue;
+            }
+
+            match include {
+                Some(mode) if mode != keep => {
+                    return Err(SiftError::InvalidQuery(
+                        "Projection cannot mix inclusion and exclusion".to_string(),
+                    ))
+                }
+                _ => include = Some(keep),
+            }
+            paths.insert(path)?;
+        }
+
+        // A projection naming only `_id` keeps or removes just that field
+        let include = include.unwrap_or(include_id && !fields.is_empty());
+        // A projection reaching into `_id` already decides which parts of it to keep
+        if include == include_id && !paths.0.contains_key("_id") {
+            paths.insert("_id")?;
+        }
+
+        Ok(Projection { include, paths })
+    }
+
+    /// Apply the projection to a document, returning the projected copy
+    pub fn apply(&self, document: &Value) -> Value {
+        if self.include {
+            include_paths(document, &self.paths).unwrap_or_else(|| Value::Object(Map::new()))
+        } else {
+            let mut projected = document.clone();
+            exclude_paths(&mut projected, &self.paths);
+            projected
+        }
+    }
+}
+
+/// Copy the parts of `value` selected by `paths`, keeping the document's field order
+fn include_paths(value: &Value, paths: &PathTree) -> Option<Value> {
+    match value {
+        Value::Object(obj) => {
+            let mut projected = Map::new();
+            for (key, field) in obj {
+                if let Some(subtree) = paths.0.get(key) {
+                    if subtree.is_leaf() {
+                        projected.insert(key.clone(), field.clone());
+                    } else if let Some(nested) = include_paths(field, subtree) {
+                        projected.insert(key.clone(), nested);
+                    }
+                }
+            }
+            Some(Value::Object(projected))
+        }
+        // Sub-paths apply to every object in an array; other elements are dropped
+        Value::Array(items) => Some(Value::Array(
+            items
+                .iter()
+                .filter(|item| item.is_object() || item.is_array())
+                .filter_map(|item| include_paths(item, paths))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Remove the parts of `value` selected by `paths`
+fn exclude_paths(value: &mut Value, paths: &PathTree) {
+    match value {
+        Value::Object(obj) => {
+            for (key, subtree) in &paths.0 {
+                if subtree.is_leaf() {
+                    obj.shift_remove(key);
+                } else if let Some(field) = obj.get_mut(key) {
+                    exclude_paths(field, subtree);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                exclude_paths(item, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn project(spec: Value, document: Value) -> Value {
+        Projection::from_value(&spec).unwrap().apply(&document)
+    }
+
+    #[test]
+    fn test_inclusion_keeps_id_and_document_order() {
+        let document = json!({"_id": 1, "name": "Alice", "age": 30, "email": "a@example.com"});
+        assert_eq!(
+            project(json!({"email": 1, "name": 1}), document.clone()),
+            json!({"_id": 1, "name": "Alice", "email": "a@example.com"})
+        );
+        assert_eq!(
+            project(json!({"name": 1, "_id": 0}), document),
+            json!({"name": "Alice"})
+        );
+    }
+
+    #[test]
+    fn test_exclusion() {
+        let document = json!({"_id": 1, "name": "Alice", "secret": {"token": "x", "hint": "y"}});
+        assert_eq!(
+            project(json!({"secret.token": 0}), document.clone()),
+            json!({"_id": 1, "name": "Alice", "secret": {"hint": "y"}})
+        );
+        assert_eq!(project(json!({"_id": 0}), document), json!({"name": "Alice", "secret": {"token": "x", "hint": "y"}}));
+    }
+
+    #[test]
+    fn test_nested_paths_through_arrays() {
+        let document = json!({
+            "address": {"city": "Paris", "zip": "75001"},
+            "items": [{"sku": "a", "qty": 1}, {"sku": "b", "qty": 2}, 5]
+        });
+        assert_eq!(
+            project(json!({"address.city": 1, "items.sku": 1}), document),
+            json!({"address": {"city": "Paris"}, "items": [{"sku": "a"}, {"sku": "b"}]})
+        );
+    }
+
+    #[test]
+    fn test_invalid_projections() {
+        assert!(Projection::from_value(&json!({"a": 1, "b": 0})).is_err());
+        assert!(Projection::from_value(&json!({"a": "yes"})).is_err());
+        assert!(Projection::from_value(&json!([1])).is_err());
+        assert!(Projection::from_value(&json!({"_id": 1, "secret": 0})).is_ok());
+        assert!(Projection::from_value(&json!({"a": 1, "a.b": 1})).is_err());
+        assert!(Projection::from_value(&json!({"a.b": 0, "a": 0})).is_err());
+        assert!(Projection::from_value(&json!({"a.b": 1, "a.c": 1})).is_ok());
+    }
+}
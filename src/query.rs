@@ -1,16 +1,22 @@
 use crate::core::{CompiledQuery, Operation, QueryContext, utils};
 use crate::{SiftError, SiftResult};
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value};
 use std::collections::HashMap;
 
 /// Represents a MongoDB-style query that can be compiled and executed
-#[derive(Debug, Clone)]
+///
+/// Conditions keep the order in which they appear in the source query.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Query {
-    conditions: HashMap<String, QueryCondition>,
+    conditions: IndexMap<String, QueryCondition>,
+    /// Keys of each field's condition object as written, operators and literal fields interleaved
+    key_order: HashMap<String, Vec<String>>,
 }
 
 /// Represents a condition in a query (either a direct value match or an operation)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum QueryCondition {
     Value(Value),
     Operations(HashMap<String, Value>),
@@ -24,7 +30,8 @@ impl Query {
     /// Create a new empty query
     pub fn new() -> Self {
         Query {
-            conditions: HashMap::new(),
+            conditions: IndexMap::new(),
+            key_order: HashMap::new(),
         }
     }
 
@@ -34,79 +41,80 @@ impl Query {
             Value::Object(obj) => Self::from_object(obj),
             _ => {
                 // For non-object values, create an equality query
-                let mut conditions = HashMap::new();
-                conditions.insert("".to_string(), QueryCondition::Value(value.clone()));
-                Ok(Query { conditions })
+                let mut query = Query::new();
+                query.conditions.insert("".to_string(), QueryCondition::Value(value.clone()));
+                Ok(query)
             }
         }
     }
 
     /// Create a query from a JSON object
     pub fn from_object(obj: &Map<String, Value>) -> SiftResult<Self> {
-        let mut conditions = HashMap::new();
+        let mut conditions = IndexMap::new();
+        let mut key_order = HashMap::new();
 
         // Check if both $and and $or are present at the top level
-        let has_and = obj.contains_key("$and");
-        let has_or = obj.contains_key("$or");
-
-        if has_and && has_or {
-            // Both $and and $or are present - $or must be at top level with $and nested inside
-            // This is our rule: when both operators exist, $or is always the top-level operator
-            let mut and_value: Option<Value> = None;
-            let mut or_value: Option<Value> = None;
-            let mut other_conditions = HashMap::new();
-
-            for (key, value) in obj {
-                match key.as_str() {
-                    "$and" => {
-                        and_value = Some(value.clone());
-                    }
-                    "$or" => {
-                        or_value = Some(value.clone());
-                    }
-                    _ => {
-                        if matches!(key.as_str(), "$not" | "$nor" | "$where") {
-                            let mut operations = HashMap::new();
-                            operations.insert(key.clone(), value.clone());
-                            other_conditions.insert(key.clone(), QueryCondition::Operations(operations));
-                        } else {
-                            let condition = Self::parse_condition(value)?;
-                            other_conditions.insert(key.clone(), condition);
-                        }
-                    }
+        let nest_and_in_or = obj.contains_key("$and") && obj.contains_key("$or");
+
+        for (key, value) in obj {
+            if nest_and_in_or && matches!(key.as_str(), "$and" | "$or") {
+                // Both $and and $or are present - $or must be at top level with $and nested inside
+                // This is our rule: when both operators exist, $or is always the top-level operator
+                if conditions.contains_key("$or") {
+                    continue;
                 }
-            }
 
-            // Always nest $and inside $or when both are present
-            let mut nested_and_obj = Map::new();
-            nested_and_obj.insert("$and".to_string(), and_value.unwrap());
-            
-            let mut or_val = or_value.unwrap();
-            if let Value::Array(ref mut or_array) = or_val {
-                or_array.push(Value::Object(nested_and_obj));
-            }
-            
-            let mut operations = HashMap::new();
-            operations.insert("$or".to_string(), or_val);
-            conditions.insert("$or".to_string(), QueryCondition::Operations(operations));
-
-            // Add other conditions
-            conditions.extend(other_conditions);
-        } else {
-            // Normal processing when both $and and $or are not present
-            for (key, value) in obj {
-                if matches!(key.as_str(), "$and" | "$or" | "$not" | "$nor" | "$where") {
-                    let mut operations = HashMap::new();
-                    operations.insert(key.clone(), value.clone());
-                    conditions.insert(key.clone(), QueryCondition::Operations(operations));
-                } else {
-                    let condition = Self::parse_condition(value)?;
-                    conditions.insert(key.clone(), condition);
+                let mut nested_and_obj = Map::new();
+                nested_and_obj.insert("$and".to_string(), obj["$and"].clone());
+
+                let mut or_val = obj["$or"].clone();
+                if let Value::Array(ref mut or_array) = or_val {
+                    or_array.push(Value::Object(nested_and_obj));
                 }
+
+                let mut operations = HashMap::new();
+                operations.insert("$or".to_string(), or_val);
+                conditions.insert("$or".to_string(), QueryCondition::Operations(operations));
+            } else if matches!(key.as_str(), "$and" | "$or" | "$not" | "$nor" | "$where" | "$function") {
+                let mut operations = HashMap::new();
+                operations.insert(key.clone(), value.clone());
+                conditions.insert(key.clone(), QueryCondition::Operations(operations));
+            } else {
+                let condition = Self::parse_condition(value)?;
+                if let (QueryCondition::Operations(_) | QueryCondition::Mixed { .. }, Value::Object(obj)) =
+                    (&condition, value)
+                {
+                    key_order.insert(key.clone(), obj.keys().cloned().collect());
+                }
+                conditions.insert(key.clone(), condition);
             }
         }
 
-        Ok(Query { conditions })
+        Ok(Query { conditions, key_order })
+    }
+
+    /// Convert the query back into its MongoDB-style JSON form
+    ///
+    /// Parsing the result with [`Query::from_value`] yields an equal query.
+    pub fn to_value(&self) -> Value {
+        if let (1, Some(QueryCondition::Value(value))) =
+            (self.conditions.len(), self.conditions.get(""))
+        {
+            return value.clone();
+        }
+
+        let mut obj = Map::new();
+        for (key, condition) in &self.conditions {
+            let value = match condition {
+                // Root-level operators such as $and are stored under their own name
+                QueryCondition::Operations(ops) if ops.len() == 1 && ops.contains_key(key) => {
+                    ops[key].clone()
+                }
+                _ => condition.to_value_in_order(self.key_order.get(key)),
+            };
+            obj.insert(key.clone(), value);
+        }
+        Value::Object(obj)
     }
 
     /// Parse a single condition value
@@ -115,7 +123,7 @@ impl Query {
             Value::Object(obj) => {
                 let mut operations = HashMap::new();
                 let mut has_operators = false;
-                let mut regular_fields = HashMap::new();
+                let mut regular_fields = Map::new();
 
                 for (key, val) in obj {
                     if key.starts_with('$') {
@@ -128,12 +136,8 @@ impl Query {
 
                 if has_operators && !regular_fields.is_empty() {
                     // Mixed operators and regular fields - treat regular fields as $eq operations
-                    let mut value_obj = Map::new();
-                    for (k, v) in regular_fields {
-                        value_obj.insert(k, v);
-                    }
                     Ok(QueryCondition::Mixed {
-                        value: Some(Value::Object(value_obj)),
+                        value: Some(Value::Object(regular_fields)),
                         operations,
                     })
                 } else if has_operators {
@@ -152,15 +156,59 @@ impl Query {
     }
 
     /// Compile the query with a specific context
+    ///
+    /// Operations are evaluated in the order their clauses appear in the source
+    /// query. With [`QueryOptions::reorder_by_cost`](crate::core::QueryOptions)
+    /// set, the root-level AND-ed operations, including the children of a root
+    /// `$and`, are instead sorted by [`estimate_cost`] so cheap checks can
+    /// short-circuit before expensive ones run.
     pub fn compile_with_context(&self, context: QueryContext) -> SiftResult<CompiledQuery> {
-        let mut operations: Vec<Box<dyn Operation>> = Vec::new();
+        let operations = self.compile_operations(&context)?;
+        Ok(CompiledQuery::new(operations, context))
+    }
+
+    /// Compile the query into AND-ed operations borrowing the given context
+    ///
+    /// Used by operators that embed sub-queries, such as `$or` and `$elemMatch`.
+    pub(crate) fn compile_operations(&self, context: &QueryContext) -> SiftResult<Vec<Box<dyn Operation>>> {
+        let mut operations = Vec::new();
+        self.collect_operations(context, &mut operations)?;
 
+        if context.options.reorder_by_cost {
+            // Stable sort keeps source order among operations of equal cost
+            operations.sort_by_key(|(cost, _)| *cost);
+        }
+
+        Ok(operations.into_iter().map(|(_, operation)| operation).collect())
+    }
+
+    /// Compile every condition, tagging each operation with its estimated cost
+    fn collect_operations(
+        &self,
+        context: &QueryContext,
+        operations: &mut Vec<(u32, Box<dyn Operation>)>,
+    ) -> SiftResult<()> {
         for (field_path, condition) in &self.conditions {
-            let field_operations = self.compile_condition(field_path, condition, &context)?;
+            if context.options.reorder_by_cost && field_path == "$and" {
+                if let QueryCondition::Operations(ops) = condition {
+                    if let Some(Value::Array(queries)) = ops.get("$and") {
+                        if queries.iter().all(Value::is_object) {
+                            // A root $and is equivalent to its children AND-ed at the root
+                            for query_value in queries {
+                                Query::from_value(query_value)?
+                                    .collect_operations(context, operations)?;
+                            }
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            let field_operations = self.compile_condition(field_path, condition, context)?;
             operations.extend(field_operations);
         }
 
-        Ok(CompiledQuery::new(operations, context))
+        Ok(())
     }
 
     /// Compile a single condition into operations
@@ -169,37 +217,38 @@ impl Query {
         field_path: &str,
         condition: &QueryCondition,
         context: &QueryContext,
-    ) -> SiftResult<Vec<Box<dyn Operation>>> {
-        let mut operations: Vec<Box<dyn Operation>> = Vec::new();
+    ) -> SiftResult<Vec<(u32, Box<dyn Operation>)>> {
+        let mut operations: Vec<(u32, Box<dyn Operation>)> = Vec::new();
 
         match condition {
             QueryCondition::Value(value) => {
                 // Direct value comparison (implicit $eq)
                 if let Some(eq_op) = context.registry.get("$eq") {
-                    let operation = eq_op.create_operation(value, &Value::Null)?;
-                    operations.push(Box::new(FieldOperation::new(
-                        field_path.to_string(),
-                        operation,
-                    )) as Box<dyn Operation>);
+                    let operation = eq_op.create_operation_with_context(value, &Value::Null, context)?;
+                    operations.push((
+                        estimate_cost("$eq", value),
+                        Box::new(FieldOperation::new(field_path.to_string(), operation)),
+                    ));
                 }
             }
             QueryCondition::Operations(ops) => {
-                for (op_name, op_value) in ops {
+                for (op_name, op_value) in in_source_order(ops, self.key_order.get(field_path)) {
                     if let Some(operator) = context.registry.get(op_name) {
-                        let operation = operator.create_operation(op_value, &Value::Null)?;
+                        let operation = operator.create_operation_with_context(op_value, &Value::Null, context)?;
+                        let cost = estimate_cost(op_name, op_value);
                         
                         // Special handling for logical operators that don't operate on specific fields
-                        if matches!(
-                            op_name.as_str(),
-                            "$and" | "$or" | "$nor" | "$where"
-                        ) {
-                            operations.push(operation);
+                        // $function applies to the whole document only at the root
+                        if matches!(op_name.as_str(), "$and" | "$or" | "$nor" | "$where")
+                            || (op_name == "$function" && field_path == "$function")
+                        {
+                            operations.push((cost, operation));
                         } else {
                             // $not can be both root-level and field-level, apply to field if field_path is not empty
-                            operations.push(Box::new(FieldOperation::new(
-                                field_path.to_string(),
-                                operation,
-                            )) as Box<dyn Operation>);
+                            operations.push((
+                                cost,
+                                Box::new(FieldOperation::new(field_path.to_string(), operation)),
+                            ));
                         }
                     } else {
                         return Err(SiftError::UnsupportedOperation(format!(
@@ -213,21 +262,21 @@ impl Query {
                 // Handle mixed value and operations
                 if let Some(val) = value {
                     if let Some(eq_op) = context.registry.get("$eq") {
-                        let operation = eq_op.create_operation(val, &Value::Null)?;
-                        operations.push(Box::new(FieldOperation::new(
-                            field_path.to_string(),
-                            operation,
-                        )) as Box<dyn Operation>);
+                        let operation = eq_op.create_operation_with_context(val, &Value::Null, context)?;
+                        operations.push((
+                            estimate_cost("$eq", val),
+                            Box::new(FieldOperation::new(field_path.to_string(), operation)),
+                        ));
                     }
                 }
 
-                for (op_name, op_value) in ops {
+                for (op_name, op_value) in in_source_order(ops, self.key_order.get(field_path)) {
                     if let Some(operator) = context.registry.get(op_name) {
-                        let operation = operator.create_operation(op_value, &Value::Null)?;
-                        operations.push(Box::new(FieldOperation::new(
-                            field_path.to_string(),
-                            operation,
-                        )) as Box<dyn Operation>);
+                        let operation = operator.create_operation_with_context(op_value, &Value::Null, context)?;
+                        operations.push((
+                            estimate_cost(op_name, op_value),
+                            Box::new(FieldOperation::new(field_path.to_string(), operation)),
+                        ));
                     } else {
                         return Err(SiftError::UnsupportedOperation(format!(
                             "Unknown operator: {}",
@@ -241,6 +290,13 @@ impl Query {
         Ok(operations)
     }
 
+    /// Report the fields, operators, nesting and estimated cost of this query without running it
+    ///
+    /// See [`QueryAnalysis`](crate::QueryAnalysis).
+    pub fn analyze(&self) -> crate::QueryAnalysis {
+        crate::analysis::analyze(&self.to_value())
+    }
+
     /// Test a value against this query directly (without compilation)
     pub fn test(&self, value: &Value) -> SiftResult<bool> {
         let compiled = self.compile()?;
@@ -254,6 +310,125 @@ impl Default for Query {
     }
 }
 
+impl QueryCondition {
+    /// Convert the condition back into the JSON value it was parsed from
+    ///
+    /// A condition on its own does not know the order its operators were
+    /// written in; [`Query::to_value`] restores that order.
+    pub fn to_value(&self) -> Value {
+        self.to_value_in_order(None)
+    }
+
+    /// Convert the condition back to JSON, placing the keys listed in `order` first
+    fn to_value_in_order(&self, order: Option<&Vec<String>>) -> Value {
+        let (fields, operations) = match self {
+            QueryCondition::Value(value) => return value.clone(),
+            QueryCondition::Operations(ops) => (None, ops),
+            QueryCondition::Mixed { value, operations } => (value.as_ref().and_then(Value::as_object), operations),
+        };
+        let mut obj = Map::new();
+        for key in order.into_iter().flatten() {
+            if let Some(value) = operations.get(key).or_else(|| fields.and_then(|fields| fields.get(key))) {
+                obj.insert(key.clone(), value.clone());
+            }
+        }
+        // Keys without a recorded position go last, literal fields before operators
+        for (key, value) in fields.into_iter().flatten().chain(in_source_order(operations, None)) {
+            if !obj.contains_key(key) {
+                obj.insert(key.clone(), value.clone());
+            }
+        }
+        Value::Object(obj)
+    }
+}
+
+/// The operations in the order their keys appear in `order`, then any others sorted by name
+fn in_source_order<'a>(
+    ops: &'a HashMap<String, Value>,
+    order: Option<&'a Vec<String>>,
+) -> impl Iterator<Item = (&'a String, &'a Value)> {
+    let listed = order.into_iter().flatten().filter_map(|key| ops.get_key_value(key));
+    let mut rest: Vec<_> = ops
+        .iter()
+        .filter(|(key, _)| !order.is_some_and(|order| order.contains(key)))
+        .collect();
+    rest.sort_by_key(|(key, _)| *key);
+    listed.chain(rest)
+}
+
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Query {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Query::from_value(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for QueryCondition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for QueryCondition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Query::parse_condition(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Cost tier for equality-style checks (`$eq`, `$ne`, `$exists`, `$type`, `$size`)
+pub const COST_EQUALITY: u32 = 1;
+/// Cost tier for ranges and membership (`$gt`, `$lte`, `$in`, `$all`, `$mod`, ...)
+pub const COST_RANGE: u32 = 2;
+/// Cost tier for `$regex`
+pub const COST_REGEX: u32 = 3;
+/// Cost tier for `$elemMatch`
+pub const COST_ELEM_MATCH: u32 = 4;
+/// Cost tier for `$where`
+pub const COST_WHERE: u32 = 5;
+
+/// Estimate the relative cost of evaluating an operator with the given parameters
+///
+/// Logical operators cost as much as the most expensive operator nested inside
+/// them. Operators this function does not know about, such as custom ones, are
+/// placed between `$regex` and `$elemMatch`.
+pub fn estimate_cost(op_name: &str, params: &Value) -> u32 {
+    match op_name {
+        "$eq" | "$ne" | "$exists" | "$type" | "$size" => COST_EQUALITY,
+        "$gt" | "$gte" | "$lt" | "$lte" | "$in" | "$nin" | "$all" | "$mod" => COST_RANGE,
+        "$regex" => COST_REGEX,
+        "$elemMatch" => nested_cost(params).max(COST_ELEM_MATCH),
+        "$where" => COST_WHERE,
+        "$and" | "$or" | "$nor" | "$not" => nested_cost(params),
+        _ => COST_REGEX,
+    }
+}
+
+/// Highest operator cost found anywhere inside a query value
+fn nested_cost(value: &Value) -> u32 {
+    match value {
+        Value::Array(items) => items.iter().map(nested_cost).max().unwrap_or(COST_EQUALITY),
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(key, val)| {
+                if key.starts_with('$') {
+                    estimate_cost(key, val)
+                } else {
+                    nested_cost(val)
+                }
+            })
+            .max()
+            .unwrap_or(COST_EQUALITY),
+        _ => COST_EQUALITY,
+    }
+}
+
 /// Wraps an operation to apply it to a specific field path
 pub struct FieldOperation {
     field_path: String,
@@ -396,4 +571,84 @@ mod tests {
         let value2 = json!({"age": 15, "status": "active"});
         assert!(!query.test(&value2).unwrap());
     }
+
+    #[test]
+    fn test_to_value_preserves_clause_order() {
+        let source = r#"{"zeta":1,"$or":[{"a":1}],"alpha":{"$gt":2,"$lt":5},"mid":"x"}"#;
+        let query: Query = serde_json::from_str(source).unwrap();
+
+        assert_eq!(serde_json::to_string(&query).unwrap(), source);
+        assert_eq!(
+            query.conditions.keys().collect::<Vec<_>>(),
+            vec!["zeta", "$or", "alpha", "mid"]
+        );
+    }
+
+    #[test]
+    fn test_to_value_with_nested_and_or() {
+        let query = Query::from_value(&json!({
+            "$and": [{"a": 1}],
+            "$or": [{"b": 2}]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            query.to_value(),
+            json!({"$or": [{"b": 2}, {"$and": [{"a": 1}]}]})
+        );
+        assert_eq!(Query::from_value(&query.to_value()).unwrap(), query);
+    }
+
+    #[test]
+    #[cfg(feature = "boa")]
+    fn test_operations_follow_source_order() {
+        let failing_where = "(function() { throw new Error('evaluated'); })()";
+        let doc = json!({"a": 2});
+
+        let eq_first = Query::from_value(&json!({"a": 1, "$where": failing_where})).unwrap();
+        assert!(!eq_first.test(&doc).unwrap());
+
+        let where_first = Query::from_value(&json!({"$where": failing_where, "a": 1})).unwrap();
+        assert!(where_first.test(&doc).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "boa")]
+    fn test_reorder_by_cost() {
+        use crate::core::QueryOptions;
+
+        let failing_where = "(function() { throw new Error('evaluated'); })()";
+        let options = QueryOptions {
+            reorder_by_cost: true,
+            ..QueryOptions::default()
+        };
+        let doc = json!({"a": 2, "name": "x"});
+
+        let query = Query::from_value(&json!({
+            "$where": failing_where,
+            "name": {"$regex": "^x"},
+            "a": 1
+        }))
+        .unwrap();
+        let compiled = query.compile_with_context(QueryContext::with_options(options.clone())).unwrap();
+        assert!(!compiled.test(&doc).unwrap());
+
+        // Children of a root $and are reordered alongside the other clauses
+        let query = Query::from_value(&json!({
+            "$and": [{"$where": failing_where}, {"a": {"$gt": 5}}]
+        }))
+        .unwrap();
+        let compiled = query.compile_with_context(QueryContext::with_options(options)).unwrap();
+        assert!(!compiled.test(&doc).unwrap());
+        assert!(query.test(&doc).is_err());
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        assert_eq!(estimate_cost("$eq", &json!(1)), COST_EQUALITY);
+        assert_eq!(estimate_cost("$lte", &json!(1)), COST_RANGE);
+        assert_eq!(estimate_cost("$not", &json!({"$regex": "a"})), COST_REGEX);
+        assert_eq!(estimate_cost("$or", &json!([{"a": 1}, {"$where": "true"}])), COST_WHERE);
+        assert_eq!(estimate_cost("$elemMatch", &json!({"a": 1})), COST_ELEM_MATCH);
+    }
 }
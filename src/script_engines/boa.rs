@@ -0,0 +1,496 @@
+use crate::core::WhereLimits;
+use crate::operation_modules::where_operation::{CompiledScript, ScriptEngine};
+use crate::{SiftError, SiftResult};
+use boa_engine::object::builtins::JsFunction;
+use boa_engine::object::IntegrityLevel;
+use boa_engine::property::{Attribute, PropertyDescriptor};
+use boa_engine::vm::RuntimeLimits;
+use boa_engine::{js_string, Context, JsError, JsObject, JsSymbol, JsValue, Script, Source};
+use serde_json::Value;
+#[cfg(target_arch = "wasm32")]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Poll, Waker};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum number of compiled `$where` functions kept per engine
+const MAX_CACHED_FUNCTIONS: usize = 256;
+
+/// Source of the ids engines cache compiled functions under
+static NEXT_SCRIPT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Instruction units the interpreter runs between budget and timeout checks
+const BUDGET_SLICE: u32 = 1024;
+
+/// Freezes every object reachable from the global object, and the global object
+/// itself apart from `__siftThis`, so a script cannot leave state behind for the
+/// scripts evaluated after it on the same thread
+const FREEZE_GLOBALS: &str = r#"(() => {
+    const seen = new Set([globalThis]);
+    const freeze = (value) => {
+        if ((typeof value !== 'object' && typeof value !== 'function') || value === null || seen.has(value)) {
+            return;
+        }
+        seen.add(value);
+        Object.freeze(value);
+        for (const key of Reflect.ownKeys(value)) {
+            const descriptor = Reflect.getOwnPropertyDescriptor(value, key);
+            freeze(descriptor.value);
+            freeze(descriptor.get);
+            freeze(descriptor.set);
+        }
+        freeze(Reflect.getPrototypeOf(value));
+    };
+    for (const key of Reflect.ownKeys(globalThis)) {
+        const descriptor = Reflect.getOwnPropertyDescriptor(globalThis, key);
+        if (key !== '__siftThis') {
+            Reflect.defineProperty(globalThis, key, { configurable: false });
+            if ('value' in descriptor) {
+                Reflect.defineProperty(globalThis, key, { writable: false });
+            }
+        }
+        freeze(descriptor.value);
+        freeze(descriptor.get);
+        freeze(descriptor.set);
+    }
+    freeze(Reflect.getPrototypeOf(globalThis));
+    Reflect.preventExtensions(globalThis);
+})()"#;
+
+/// Time a worker gets past the script's timeout before the caller gives up on it
+#[cfg(not(target_arch = "wasm32"))]
+const WATCHDOG_GRACE: Duration = Duration::from_millis(100);
+
+/// Most `$where` worker threads alive at once, abandoned ones included
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_WORKERS: usize = 64;
+
+/// Most abandoned workers still finishing a call before new scripts are refused
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_ABANDONED_WORKERS: usize = 4;
+
+// Boa contexts are not `Send`, so scripts are handed to worker threads, each
+// owning a context, which can be abandoned when they overrun
+#[cfg(not(target_arch = "wasm32"))]
+static IDLE_WORKERS: Mutex<Vec<Worker>> = Mutex::new(Vec::new());
+#[cfg(not(target_arch = "wasm32"))]
+static LIVE_WORKERS: AtomicUsize = AtomicUsize::new(0);
+#[cfg(not(target_arch = "wasm32"))]
+static ABANDONED_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    // There are no threads to hand scripts to, so they run on the caller
+    static WHERE_ENGINE: RefCell<WhereEngine> = RefCell::new(WhereEngine::new());
+}
+
+/// JavaScript `$where` engine backed by Boa
+///
+/// Sources may be an expression, a function body or a function, as in MongoDB
+/// and sift.js. The document is available as both `this` and `obj`, and only
+/// ECMAScript built-ins such as `Date`, `Math` and `JSON` are exposed. The
+/// built-ins are frozen, so scripts sharing a thread cannot affect each other.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoaEngine;
+
+impl ScriptEngine for BoaEngine {
+    fn compile(&self, source: &str, limits: &WhereLimits) -> SiftResult<Box<dyn CompiledScript>> {
+        let script = Arc::new(ScriptSource {
+            id: NEXT_SCRIPT_ID.fetch_add(1, Ordering::Relaxed),
+            source: source.to_string(),
+            limits: limits.clone(),
+        });
+        run_job(Job::Compile(script.clone()))?;
+        Ok(Box::new(BoaScript { script }))
+    }
+
+    fn name(&self) -> &'static str {
+        "boa"
+    }
+}
+
+/// A `$where` source and its limits, shared by a script and the jobs running it
+struct ScriptSource {
+    /// Key of the compiled function in every engine that has compiled it
+    id: u64,
+    source: String,
+    limits: WhereLimits,
+}
+
+/// Boa contexts are not `Send`, so a script is compiled once in each engine
+/// that evaluates it, which afterwards finds the function by the script's id
+struct BoaScript {
+    script: Arc<ScriptSource>,
+}
+
+impl CompiledScript for BoaScript {
+    fn evaluate(&self, value: &Value) -> SiftResult<bool> {
+        run_job(Job::Evaluate(self.script.clone(), value.clone()))
+    }
+}
+
+/// Work for a [`WhereEngine`], sent to the thread that owns it
+enum Job {
+    Compile(Arc<ScriptSource>),
+    Evaluate(Arc<ScriptSource>, Value),
+}
+
+impl Job {
+    fn run(self, engine: &mut WhereEngine) -> SiftResult<bool> {
+        let started = Instant::now();
+        match self {
+            Job::Compile(script) => engine.function(&script, started).map(|_| true),
+            Job::Evaluate(script, value) => engine.evaluate(&script, &value, started),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            Job::Compile(script) | Job::Evaluate(script, _) => script.limits.timeout,
+        }
+    }
+}
+
+/// A thread owning a [`WhereEngine`], with the channels to hand it jobs
+#[cfg(not(target_arch = "wasm32"))]
+struct Worker {
+    jobs: Sender<Job>,
+    results: Receiver<SiftResult<bool>>,
+    abandoned: Arc<AtomicBool>,
+}
+
+/// Counts a worker thread as live until it exits
+#[cfg(not(target_arch = "wasm32"))]
+struct LiveWorker {
+    abandoned: Arc<AtomicBool>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for LiveWorker {
+    fn drop(&mut self) {
+        if self.abandoned.load(Ordering::SeqCst) {
+            ABANDONED_WORKERS.fetch_sub(1, Ordering::SeqCst);
+        }
+        LIVE_WORKERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Worker {
+    /// Start a worker and wait until its engine is ready, so setup does not count against a timeout
+    fn spawn() -> SiftResult<Self> {
+        LIVE_WORKERS
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| (live < MAX_WORKERS).then_some(live + 1))
+            .map_err(|_| {
+                SiftError::ResourceLimitExceeded(format!("All {} $where workers are busy", MAX_WORKERS))
+            })?;
+
+        let (jobs, pending) = mpsc::channel::<Job>();
+        let (finished, results) = mpsc::channel();
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let live = LiveWorker {
+            abandoned: abandoned.clone(),
+        };
+        std::thread::Builder::new()
+            .name("sift-where".to_string())
+            .spawn(move || {
+                let _live = live;
+                let mut engine = WhereEngine::new();
+                if finished.send(Ok(true)).is_err() {
+                    return;
+                }
+                // Stops once the caller has dropped the worker and the running job is over
+                for job in pending {
+                    if finished.send(job.run(&mut engine)).is_err() {
+                        break;
+                    }
+                    if engine.aborted {
+                        // An abandoned script leaves interpreter frames behind
+                        engine = WhereEngine::new();
+                    }
+                }
+            })
+            .map_err(|e| SiftError::EvaluationError(format!("Failed to start $where worker: {}", e)))?;
+
+        results
+            .recv()
+            .map_err(|_| SiftError::EvaluationError("$where worker stopped unexpectedly".to_string()))??;
+        Ok(Worker { jobs, results, abandoned })
+    }
+
+    /// Give up on a worker still running a job; its thread exits once the job is over
+    fn abandon(self) {
+        // Counted before the thread can see the flag, so the count never drops below zero
+        ABANDONED_WORKERS.fetch_add(1, Ordering::SeqCst);
+        self.abandoned.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn idle_workers() -> MutexGuard<'static, Vec<Worker>> {
+    // Workers are only pushed and popped, so a poisoned pool is still consistent
+    IDLE_WORKERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Run a job on an idle worker, abandoning the worker if the job outlives its timeout
+///
+/// The engine checks the timeout itself between instruction slices, but cannot
+/// interrupt a single call into a built-in such as `"x".repeat(1e9)`. The
+/// caller still gets its error on time; the abandoned worker finishes the
+/// call in the background and exits. At most [`MAX_WORKERS`] threads run at
+/// once, and while [`MAX_ABANDONED_WORKERS`] abandoned ones are still running
+/// new jobs fail straight away.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_job(job: Job) -> SiftResult<bool> {
+    let abandoned = ABANDONED_WORKERS.load(Ordering::SeqCst);
+    if abandoned >= MAX_ABANDONED_WORKERS {
+        return Err(SiftError::ResourceLimitExceeded(format!(
+            "{} $where scripts are still running past their timeout",
+            abandoned
+        )));
+    }
+
+    let idle = idle_workers().pop();
+    let worker = match idle {
+        Some(worker) => worker,
+        None => Worker::spawn()?,
+    };
+
+    let timeout = job.timeout();
+    let stopped = || SiftError::EvaluationError("$where worker stopped unexpectedly".to_string());
+    worker.jobs.send(job).map_err(|_| stopped())?;
+    let result = match timeout {
+        Some(timeout) => worker.results.recv_timeout(timeout + WATCHDOG_GRACE),
+        None => worker.results.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+
+    match result {
+        Ok(result) => {
+            idle_workers().push(worker);
+            result
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            worker.abandon();
+            Err(SiftError::ResourceLimitExceeded(format!(
+                "$where exceeded its timeout of {:?}",
+                timeout.unwrap_or_default()
+            )))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(stopped()),
+    }
+}
+
+/// Run a job on this thread's engine; nothing can stop a built-in call that runs long
+#[cfg(target_arch = "wasm32")]
+fn run_job(job: Job) -> SiftResult<bool> {
+    WHERE_ENGINE.with(|engine| {
+        let mut engine = engine.borrow_mut();
+        let result = job.run(&mut engine);
+        if engine.aborted {
+            // An abandoned script leaves interpreter frames behind
+            *engine = WhereEngine::new();
+        }
+        result
+    })
+}
+
+/// A reusable Boa context together with the `$where` functions compiled in it
+struct WhereEngine {
+    context: Context,
+    /// Compiled functions by script id
+    functions: HashMap<u64, JsFunction>,
+    /// Symbol under which the function is attached to the document it is called on
+    method_key: JsSymbol,
+    /// Script calling the function as a method of the document, so it runs under the budget
+    runner: Script,
+    aborted: bool,
+}
+
+impl WhereEngine {
+    fn new() -> Self {
+        let mut context = Context::default();
+        let method_key = JsSymbol::new(Some(js_string!("siftWhere"))).expect("symbol space exhausted");
+
+        context
+            .register_global_property(js_string!("__siftKey"), method_key.clone(), Attribute::empty())
+            .expect("fresh context has no __siftKey");
+        context
+            .register_global_property(
+                js_string!("__siftCall"),
+                JsSymbol::new(Some(js_string!("siftCall"))).expect("symbol space exhausted"),
+                Attribute::empty(),
+            )
+            .expect("fresh context has no __siftCall");
+        context
+            .register_global_property(js_string!("__siftThis"), JsValue::undefined(), Attribute::WRITABLE)
+            .expect("fresh context has no __siftThis");
+        let runner = Script::parse(Source::from_bytes("__siftThis[__siftKey](__siftThis)"), None, &mut context)
+            .expect("runner script is valid");
+        context
+            .eval(Source::from_bytes(FREEZE_GLOBALS))
+            .expect("built-ins can be frozen");
+
+        WhereEngine {
+            context,
+            functions: HashMap::new(),
+            method_key,
+            runner,
+            aborted: false,
+        }
+    }
+
+    /// Get the compiled function for a script, compiling it on first use
+    fn function(&mut self, script: &ScriptSource, started: Instant) -> SiftResult<JsFunction> {
+        if let Some(function) = self.functions.get(&script.id) {
+            return Ok(function.clone());
+        }
+
+        if self.functions.len() >= MAX_CACHED_FUNCTIONS {
+            self.functions.clear();
+        }
+
+        let wrapper = self.parse(&script.source)?;
+        // The source could smuggle statements past the wrapper, so it runs under the limits too
+        let compiled = self
+            .run(&wrapper, &script.limits, started)?
+            .map_err(|e| SiftError::InvalidQuery(format!("Invalid $where expression: {}", e)))?;
+        let function = compiled
+            .as_object()
+            .cloned()
+            .and_then(JsFunction::from_object)
+            .ok_or_else(|| {
+                SiftError::InvalidQuery("$where expression did not compile to a function".to_string())
+            })?;
+        self.freeze(&function)
+            .map_err(|e| SiftError::InvalidQuery(format!("Invalid $where expression: {}", e)))?;
+
+        self.functions.insert(script.id, function.clone());
+        Ok(function)
+    }
+
+    /// Freeze a compiled function and its `prototype`, which every evaluation of it can reach
+    fn freeze(&mut self, function: &JsFunction) -> Result<(), JsError> {
+        function.set_integrity_level(IntegrityLevel::Frozen, &mut self.context)?;
+        if let Some(prototype) = function.get(js_string!("prototype"), &mut self.context)?.as_object() {
+            prototype.set_integrity_level(IntegrityLevel::Frozen, &mut self.context)?;
+        }
+        Ok(())
+    }
+
+    /// Parse a `$where` source into a script producing the wrapper function
+    ///
+    /// The source is tried first as an expression and then as a function body. An
+    /// expression that evaluates to a function, such as `function() { ... }` or
+    /// `doc => ...`, is called with `this` and its first argument bound to the document.
+    fn parse(&mut self, expression: &str) -> SiftResult<Script> {
+        // Line breaks keep trailing comments and `return` from swallowing the wrapper
+        let trimmed = expression.trim().trim_end_matches(';');
+        let as_expression = format!(
+            "(function(obj) {{ const __siftResult = (\n{}\n); \
+             if (typeof __siftResult !== 'function') return __siftResult; \
+             this[__siftCall] = __siftResult; return this[__siftCall](obj); }})",
+            trimmed
+        );
+
+        Script::parse(Source::from_bytes(&as_expression), None, &mut self.context).or_else(|error| {
+            let as_body = format!("(function(obj) {{\n{}\n}})", expression);
+            Script::parse(Source::from_bytes(&as_body), None, &mut self.context)
+                .map_err(|_| SiftError::InvalidQuery(format!("Invalid $where expression: {}", error)))
+        })
+    }
+
+    /// Call the script's function with `this` bound to the document
+    fn evaluate(&mut self, script: &ScriptSource, value: &Value, started: Instant) -> SiftResult<bool> {
+        let function = self.function(script, started)?;
+        let receiver = self.receiver(value)?;
+
+        receiver
+            .define_property_or_throw(
+                self.method_key.clone(),
+                PropertyDescriptor::builder()
+                    .value(function)
+                    .writable(false)
+                    .enumerable(false)
+                    .configurable(true),
+                &mut self.context,
+            )
+            .and_then(|_| {
+                self.context
+                    .global_object()
+                    .set(js_string!("__siftThis"), receiver, false, &mut self.context)
+            })
+            .map_err(|e| SiftError::EvaluationError(format!("Failed to bind document: {}", e)))?;
+
+        let runner = self.runner.clone();
+        let result = self
+            .run(&runner, &script.limits, started)?
+            .map_err(|e| SiftError::EvaluationError(format!("JavaScript execution error: {}", e)))?;
+
+        Ok(result.to_boolean())
+    }
+
+    /// Convert the document into the object used as `this`
+    fn receiver(&mut self, value: &Value) -> SiftResult<JsObject> {
+        if value.is_null() {
+            return Ok(JsObject::with_object_proto(self.context.intrinsics()));
+        }
+
+        JsValue::from_json(value, &mut self.context)
+            .and_then(|js_value| js_value.to_object(&mut self.context))
+            .map_err(|e| SiftError::EvaluationError(format!("Failed to convert document: {}", e)))
+    }
+
+    /// Run a script, enforcing the instruction budget and the timeout counted from `started` between slices
+    ///
+    /// Limit violations are returned as the outer error; script errors as the inner one.
+    fn run(&mut self, script: &Script, limits: &WhereLimits, started: Instant) -> SiftResult<Result<JsValue, JsError>> {
+        let mut runtime_limits = RuntimeLimits::default();
+        runtime_limits.set_loop_iteration_limit(limits.loop_iteration_limit);
+        runtime_limits.set_recursion_limit(limits.recursion_limit);
+        runtime_limits.set_stack_size_limit(limits.stack_size_limit);
+        self.context.set_runtime_limits(runtime_limits);
+
+        let mut spent: u64 = 0;
+        let mut task = std::task::Context::from_waker(Waker::noop());
+        let mut evaluation = std::pin::pin!(script.evaluate_async_with_budget(&mut self.context, BUDGET_SLICE));
+
+        let result = loop {
+            if let Poll::Ready(result) = evaluation.as_mut().poll(&mut task) {
+                break result;
+            }
+
+            spent += u64::from(BUDGET_SLICE);
+            let exceeded = if limits.instruction_budget.is_some_and(|budget| spent > budget) {
+                Some(format!("$where exceeded its instruction budget of {}", spent - u64::from(BUDGET_SLICE)))
+            } else if limits.timeout.is_some_and(|timeout| started.elapsed() > timeout) {
+                Some(format!("$where exceeded its timeout of {:?}", limits.timeout.unwrap_or_default()))
+            } else {
+                None
+            };
+
+            if let Some(message) = exceeded {
+                self.aborted = true;
+                return Err(SiftError::ResourceLimitExceeded(message));
+            }
+        };
+
+        match result {
+            Err(e) if e.as_native().is_some_and(|native| native.is_runtime_limit()) => {
+                Err(SiftError::ResourceLimitExceeded(format!("$where {}", e)))
+            }
+            result => Ok(result),
+        }
+    }
+}
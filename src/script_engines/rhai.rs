@@ -0,0 +1,104 @@
+use crate::core::WhereLimits;
+use crate::operation_modules::where_operation::{CompiledScript, ScriptEngine};
+use crate::{SiftError, SiftResult};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde_json::Value;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Name of the function the `$where` source is wrapped in
+const WHERE_FUNCTION: &str = "__sift_where";
+
+thread_local! {
+    // Deadline of the evaluation running on this thread, checked while it makes progress
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// `$where` engine backed by the embedded Rhai scripting language
+///
+/// The source is the body of a function whose result is the match: the
+/// document is available as both `this` and `obj`, e.g. `this.age > 25`.
+/// `print`, `debug` and `eval` are disabled. Loops are bounded by the
+/// instruction budget, as Rhai has no separate loop iteration limit, and
+/// `stack_size_limit` does not apply. `max_data_size` caps the length of
+/// strings, arrays and object maps.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RhaiEngine;
+
+impl ScriptEngine for RhaiEngine {
+    fn compile(&self, source: &str, limits: &WhereLimits) -> SiftResult<Box<dyn CompiledScript>> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(limits.instruction_budget.unwrap_or(0))
+            .set_max_call_levels(limits.recursion_limit)
+            .set_max_string_size(limits.max_data_size.unwrap_or(0))
+            .set_max_array_size(limits.max_data_size.unwrap_or(0))
+            .set_max_map_size(limits.max_data_size.unwrap_or(0))
+            .on_print(|_| {})
+            .on_debug(|_, _, _| {})
+            .disable_symbol("eval");
+
+        if let Some(timeout) = limits.timeout {
+            engine.on_progress(move |_| {
+                let expired = DEADLINE
+                    .with(|deadline| deadline.get())
+                    .is_some_and(|deadline| Instant::now() > deadline);
+                expired.then(|| format!("$where exceeded its timeout of {:?}", timeout).into())
+            });
+        }
+
+        let wrapped = format!("fn {}(obj) {{\n{}\n}}", WHERE_FUNCTION, source);
+        let ast = engine
+            .compile(&wrapped)
+            .map_err(|e| SiftError::InvalidQuery(format!("Invalid $where script: {}", e)))?;
+
+        Ok(Box::new(RhaiScript {
+            engine,
+            ast,
+            timeout: limits.timeout,
+        }))
+    }
+
+    fn name(&self) -> &'static str {
+        "rhai"
+    }
+}
+
+struct RhaiScript {
+    engine: Engine,
+    ast: AST,
+    timeout: Option<Duration>,
+}
+
+impl CompiledScript for RhaiScript {
+    fn evaluate(&self, value: &Value) -> SiftResult<bool> {
+        let mut document = rhai::serde::to_dynamic(value)
+            .map_err(|e| SiftError::EvaluationError(format!("Failed to convert document: {}", e)))?;
+        let obj = document.clone();
+
+        DEADLINE.with(|deadline| deadline.set(self.timeout.map(|timeout| Instant::now() + timeout)));
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut document);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, WHERE_FUNCTION, (obj,));
+        DEADLINE.with(|deadline| deadline.set(None));
+
+        match result {
+            Ok(result) if result.is_unit() => Ok(false),
+            Ok(result) => result.as_bool().map_err(|type_name| {
+                SiftError::EvaluationError(format!("$where script returned {} instead of a boolean", type_name))
+            }),
+            Err(e) => match *e {
+                EvalAltResult::ErrorTooManyOperations(_)
+                | EvalAltResult::ErrorStackOverflow(_)
+                | EvalAltResult::ErrorDataTooLarge(..) => {
+                    Err(SiftError::ResourceLimitExceeded(format!("$where {}", e)))
+                }
+                EvalAltResult::ErrorTerminated(reason, _) => {
+                    Err(SiftError::ResourceLimitExceeded(reason.to_string()))
+                }
+                _ => Err(SiftError::EvaluationError(format!("Rhai execution error: {}", e))),
+            },
+        }
+    }
+}
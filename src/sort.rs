@@ -0,0 +1,206 @@
+use crate::core::utils;
+use crate::{SiftError, SiftResult};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// A MongoDB-style sort specification such as `{"age": -1, "name": 1}`
+///
+/// Keys are compared in the order given. Values of different types follow
+/// MongoDB's ordering: missing and null, numbers, strings, objects, arrays,
+/// then booleans. RFC 3339 date strings sort chronologically, before all
+/// other strings, which sort by their text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sort {
+    keys: Vec<(String, SortOrder)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl Sort {
+    /// Parse a sort document, where each field maps to `1` or `-1`
+    pub fn from_value(spec: &Value) -> SiftResult<Self> {
+        let Value::Object(fields) = spec else {
+            return Err(SiftError::InvalidQuery("Sort must be an object".to_string()));
+        };
+
+        let keys = fields
+            .iter()
+            .map(|(path, direction)| match direction.as_i64() {
+                Some(1) => Ok((path.clone(), SortOrder::Ascending)),
+                Some(-1) => Ok((path.clone(), SortOrder::Descending)),
+                _ => Err(SiftError::InvalidQuery(format!(
+                    "Sort direction for '{}' must be 1 or -1",
+                    path
+                ))),
+            })
+            .collect::<SiftResult<_>>()?;
+
+        Ok(Sort { keys })
+    }
+
+    /// Compare two documents by the sort keys
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        for (path, order) in &self.keys {
+            let ordering = compare_sort_values(
+                utils::get_nested_value(a, path),
+                utils::get_nested_value(b, path),
+            );
+            let ordering = match order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Stable-sort documents in place
+    pub fn sort<T: std::borrow::Borrow<Value>>(&self, documents: &mut [T]) {
+        documents.sort_by(|a, b| self.compare(a.borrow(), b.borrow()));
+    }
+}
+
+/// Rank of a value's type in MongoDB's cross-type sort order
+fn type_rank(value: Option<&Value>) -> u8 {
+    match value {
+        None | Some(Value::Null) => 0,
+        Some(Value::Number(_)) => 1,
+        Some(Value::String(_)) => 2,
+        Some(Value::Object(_)) => 3,
+        Some(Value::Array(_)) => 4,
+        Some(Value::Bool(_)) => 5,
+    }
+}
+
+pub(crate) fn compare_sort_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    type_rank(a).cmp(&type_rank(b)).then_with(|| match (a, b) {
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (Some(Value::Array(a)), Some(Value::Array(b))) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare_sort_values(Some(a), Some(b)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Some(Value::Object(a)), Some(Value::Object(b))) => a
+            .iter()
+            .zip(b)
+            .map(|((a_key, a), (b_key, b))| {
+                a_key.cmp(b_key).then_with(|| compare_sort_values(Some(a), Some(b)))
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Some(Value::String(a)), Some(Value::String(b))) => compare_strings(a, b),
+        (Some(a), Some(b)) => utils::compare_numbers(a, b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    })
+}
+
+/// Dates first, chronologically, then the other strings by their text
+///
+/// Unlike [`utils::compare_values`], a date and a plain string never compare
+/// by text, which would make the order inconsistent and sorting panic.
+fn compare_strings(a: &str, b: &str) -> Ordering {
+    match (a.parse::<DateTime<Utc>>(), b.parse::<DateTime<Utc>>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sort_by_multiple_keys() {
+        let sort = Sort::from_value(&json!({"team": 1, "score": -1})).unwrap();
+        let mut documents = vec![
+            json!({"team": "b", "score": 1}),
+            json!({"team": "a", "score": 2}),
+            json!({"team": "b", "score": 3}),
+            json!({"team": "a", "score": 5}),
+        ];
+        sort.sort(&mut documents);
+
+        let order: Vec<_> = documents.iter().map(|d| (d["team"].clone(), d["score"].clone())).collect();
+        assert_eq!(
+            order,
+            vec![
+                (json!("a"), json!(5)),
+                (json!("a"), json!(2)),
+                (json!("b"), json!(3)),
+                (json!("b"), json!(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_across_types_and_missing_fields() {
+        let sort = Sort::from_value(&json!({"v": 1})).unwrap();
+        let mut documents = vec![
+            json!({"v": true}),
+            json!({"v": "x"}),
+            json!({"v": 10}),
+            json!({}),
+            json!({"v": 2.5}),
+            json!({"v": null}),
+        ];
+        sort.sort(&mut documents);
+
+        let values: Vec<_> = documents.iter().map(|d| d.get("v").cloned()).collect();
+        assert_eq!(
+            values,
+            vec![None, Some(json!(null)), Some(json!(2.5)), Some(json!(10)), Some(json!("x")), Some(json!(true))]
+        );
+    }
+
+    #[test]
+    fn test_sort_nested_paths_and_dates() {
+        let sort = Sort::from_value(&json!({"meta.created": -1})).unwrap();
+        let older = json!({"meta": {"created": "2023-01-05T00:00:00Z"}});
+        let newer = json!({"meta": {"created": "2024-01-01T00:00:00+02:00"}});
+        assert_eq!(sort.compare(&newer, &older), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_mixed_dates_and_strings_is_consistent() {
+        let sort = Sort::from_value(&json!({"v": 1})).unwrap();
+        let mut documents: Vec<Value> = (0..5000)
+            .map(|i| {
+                let day = i % 28 + 1;
+                let v = match i % 4 {
+                    0 => format!("2024-01-{:02}T{:02}:00:00+0{}:00", day, i % 24, i % 10),
+                    1 => format!("2024-01-{:02}", day),
+                    2 => format!("2024-01-{:02}T{:02}:00:00Z", day, (i * 7) % 24),
+                    _ => format!("note {}", i * 7919 % 5000),
+                };
+                json!({ "v": v })
+            })
+            .collect();
+        sort.sort(&mut documents);
+
+        for pair in documents.windows(2) {
+            assert_ne!(sort.compare(&pair[0], &pair[1]), Ordering::Greater);
+        }
+        // Dates sort before plain strings, including date-like ones that are not RFC 3339
+        assert_eq!(sort.compare(&json!({"v": "2024-01-02T00:00:00Z"}), &json!({"v": "2024-01-01"})), Ordering::Less);
+        assert_eq!(sort.compare(&json!({"v": "2025-01-01T00:00:00Z"}), &json!({"v": "a"})), Ordering::Less);
+        assert_eq!(sort.compare(&json!({"v": "2024-01-01T02:00:00+02:00"}), &json!({"v": "2024-01-01T00:00:00Z"})), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_invalid_sort() {
+        assert!(Sort::from_value(&json!({"a": 2})).is_err());
+        assert!(Sort::from_value(&json!({"a": "asc"})).is_err());
+        assert!(Sort::from_value(&json!(["a"])).is_err());
+    }
+}
@@ -0,0 +1,632 @@
+use crate::core::{test_all, utils, Operation, QueryContext};
+use crate::sort::compare_sort_values;
+use crate::{Query, SiftError, SiftResult};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// A MongoDB-style update document such as `{"$set": {"status": "done"}, "$inc": {"attempts": 1}}`
+///
+/// Supported operators are `$set`, `$unset`, `$inc`, `$mul`, `$min`, `$max`,
+/// `$rename`, `$currentDate`, `$push` (with `$each`, `$position` and `$slice`),
+/// `$addToSet` (with `$each`), `$pull` and `$pop`. A document without
+/// operators replaces the whole document, keeping its `_id`.
+pub struct Update {
+    kind: UpdateKind,
+}
+
+enum UpdateKind {
+    Replace(Map<String, Value>),
+    Operators(Vec<FieldUpdate>),
+}
+
+/// One operator applied to one field path
+struct FieldUpdate {
+    path: String,
+    action: Action,
+}
+
+enum Action {
+    Set(Value),
+    Unset,
+    Inc(Number),
+    Mul(Number),
+    Min(Value),
+    Max(Value),
+    Rename(String),
+    CurrentDate,
+    Push {
+        values: Vec<Value>,
+        position: Option<i64>,
+        slice: Option<i64>,
+    },
+    AddToSet(Vec<Value>),
+    Pull(PullCondition),
+    Pop(i64),
+}
+
+enum PullCondition {
+    Equals(Value),
+    // Operators such as `{"$gte": 6}` applied to each element's value
+    Value(Vec<Box<dyn Operation>>),
+    // A query such as `{"score": {"$lt": 5}}` applied to each element as a document
+    Document(Vec<Box<dyn Operation>>),
+}
+
+impl PullCondition {
+    fn matches(&self, element: &Value) -> SiftResult<bool> {
+        match self {
+            PullCondition::Equals(value) => Ok(utils::values_equal(element, value)),
+            PullCondition::Value(query) => {
+                let mut wrapper = Map::new();
+                wrapper.insert(PULL_FIELD.to_string(), element.clone());
+                test_all(query, &Value::Object(wrapper))
+            }
+            PullCondition::Document(query) => Ok(element.is_object() && test_all(query, element)?),
+        }
+    }
+}
+
+/// Field used to test a `$pull` value condition against a single element
+const PULL_FIELD: &str = "__sift_pull";
+
+/// Most nulls an update may add to pad an array up to the index it sets
+const MAX_ARRAY_PADDING: usize = 1024;
+
+fn invalid(message: String) -> SiftError {
+    SiftError::InvalidQuery(message)
+}
+
+impl Update {
+    /// Parse an update document
+    pub fn from_value(spec: &Value) -> SiftResult<Self> {
+        Self::from_value_with_context(spec, &QueryContext::new())
+    }
+
+    /// Parse an update document, compiling `$pull` conditions with `context`
+    ///
+    /// The context's options apply to those conditions as they do to queries,
+    /// e.g. `allow_where: false` rejects `$where` inside `$pull`.
+    pub fn from_value_with_context(spec: &Value, context: &QueryContext) -> SiftResult<Self> {
+        let Value::Object(fields) = spec else {
+            return Err(invalid("Update must be an object".to_string()));
+        };
+
+        let operators = fields.keys().filter(|key| key.starts_with('$')).count();
+        if operators == 0 {
+            return Ok(Update {
+                kind: UpdateKind::Replace(fields.clone()),
+            });
+        }
+        if operators != fields.len() {
+            return Err(invalid("Update cannot mix operators and fields".to_string()));
+        }
+
+        let mut updates = Vec::new();
+        for (operator, arguments) in fields {
+            let Value::Object(arguments) = arguments else {
+                return Err(invalid(format!("{} requires an object of fields", operator)));
+            };
+            for (path, argument) in arguments {
+                let action = parse_action(operator, path, argument, context)?;
+                updates.push(FieldUpdate {
+                    path: path.clone(),
+                    action,
+                });
+            }
+        }
+        check_paths(&updates)?;
+
+        Ok(Update {
+            kind: UpdateKind::Operators(updates),
+        })
+    }
+
+    /// Apply the update to a document, returning whether it changed
+    ///
+    /// The document is left untouched if any operator fails.
+    pub fn apply(&self, document: &mut Value) -> SiftResult<bool> {
+        let mut updated = document.clone();
+
+        match &self.kind {
+            UpdateKind::Replace(fields) => {
+                let id = document.get("_id");
+                if let (Some(id), Some(new_id)) = (id, fields.get("_id")) {
+                    if !utils::values_equal(id, new_id) {
+                        return Err(invalid("Replacement cannot change _id".to_string()));
+                    }
+                }
+                let mut replacement = Map::new();
+                if let Some(id) = id {
+                    replacement.insert("_id".to_string(), id.clone());
+                }
+                replacement.extend(fields.iter().map(|(key, value)| (key.clone(), value.clone())));
+                updated = Value::Object(replacement);
+            }
+            UpdateKind::Operators(updates) => {
+                for update in updates {
+                    apply_action(&mut updated, &update.path, &update.action)?;
+                }
+            }
+        }
+
+        if updated == *document {
+            return Ok(false);
+        }
+        *document = updated;
+        Ok(true)
+    }
+}
+
+fn parse_action(operator: &str, path: &str, argument: &Value, context: &QueryContext) -> SiftResult<Action> {
+    if path.is_empty() || path.split('.').any(str::is_empty) {
+        return Err(invalid(format!("Invalid field path '{}' in {}", path, operator)));
+    }
+    if path == "_id" || path.starts_with("_id.") {
+        return Err(invalid(format!("{} cannot modify _id", operator)));
+    }
+
+    let number = |argument: &Value| match argument {
+        Value::Number(n) => Ok(n.clone()),
+        _ => Err(invalid(format!("{} requires a number for '{}'", operator, path))),
+    };
+
+    Ok(match operator {
+        "$set" => Action::Set(argument.clone()),
+        "$unset" => Action::Unset,
+        "$inc" => Action::Inc(number(argument)?),
+        "$mul" => Action::Mul(number(argument)?),
+        "$min" => Action::Min(argument.clone()),
+        "$max" => Action::Max(argument.clone()),
+        "$rename" => match argument.as_str() {
+            Some(target) if target == "_id" || target.starts_with("_id.") => {
+                return Err(invalid("$rename cannot modify _id".to_string()))
+            }
+            Some(target) if !target.is_empty() && target != path => Action::Rename(target.to_string()),
+            _ => return Err(invalid(format!("$rename requires a new field name for '{}'", path))),
+        },
+        "$currentDate" => match argument {
+            Value::Bool(true) => Action::CurrentDate,
+            Value::Object(spec) if spec.get("$type").and_then(Value::as_str) == Some("date") => {
+                Action::CurrentDate
+            }
+            _ => return Err(invalid(format!("$currentDate requires true or {{\"$type\": \"date\"}} for '{}'", path))),
+        },
+        "$push" => parse_push(path, argument)?,
+        "$addToSet" => Action::AddToSet(parse_each("$addToSet", path, argument, &["$each"])?),
+        "$pull" => Action::Pull(parse_pull(argument, context)?),
+        "$pop" => match argument.as_i64() {
+            Some(direction @ (1 | -1)) => Action::Pop(direction),
+            _ => return Err(invalid(format!("$pop requires 1 or -1 for '{}'", path))),
+        },
+        _ => {
+            return Err(SiftError::UnsupportedOperation(format!(
+                "Unknown update operator: {}",
+                operator
+            )))
+        }
+    })
+}
+
+/// Values of `$push`/`$addToSet`, which take one value or `{"$each": [...]}`
+fn parse_each(operator: &str, path: &str, argument: &Value, modifiers: &[&str]) -> SiftResult<Vec<Value>> {
+    let Some(spec) = argument.as_object().filter(|spec| spec.contains_key("$each")) else {
+        return Ok(vec![argument.clone()]);
+    };
+    if let Some(modifier) = spec.keys().find(|key| !modifiers.contains(&key.as_str())) {
+        return Err(invalid(format!("Unsupported {} modifier '{}' for '{}'", operator, modifier, path)));
+    }
+    match &spec["$each"] {
+        Value::Array(values) => Ok(values.clone()),
+        _ => Err(invalid(format!("$each requires an array for '{}'", path))),
+    }
+}
+
+fn parse_push(path: &str, argument: &Value) -> SiftResult<Action> {
+    let values = parse_each("$push", path, argument, &["$each", "$position", "$slice"])?;
+    let modifier = |name: &str| match argument.get("$each").and(argument.get(name)) {
+        None => Ok(None),
+        Some(value) => value
+            .as_i64()
+            .map(Some)
+            .ok_or_else(|| invalid(format!("{} requires an integer for '{}'", name, path))),
+    };
+    Ok(Action::Push {
+        values,
+        position: modifier("$position")?,
+        slice: modifier("$slice")?,
+    })
+}
+
+fn parse_pull(argument: &Value, context: &QueryContext) -> SiftResult<PullCondition> {
+    let Value::Object(condition) = argument else {
+        return Ok(PullCondition::Equals(argument.clone()));
+    };
+
+    // Operators on the element itself, as opposed to a query over element documents
+    let is_value_condition = !condition.is_empty()
+        && condition
+            .keys()
+            .all(|key| key.starts_with('$') && !matches!(key.as_str(), "$and" | "$or" | "$nor" | "$where" | "$function"));
+
+    if is_value_condition {
+        let mut wrapper = Map::new();
+        wrapper.insert(PULL_FIELD.to_string(), argument.clone());
+        Ok(PullCondition::Value(Query::from_value(&Value::Object(wrapper))?.compile_operations(context)?))
+    } else {
+        Ok(PullCondition::Document(Query::from_value(argument)?.compile_operations(context)?))
+    }
+}
+
+/// Reject updates touching the same field twice, or a field and one of its sub-fields
+fn check_paths(updates: &[FieldUpdate]) -> SiftResult<()> {
+    let mut paths: Vec<&str> = Vec::new();
+    for update in updates {
+        paths.push(&update.path);
+        if let Action::Rename(target) = &update.action {
+            paths.push(target);
+        }
+    }
+
+    for (i, a) in paths.iter().enumerate() {
+        for b in &paths[i + 1..] {
+            let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+            if longer == shorter || longer.strip_prefix(*shorter).is_some_and(|rest| rest.starts_with('.')) {
+                return Err(invalid(format!("Updating '{}' and '{}' would conflict", a, b)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_action(document: &mut Value, path: &str, action: &Action) -> SiftResult<()> {
+    match action {
+        Action::Set(value) => set_path(document, path, value.clone()),
+        Action::Unset => {
+            remove_path(document, path);
+            Ok(())
+        }
+        Action::Inc(amount) => {
+            let current = get_path(document, path).cloned();
+            let result = match current {
+                None => Value::Number(amount.clone()),
+                Some(Value::Number(current)) => arithmetic(&current, amount, i64::checked_add, |a, b| a + b)
+                    .ok_or_else(|| invalid(format!("$inc overflows field '{}'", path)))?,
+                Some(_) => return Err(invalid(format!("Cannot apply $inc to non-numeric field '{}'", path))),
+            };
+            set_path(document, path, result)
+        }
+        Action::Mul(factor) => {
+            let current = get_path(document, path).cloned();
+            let current = match current {
+                // A missing field is created as zero of the factor's type
+                None => Number::from(0),
+                Some(Value::Number(current)) => current,
+                Some(_) => return Err(invalid(format!("Cannot apply $mul to non-numeric field '{}'", path))),
+            };
+            let result = arithmetic(&current, factor, i64::checked_mul, |a, b| a * b)
+                .ok_or_else(|| invalid(format!("$mul overflows field '{}'", path)))?;
+            set_path(document, path, result)
+        }
+        Action::Min(value) | Action::Max(value) => {
+            let wanted = if matches!(action, Action::Min(_)) { Ordering::Less } else { Ordering::Greater };
+            let replace = get_path(document, path)
+                .is_none_or(|current| compare_sort_values(Some(value), Some(current)) == wanted);
+            if replace {
+                set_path(document, path, value.clone())?;
+            }
+            Ok(())
+        }
+        Action::Rename(target) => {
+            if let Some(value) = remove_path(document, path) {
+                set_path(document, target, value)?;
+            }
+            Ok(())
+        }
+        Action::CurrentDate => set_path(document, path, Value::String(chrono::Utc::now().to_rfc3339())),
+        Action::Push { values, position, slice } => {
+            let items = array_at(document, path, "$push")?;
+            let len = items.len() as i64;
+            let index = match position {
+                None => len,
+                Some(position) if *position < 0 => (len + position).max(0),
+                Some(position) => (*position).min(len),
+            } as usize;
+            items.splice(index..index, values.iter().cloned());
+
+            match slice {
+                Some(slice) if *slice >= 0 => items.truncate(*slice as usize),
+                Some(slice) => {
+                    let keep = slice.unsigned_abs() as usize;
+                    if items.len() > keep {
+                        items.drain(..items.len() - keep);
+                    }
+                }
+                None => {}
+            }
+            Ok(())
+        }
+        Action::AddToSet(values) => {
+            let items = array_at(document, path, "$addToSet")?;
+            for value in values {
+                if !items.iter().any(|item| utils::values_equal(item, value)) {
+                    items.push(value.clone());
+                }
+            }
+            Ok(())
+        }
+        Action::Pull(condition) => {
+            let Some(current) = get_path_mut(document, path) else {
+                return Ok(());
+            };
+            let Value::Array(items) = current else {
+                return Err(invalid(format!("Cannot apply $pull to non-array field '{}'", path)));
+            };
+            let mut kept = Vec::with_capacity(items.len());
+            for item in items.drain(..) {
+                if !condition.matches(&item)? {
+                    kept.push(item);
+                }
+            }
+            *items = kept;
+            Ok(())
+        }
+        Action::Pop(direction) => {
+            match get_path_mut(document, path) {
+                None => {}
+                Some(Value::Array(items)) if items.is_empty() => {}
+                Some(Value::Array(items)) if *direction == 1 => {
+                    items.pop();
+                }
+                Some(Value::Array(items)) => {
+                    items.remove(0);
+                }
+                Some(_) => return Err(invalid(format!("Cannot apply $pop to non-array field '{}'", path))),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Combine two numbers, staying integral unless either side is a float or the result overflows
+///
+/// Returns `None` when even a float cannot hold the result.
+fn arithmetic(a: &Number, b: &Number, integer: fn(i64, i64) -> Option<i64>, float: fn(f64, f64) -> f64) -> Option<Value> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        if let Some(result) = integer(a, b) {
+            return Some(Value::from(result));
+        }
+    }
+    let result = float(a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+    Number::from_f64(result).map(Value::Number)
+}
+
+/// The array at `path`, created empty if the field is missing
+fn array_at<'a>(document: &'a mut Value, path: &str, operator: &str) -> SiftResult<&'a mut Vec<Value>> {
+    if get_path(document, path).is_none() {
+        set_path(document, path, Value::Array(Vec::new()))?;
+    }
+    match get_path_mut(document, path) {
+        Some(Value::Array(items)) => Ok(items),
+        _ => Err(invalid(format!("Cannot apply {} to non-array field '{}'", operator, path))),
+    }
+}
+
+fn get_path<'a>(document: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(document, |value, segment| match value {
+        Value::Object(obj) => obj.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn get_path_mut<'a>(document: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(document, |value, segment| match value {
+        Value::Object(obj) => obj.get_mut(segment),
+        Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Set the value at `path`, creating missing objects along the way
+///
+/// Numeric segments index into arrays, which are padded with nulls as needed,
+/// up to [`MAX_ARRAY_PADDING`] past their end.
+fn set_path(document: &mut Value, path: &str, value: Value) -> SiftResult<()> {
+    let mut current = document;
+    let mut segments = path.split('.').peekable();
+
+    while let Some(segment) = segments.next() {
+        let last = segments.peek().is_none();
+        let next = match current {
+            Value::Object(obj) => {
+                let slot = obj.entry(segment.to_string()).or_insert(Value::Null);
+                if slot.is_null() && !last {
+                    *slot = Value::Object(Map::new());
+                }
+                slot
+            }
+            Value::Array(items) => {
+                let Ok(index) = segment.parse::<usize>() else {
+                    return Err(invalid(format!("Cannot create field '{}' in an array at '{}'", segment, path)));
+                };
+                if items.len() <= index {
+                    if index - items.len() > MAX_ARRAY_PADDING {
+                        return Err(invalid(format!(
+                            "Index {} at '{}' is more than {} past the end of the array",
+                            index, path, MAX_ARRAY_PADDING
+                        )));
+                    }
+                    items.resize(index + 1, Value::Null);
+                }
+                let slot = &mut items[index];
+                if slot.is_null() && !last {
+                    *slot = Value::Object(Map::new());
+                }
+                slot
+            }
+            _ => {
+                return Err(invalid(format!("Cannot create field '{}' in a non-object value at '{}'", segment, path)))
+            }
+        };
+
+        if last {
+            *next = value;
+            return Ok(());
+        }
+        current = next;
+    }
+    Ok(())
+}
+
+/// Remove the value at `path`; array elements are set to null rather than shifted
+fn remove_path(document: &mut Value, path: &str) -> Option<Value> {
+    let (parent, field) = match path.rsplit_once('.') {
+        Some((parent, field)) => (get_path_mut(document, parent)?, field),
+        None => (document, path),
+    };
+    match parent {
+        Value::Object(obj) => obj.shift_remove(field),
+        Value::Array(items) => {
+            let item = items.get_mut(field.parse::<usize>().ok()?)?;
+            Some(std::mem::replace(item, Value::Null))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn update(spec: Value, mut document: Value) -> Value {
+        Update::from_value(&spec).unwrap().apply(&mut document).unwrap();
+        document
+    }
+
+    #[test]
+    fn test_field_operators() {
+        let document = json!({"_id": 1, "name": "Alice", "visits": 2, "price": 10, "tmp": true, "best": 5});
+        assert_eq!(
+            update(
+                json!({
+                    "$set": {"address.city": "Paris"},
+                    "$inc": {"visits": 1, "logins": 2},
+                    "$mul": {"price": 1.5},
+                    "$unset": {"tmp": ""},
+                    "$max": {"best": 7},
+                    "$rename": {"name": "fullName"}
+                }),
+                document
+            ),
+            json!({"_id": 1, "visits": 3, "price": 15.0, "best": 7, "address": {"city": "Paris"}, "logins": 2, "fullName": "Alice"})
+        );
+        assert_eq!(update(json!({"$min": {"best": 9}}), json!({"best": 5})), json!({"best": 5}));
+
+        // Dates order before plain strings, as in sorting
+        let seen = json!({"seen": "2024-05-01T00:00:00Z"});
+        assert_eq!(update(json!({"$max": {"seen": "2024-01-01"}}), seen.clone()), json!({"seen": "2024-01-01"}));
+        assert_eq!(update(json!({"$min": {"seen": "2024-01-01"}}), seen.clone()), seen);
+        assert_eq!(
+            update(json!({"$max": {"seen": "2024-05-01T03:00:00+02:00"}}), seen.clone()),
+            json!({"seen": "2024-05-01T03:00:00+02:00"})
+        );
+    }
+
+    #[test]
+    fn test_array_operators() {
+        let document = json!({"tags": ["a", "b"], "scores": [3, 8, 1, 9], "items": [{"qty": 1}, {"qty": 5}], "queue": [1, 2, 3]});
+        assert_eq!(
+            update(
+                json!({
+                    "$push": {"tags": {"$each": ["c", "d"], "$slice": -3}},
+                    "$pull": {"scores": {"$gte": 8}, "items": {"qty": {"$lt": 2}}},
+                    "$pop": {"queue": -1},
+                    "$addToSet": {"labels": {"$each": ["x", "x", "y"]}}
+                }),
+                document
+            ),
+            json!({"tags": ["b", "c", "d"], "scores": [3, 1], "items": [{"qty": 5}], "queue": [2, 3], "labels": ["x", "y"]})
+        );
+        assert_eq!(
+            update(json!({"$push": {"tags": {"$each": ["z"], "$position": 0}}, "$pull": {"n": 2}}), json!({"tags": ["a"], "n": [1, 2, 2]})),
+            json!({"tags": ["z", "a"], "n": [1]})
+        );
+    }
+
+    #[test]
+    fn test_replacement_keeps_id() {
+        assert_eq!(update(json!({"name": "Bob"}), json!({"_id": 7, "name": "Alice", "age": 3})), json!({"_id": 7, "name": "Bob"}));
+        let replace = Update::from_value(&json!({"_id": 8})).unwrap();
+        assert!(replace.apply(&mut json!({"_id": 7})).is_err());
+    }
+
+    #[test]
+    fn test_apply_reports_changes_and_is_atomic() {
+        let set = Update::from_value(&json!({"$set": {"a": 1}})).unwrap();
+        let mut document = json!({"a": 1});
+        assert!(!set.apply(&mut document).unwrap());
+
+        let failing = Update::from_value(&json!({"$set": {"b": 2}, "$inc": {"a": 1}})).unwrap();
+        let mut document = json!({"a": "text"});
+        assert!(failing.apply(&mut document).is_err());
+        assert_eq!(document, json!({"a": "text"}));
+    }
+
+    #[test]
+    fn test_overflowing_arithmetic_is_rejected() {
+        assert_eq!(update(json!({"$inc": {"a": 1}}), json!({"a": i64::MAX})), json!({"a": i64::MAX as f64 + 1.0}));
+
+        for spec in [json!({"$inc": {"a": f64::MAX}}), json!({"$mul": {"a": 2}})] {
+            let overflow = Update::from_value(&spec).unwrap();
+            let mut document = json!({"a": f64::MAX});
+            assert!(matches!(overflow.apply(&mut document), Err(SiftError::InvalidQuery(_))));
+            assert_eq!(document, json!({"a": f64::MAX}));
+        }
+    }
+
+    #[test]
+    fn test_array_padding_is_bounded() {
+        assert_eq!(update(json!({"$set": {"a.2": 1}}), json!({"a": [0]})), json!({"a": [0, null, 1]}));
+
+        for index in ["18446744073709551615", "1000000000"] {
+            let set = Update::from_value(&json!({"$set": {format!("a.{}", index): 1}})).unwrap();
+            let mut document = json!({"a": [0]});
+            assert!(matches!(set.apply(&mut document), Err(SiftError::InvalidQuery(_))));
+            assert_eq!(document, json!({"a": [0]}));
+        }
+    }
+
+    #[test]
+    fn test_pull_conditions_use_the_context_options() {
+        let spec = json!({"$pull": {"items": {"$where": "this.a === 1"}}});
+        let options = crate::QueryOptions {
+            allow_where: false,
+            ..crate::QueryOptions::default()
+        };
+        assert!(matches!(
+            Update::from_value_with_context(&spec, &QueryContext::with_options(options)),
+            Err(SiftError::UnsupportedOperation(_))
+        ));
+
+        let mut context = QueryContext::new();
+        context.register_function("isOne", |item: &Value| item["a"] == 1);
+        let pull = Update::from_value_with_context(&json!({"$pull": {"items": {"$where": {"$fn": "isOne"}}}}), &context).unwrap();
+        let mut document = json!({"items": [{"a": 1}, {"a": 2}]});
+        pull.apply(&mut document).unwrap();
+        assert_eq!(document, json!({"items": [{"a": 2}]}));
+    }
+
+    #[test]
+    fn test_invalid_updates() {
+        assert!(Update::from_value(&json!({"$set": {"a": 1}, "b": 2})).is_err());
+        assert!(Update::from_value(&json!({"$set": {"_id": 1}})).is_err());
+        assert!(Update::from_value(&json!({"$set": {"a": 1}, "$unset": {"a.b": ""}})).is_err());
+        assert!(Update::from_value(&json!({"$inc": {"a": "1"}})).is_err());
+        assert!(Update::from_value(&json!({"$set": 1})).is_err());
+        assert!(matches!(
+            Update::from_value(&json!({"$bogus": {"a": 1}})),
+            Err(SiftError::UnsupportedOperation(_))
+        ));
+    }
+}
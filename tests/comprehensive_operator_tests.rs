@@ -442,6 +442,7 @@ mod comprehensive_tests {
 
     // Test 9: $where Operator Tests
     #[test]
+    #[cfg(feature = "boa")]
     fn test_where_operator() {
         // Simple property comparison
         let simple_data = json!({"a": 10, "b": 5});
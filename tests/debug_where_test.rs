@@ -1,3 +1,5 @@
+#![cfg(feature = "boa")]
+
 use sift_rs::sift;
 use serde_json::json;
 
@@ -0,0 +1,119 @@
+use proptest::prelude::*;
+use serde_json::{json, Map, Value};
+use sift_rs::{Query, QueryCondition};
+
+fn scalar() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        (-1000i64..1000).prop_map(Value::from),
+        // Quarter steps are exact in binary, so text round trips stay lossless
+        (-4000i32..4000).prop_map(|n| Value::from(f64::from(n) / 4.0)),
+        "[a-z]{0,8}".prop_map(Value::String),
+    ]
+}
+
+fn field_name() -> impl Strategy<Value = String> {
+    prop_oneof!["[a-z]{1,6}", "[a-z]{1,4}\\.[a-z]{1,4}"]
+}
+
+/// Values usable on the right-hand side of a field: plain values, operator
+/// objects, and objects mixing operators with literal keys
+fn field_condition() -> impl Strategy<Value = Value> {
+    let operator = prop_oneof![
+        Just("$eq"),
+        Just("$ne"),
+        Just("$gt"),
+        Just("$gte"),
+        Just("$lt"),
+        Just("$lte"),
+    ];
+
+    prop_oneof![
+        scalar(),
+        prop::collection::vec(scalar(), 0..4).prop_map(Value::Array),
+        (operator, scalar()).prop_map(|(op, value)| json!({ op: value })),
+        prop::collection::vec(scalar(), 0..5).prop_map(|values| json!({"$in": values})),
+        any::<bool>().prop_map(|exists| json!({"$exists": exists})),
+        ("[a-z]{1,4}", scalar(), scalar())
+            .prop_map(|(key, literal, bound)| json!({ key: literal, "$gt": bound })),
+        ("[a-z]{1,4}", scalar(), scalar(), scalar())
+            .prop_map(|(key, literal, low, high)| json!({"$gt": low, key: literal, "$lt": high})),
+        prop::collection::btree_map("[a-z]{1,4}", scalar(), 1..3)
+            .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+    ]
+}
+
+fn query_value() -> impl Strategy<Value = Value> {
+    let leaf = prop::collection::vec((field_name(), field_condition()), 0..4)
+        .prop_map(|fields| Value::Object(fields.into_iter().collect::<Map<_, _>>()));
+
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        let logical = prop_oneof![Just("$and"), Just("$or"), Just("$nor")];
+        (
+            prop::collection::vec((field_name(), field_condition()), 0..3),
+            prop::collection::vec((logical, prop::collection::vec(inner.clone(), 1..3)), 0..3),
+            prop::option::of(inner),
+        )
+            .prop_map(|(fields, logicals, negated)| {
+                let mut obj: Map<String, Value> = fields.into_iter().collect();
+                for (op, queries) in logicals {
+                    obj.insert(op.to_string(), Value::Array(queries));
+                }
+                if let Some(query) = negated {
+                    obj.insert("$not".to_string(), query);
+                }
+                Value::Object(obj)
+            })
+    })
+}
+
+proptest! {
+    #[test]
+    fn round_trip_through_value(source in query_value()) {
+        let query = Query::from_value(&source).unwrap();
+        prop_assert_eq!(Query::from_value(&query.to_value()).unwrap(), query);
+    }
+
+    #[test]
+    fn round_trip_through_serde(source in query_value()) {
+        let query = Query::from_value(&source).unwrap();
+        let text = serde_json::to_string(&query).unwrap();
+        let parsed: Query = serde_json::from_str(&text).unwrap();
+        prop_assert_eq!(parsed, query);
+    }
+
+    #[test]
+    fn to_value_reproduces_source(source in query_value()) {
+        // The only rewrite applied while parsing is nesting a top-level $and inside $or
+        prop_assume!(!(source.get("$and").is_some() && source.get("$or").is_some()));
+        let query = Query::from_value(&source).unwrap();
+        // Value equality ignores key order, so compare the text as well
+        prop_assert_eq!(serde_json::to_string(&query.to_value()).unwrap(), serde_json::to_string(&source).unwrap());
+        prop_assert_eq!(query.to_value(), source);
+    }
+
+    #[test]
+    fn condition_round_trip(source in field_condition()) {
+        // Only a whole query keeps the operators in the order they were written
+        let condition: QueryCondition = serde_json::from_value(source.clone()).unwrap();
+        prop_assert_eq!(serde_json::to_value(&condition).unwrap(), source);
+    }
+}
+
+#[test]
+fn mixed_condition_keeps_interleaved_key_order() {
+    let source = json!({"age": {"$gte": 18, "unit": "years", "$lt": 65}});
+    let query = Query::from_value(&source).unwrap();
+    assert_eq!(
+        serde_json::to_string(&query).unwrap(),
+        r#"{"age":{"$gte":18,"unit":"years","$lt":65}}"#
+    );
+}
+
+#[test]
+fn non_object_query_round_trips() {
+    let query = Query::from_value(&json!(42)).unwrap();
+    assert_eq!(query.to_value(), json!(42));
+    assert_eq!(serde_json::from_value::<Query>(json!(42)).unwrap(), query);
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "rhai")]
+
+use serde_json::{json, Value};
+use sift_rs::{
+    Query, QueryContext, QueryOptions, RhaiEngine, ScriptEngine, SiftError, SiftResult, WhereLimits,
+    WhereOperator,
+};
+use std::time::Duration;
+
+fn rhai_context(options: QueryOptions) -> QueryContext {
+    let mut context = QueryContext::with_options(options);
+    context
+        .registry
+        .register("$where".to_string(), Box::new(WhereOperator::new(RhaiEngine)));
+    context
+}
+
+fn sift_rhai(query: &Value, value: &Value) -> SiftResult<bool> {
+    Query::from_value(query)?
+        .compile_with_context(rhai_context(QueryOptions::default()))?
+        .test(value)
+}
+
+#[test]
+fn test_rhai_expressions() {
+    let doc = json!({"a": 2, "b": 1.5, "name": "Alice", "tags": ["x", "y"], "nested": {"n": 3}});
+
+    assert!(sift_rhai(&json!({"$where": "this.a > this.b"}), &doc).unwrap());
+    assert!(sift_rhai(&json!({"$where": "obj.name.starts_with(\"A\")"}), &doc).unwrap());
+    assert!(sift_rhai(&json!({"$where": "this.tags.len() == 2 && \"y\" in this.tags"}), &doc).unwrap());
+    assert!(sift_rhai(&json!({"$where": "let n = this.nested.n; n * 2 == 6"}), &doc).unwrap());
+    assert!(!sift_rhai(&json!({"$where": "this.a > 5"}), &doc).unwrap());
+}
+
+#[test]
+fn test_rhai_errors() {
+    let query = Query::from_value(&json!({"$where": "this.a >"})).unwrap();
+    assert!(matches!(
+        query.compile_with_context(rhai_context(QueryOptions::default())),
+        Err(SiftError::InvalidQuery(_))
+    ));
+
+    assert!(matches!(
+        sift_rhai(&json!({"$where": "this.a + 1"}), &json!({"a": 1})),
+        Err(SiftError::EvaluationError(_))
+    ));
+}
+
+#[test]
+fn test_rhai_limits() {
+    assert!(matches!(
+        sift_rhai(&json!({"$where": "loop {}"}), &json!({})),
+        Err(SiftError::ResourceLimitExceeded(_))
+    ));
+
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            instruction_budget: None,
+            timeout: Some(Duration::from_millis(20)),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    let query = Query::from_value(&json!({"$where": "loop {}"})).unwrap();
+    let compiled = query.compile_with_context(rhai_context(options)).unwrap();
+    assert!(matches!(compiled.test(&json!({})), Err(SiftError::ResourceLimitExceeded(_))));
+}
+
+#[test]
+fn test_rhai_data_size_limits() {
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            max_data_size: Some(1000),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    let sources = [
+        r#"let s = "x"; for i in 0..20 { s += s; } s.len() > 0"#,
+        "let a = []; for i in 0..2000 { a.push(i); } a.len() > 0",
+        r#"let m = #{}; for i in 0..2000 { m[i.to_string()] = i; } m.len() > 0"#,
+    ];
+    for source in sources {
+        let query = Query::from_value(&json!({"$where": source})).unwrap();
+        let compiled = query.compile_with_context(rhai_context(options.clone())).unwrap();
+        assert!(
+            matches!(compiled.test(&json!({})), Err(SiftError::ResourceLimitExceeded(_))),
+            "{:?} should exceed the limit",
+            source
+        );
+    }
+
+    // Within the limit the same scripts run
+    let query = Query::from_value(&json!({"$where": "let a = []; for i in 0..10 { a.push(i); } a.len() == 10"})).unwrap();
+    assert!(query.compile_with_context(rhai_context(options)).unwrap().test(&json!({})).unwrap());
+}
+
+#[test]
+fn test_rhai_respects_allow_where() {
+    let options = QueryOptions {
+        allow_where: false,
+        ..QueryOptions::default()
+    };
+    let query = Query::from_value(&json!({"$or": [{"$where": "true"}]})).unwrap();
+    assert!(matches!(
+        query.compile_with_context(rhai_context(options)),
+        Err(SiftError::UnsupportedOperation(_))
+    ));
+}
+
+#[test]
+fn test_rhai_engine_name() {
+    assert_eq!(RhaiEngine.name(), "rhai");
+}
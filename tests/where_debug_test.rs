@@ -29,6 +29,7 @@ mod where_debug_tests {
     }
 
     #[test]
+    #[cfg(feature = "boa")]
     fn test_where_operator_debug() {
         let data = json!({
             "user": {
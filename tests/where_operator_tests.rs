@@ -0,0 +1,319 @@
+#![cfg(feature = "boa")]
+
+use serde_json::{json, Value};
+use sift_rs::{sift, Query, QueryContext, QueryOptions, SiftError, SiftResult, WhereLimits};
+use std::time::{Duration, Instant};
+
+fn sift_with_options(query: &Value, value: &Value, options: QueryOptions) -> SiftResult<bool> {
+    Query::from_value(query)?
+        .compile_with_context(QueryContext::with_options(options))?
+        .test(value)
+}
+
+#[test]
+fn test_where_syntax_error_fails_at_compile_time() {
+    let query = Query::from_value(&json!({"$where": "this.a >"})).unwrap();
+    assert!(matches!(query.compile(), Err(SiftError::InvalidQuery(_))));
+}
+
+#[test]
+fn test_where_compiled_query_is_reusable() {
+    let compiled = Query::from_value(&json!({"$where": "this.score * 2 > 10"}))
+        .unwrap()
+        .compile()
+        .unwrap();
+
+    let matches = (0..100)
+        .map(|score| json!({"score": score}))
+        .filter(|doc| compiled.test(doc).unwrap())
+        .count();
+    assert_eq!(matches, 94);
+}
+
+#[test]
+fn test_where_runtime_error_is_evaluation_error() {
+    let query = json!({"$where": "this.missing.field === 1"});
+    assert!(matches!(sift(&query, &json!({})), Err(SiftError::EvaluationError(_))));
+}
+
+#[test]
+fn test_where_uses_javascript_truthiness() {
+    assert!(!sift(&json!({"$where": "NaN"}), &json!({})).unwrap());
+    assert!(!sift(&json!({"$where": "this.name"}), &json!({"name": ""})).unwrap());
+    assert!(sift(&json!({"$where": "this.tags"}), &json!({"tags": []})).unwrap());
+}
+
+#[test]
+fn test_where_from_multiple_threads() {
+    let compiled = Query::from_value(&json!({"$where": "this.n % 2 === 0"}))
+        .unwrap()
+        .compile()
+        .unwrap();
+
+    std::thread::scope(|scope| {
+        for offset in 0..4 {
+            let compiled = &compiled;
+            scope.spawn(move || {
+                assert!(compiled.test(&json!({"n": offset * 2})).unwrap());
+                assert!(!compiled.test(&json!({"n": offset * 2 + 1})).unwrap());
+            });
+        }
+    });
+}
+
+#[test]
+fn test_where_infinite_loop_hits_resource_limit() {
+    let query = json!({"$where": "(() => { while (true) {} })()"});
+    assert!(matches!(sift(&query, &json!({})), Err(SiftError::ResourceLimitExceeded(_))));
+
+    // The engine is usable again after an aborted script
+    assert!(sift(&json!({"$where": "this.a === 1"}), &json!({"a": 1})).unwrap());
+}
+
+#[test]
+fn test_where_deep_recursion_is_limited() {
+    let query = json!({"$where": "(function f(n) { return f(n + 1); })(0)"});
+    assert!(matches!(sift(&query, &json!({})), Err(SiftError::ResourceLimitExceeded(_))));
+}
+
+#[test]
+fn test_where_instruction_budget() {
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            instruction_budget: Some(10_000),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    let query = json!({"$where": "(() => { let s = 0; for (let i = 0; i < 100000; i++) s += i; return s > 0; })()"});
+    assert!(matches!(
+        sift_with_options(&query, &json!({}), options),
+        Err(SiftError::ResourceLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_where_timeout() {
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            loop_iteration_limit: u64::MAX,
+            instruction_budget: None,
+            timeout: Some(Duration::from_millis(20)),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    let query = json!({"$where": "(() => { while (true) {} })()"});
+    assert!(matches!(
+        sift_with_options(&query, &json!({}), options),
+        Err(SiftError::ResourceLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_where_timeout_interrupts_long_builtin_calls() {
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            timeout: Some(Duration::from_millis(50)),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    // A single native call the interpreter cannot interrupt
+    let query = json!({"$where": "new Array(1e6).fill(0).sort().length > 0"});
+    let started = Instant::now();
+    assert!(matches!(
+        sift_with_options(&query, &json!({}), options),
+        Err(SiftError::ResourceLimitExceeded(_))
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+
+    // A new worker takes over from the abandoned one
+    assert!(sift(&json!({"$where": "this.a === 1"}), &json!({"a": 1})).unwrap());
+}
+
+#[test]
+fn test_where_can_be_disabled() {
+    let options = QueryOptions {
+        allow_where: false,
+        ..QueryOptions::default()
+    };
+
+    let query = json!({"$where": "true"});
+    assert!(matches!(
+        sift_with_options(&query, &json!({}), options.clone()),
+        Err(SiftError::UnsupportedOperation(_))
+    ));
+
+    // Nested sub-queries are compiled with the same options
+    let nested = json!({"$or": [{"a": 1}, {"$where": "true"}]});
+    assert!(matches!(
+        sift_with_options(&nested, &json!({"a": 1}), options.clone()),
+        Err(SiftError::UnsupportedOperation(_))
+    ));
+
+    let in_elem_match = json!({"items": {"$elemMatch": {"$where": "true"}}});
+    assert!(matches!(
+        sift_with_options(&in_elem_match, &json!({"items": [{}]}), options),
+        Err(SiftError::UnsupportedOperation(_))
+    ));
+}
+
+#[test]
+fn test_where_exposes_no_host_apis() {
+    for global in ["console", "require", "process", "fetch", "setTimeout"] {
+        let query = json!({"$where": format!("typeof {} === 'undefined'", global)});
+        assert!(sift(&query, &json!({})).unwrap(), "{} should not be defined", global);
+    }
+}
+
+#[test]
+fn test_where_on_non_object_values() {
+    assert!(sift(&json!({"$where": "this.length === 3"}), &json!("abc")).unwrap());
+    assert!(sift(&json!({"$where": "Object.keys(this).length === 0"}), &Value::Null).unwrap());
+}
+
+#[test]
+fn test_where_function_syntax() {
+    let doc = json!({"a": 2});
+    let matching = [
+        "function() { return this.a > 1 }",
+        "function () {\n  return obj.a > 1;\n}",
+        "  function named(doc) { return doc.a > 1; }  ",
+        "function() { return this.a > 1 };",
+        "(function() { return this.a > 1 })",
+        "doc => doc.a > 1",
+    ];
+    for source in matching {
+        assert!(sift(&json!({"$where": source}), &doc).unwrap(), "{:?} should match", source);
+    }
+
+    // A function that returns a falsy value no longer matches just by being a function
+    assert!(!sift(&json!({"$where": "function() { return this.a > 5 }"}), &doc).unwrap());
+    assert!(!sift(&json!({"$where": "function() {}"}), &doc).unwrap());
+}
+
+#[test]
+fn test_where_function_bodies_and_expressions() {
+    let doc = json!({"a": 2});
+    let matching = [
+        "this.a > 1",
+        "obj.a > 1",
+        "this.a > 1;",
+        "this.a > 1 // trailing comment",
+        "\nthis.a > 1\n",
+        "return this.a > 1",
+        "var doubled = obj.a * 2;\nreturn doubled === 4;",
+        "if (this.a > 1) { return true; } return false;",
+    ];
+    for source in matching {
+        assert!(sift(&json!({"$where": source}), &doc).unwrap(), "{:?} should match", source);
+    }
+    assert!(!sift(&json!({"$where": "return obj.a > 5"}), &doc).unwrap());
+}
+
+#[test]
+fn test_where_function_syntax_is_limited() {
+    let query = json!({"$where": "function() { while (true) {} }"});
+    assert!(matches!(sift(&query, &json!({})), Err(SiftError::ResourceLimitExceeded(_))));
+}
+
+#[test]
+fn test_where_scripts_cannot_share_state() {
+    let docs = [json!({"a": 1}), json!({"a": 2}), json!({"a": 3}), json!({"a": 4})];
+    let counters = [
+        "globalThis.n = (globalThis.n || 0) + 1; return globalThis.n <= 2",
+        "var f = arguments.callee; f.n = (f.n || 0) + 1; return f.n <= 2",
+        "var p = arguments.callee.prototype; p.n = (p.n || 0) + 1; return p.n <= 2",
+        "Math.n = (Math.n || 0) + 1; return Math.n <= 2",
+    ];
+    for source in counters {
+        let compiled = Query::from_value(&json!({"$where": source})).unwrap().compile().unwrap();
+        let results: Vec<_> = docs.iter().map(|doc| compiled.test(doc).ok()).collect();
+        assert!(results.iter().all(|result| *result == results[0]), "{:?} gave {:?}", source, results);
+    }
+
+    // A script cannot change what later scripts see through the built-ins
+    let polluting = json!({"$and": [
+        {"$where": "Object.prototype.admin = true; Array.prototype.admin = true; return true"},
+        {"$where": "this.admin === true || [].admin === true"},
+    ]});
+    assert!(!sift(&polluting, &json!({"a": 1})).unwrap());
+    assert!(!sift(&json!({"$where": "({}).admin === true"}), &json!({})).unwrap());
+
+    let redefining = json!({"$where": "JSON.stringify = () => '1'; globalThis.Math = null; return true"});
+    assert!(sift(&redefining, &json!({})).unwrap());
+    assert!(sift(&json!({"$where": "JSON.stringify(this) === '{\"a\":1}' && Math.abs(-1) === 1"}), &json!({"a": 1})).unwrap());
+}
+
+#[test]
+fn test_where_helper_globals() {
+    let doc = json!({
+        "created": "2023-06-15T12:00:00Z",
+        "scores": [72, 95, 88],
+        "tags": ["a", "b"],
+        "nested": {"x": 1}
+    });
+    let matching = [
+        "new Date(this.created) < new Date('2024-01-01')",
+        "new Date(this.created).getUTCFullYear() === 2023",
+        "Date.parse(obj.created) === Date.UTC(2023, 5, 15, 12)",
+        "Math.max(...this.scores) === 95",
+        "Math.abs(-this.scores[0]) === 72 && Math.floor(7.9) === 7",
+        "JSON.stringify(this.tags) === '[\"a\",\"b\"]'",
+        "JSON.parse('{\"x\":1}').x === this.nested.x",
+        "JSON.stringify(this.nested) === '{\"x\":1}'",
+        "Object.keys(this).length === 4",
+    ];
+    for source in matching {
+        assert!(sift(&json!({"$where": source}), &doc).unwrap(), "{:?} should match", source);
+    }
+}
+
+fn vip_context(options: QueryOptions) -> QueryContext {
+    let mut context = QueryContext::with_options(options);
+    context.register_function("isVip", |doc: &Value| {
+        doc["tier"] == "gold" || doc["spend"].as_f64().unwrap_or(0.0) > 1000.0
+    });
+    context
+}
+
+#[test]
+fn test_where_native_function() {
+    let query = Query::from_value(&json!({"$where": {"$fn": "isVip"}})).unwrap();
+    let compiled = query.compile_with_context(vip_context(QueryOptions::default())).unwrap();
+
+    assert!(compiled.test(&json!({"tier": "gold"})).unwrap());
+    assert!(compiled.test(&json!({"spend": 1500})).unwrap());
+    assert!(!compiled.test(&json!({"tier": "silver", "spend": 10})).unwrap());
+    assert_eq!(query.to_value(), json!({"$where": {"$fn": "isVip"}}));
+}
+
+#[test]
+fn test_where_native_function_in_nested_queries() {
+    let query = Query::from_value(&json!({
+        "$or": [{"admin": true}, {"$where": {"$fn": "isVip"}}],
+        "orders": {"$elemMatch": {"$function": {"name": "isVip"}}}
+    }))
+    .unwrap();
+    // Native functions are trusted, so disabling JavaScript does not reject them
+    let options = QueryOptions {
+        allow_where: false,
+        ..QueryOptions::default()
+    };
+    let compiled = query.compile_with_context(vip_context(options)).unwrap();
+
+    assert!(compiled.test(&json!({"tier": "gold", "orders": [{"spend": 2000}]})).unwrap());
+    assert!(!compiled.test(&json!({"tier": "gold", "orders": [{"spend": 20}]})).unwrap());
+    assert!(!compiled.test(&json!({"orders": [{"spend": 2000}]})).unwrap());
+}
+
+#[test]
+fn test_where_unknown_native_function() {
+    let query = Query::from_value(&json!({"$where": {"$fn": "isVip"}})).unwrap();
+    assert!(matches!(query.compile(), Err(SiftError::InvalidQuery(_))));
+    assert!(matches!(
+        sift(&json!({"$where": {"$fn": 5}}), &json!({})),
+        Err(SiftError::InvalidQuery(_))
+    ));
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "boa")]
+// Kept apart from the other `$where` tests: the workers this abandons stay busy
+// for a while and make every `$where` in the process fail until they finish
+
+use serde_json::json;
+use sift_rs::{Query, QueryContext, QueryOptions, SiftError, WhereLimits};
+use std::time::{Duration, Instant};
+
+/// Number of live `$where` worker threads in this process
+#[cfg(target_os = "linux")]
+fn where_threads() -> usize {
+    std::fs::read_dir("/proc/self/task")
+        .unwrap()
+        .filter_map(|task| std::fs::read_to_string(task.unwrap().path().join("comm")).ok())
+        .filter(|name| name.trim() == "sift-where")
+        .count()
+}
+
+#[test]
+fn test_abandoned_where_workers_are_bounded() {
+    let options = QueryOptions {
+        where_limits: WhereLimits {
+            timeout: Some(Duration::from_millis(20)),
+            ..WhereLimits::default()
+        },
+        ..QueryOptions::default()
+    };
+    // A single native call the interpreter cannot interrupt, which outlives every timeout below
+    let query = Query::from_value(&json!({"$where": "new Array(1e6).fill(0).sort().length > 0"})).unwrap();
+    let compiled = query.compile_with_context(QueryContext::with_options(options)).unwrap();
+
+    let mut refused = 0;
+    for _ in 0..12 {
+        let started = Instant::now();
+        let result = compiled.test(&json!({}));
+        assert!(matches!(result, Err(SiftError::ResourceLimitExceeded(_))), "{:?}", result);
+        // Once enough workers are abandoned, new scripts fail without waiting for a timeout
+        if started.elapsed() < Duration::from_millis(20) {
+            refused += 1;
+        }
+        #[cfg(target_os = "linux")]
+        assert!(where_threads() <= 5, "{} $where threads", where_threads());
+    }
+    assert!(refused >= 6, "only {} scripts were refused", refused);
+}
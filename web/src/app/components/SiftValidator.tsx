@@ -12,6 +12,11 @@ interface ValidationResponse {
   valid: boolean;
 }
 
+interface ValidationItemResult {
+  valid?: boolean;
+  error?: { code: string; message: string; path: string };
+}
+
 export const SiftValidator: React.FC = () => {
   const [jsonInput, setJsonInput] = useState<string>(`{
   "name": "Alice",
@@ -60,8 +65,12 @@ export const SiftValidator: React.FC = () => {
         throw new Error(errorData.message || `HTTP error! status: ${response.status}`);
       }
 
-      const results = await response.json();
-      setValidationResult(results[0]);
+      const results: ValidationItemResult[] = await response.json();
+      const [result] = results;
+      if (result.error) {
+        throw new Error(result.error.message);
+      }
+      setValidationResult({ valid: result.valid === true });
     } catch (error) {
       console.error('Validation error:', error);
       setValidationError(error instanceof Error ? error.message : 'Unknown error occurred');